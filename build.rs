@@ -1,5 +1,28 @@
 extern crate prost_build;
 
 fn main() {
-    prost_build::compile_protos(&["proto/book.proto"], &["proto/"]).unwrap();
+    let mut config = prost_build::Config::new();
+    // the book has no maps, but any added later are ordered, so that encoding the same book
+    // always yields the same bytes
+    config.btree_map(["."]);
+    #[cfg(feature = "serde")]
+    let descriptor_path =
+        std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap()).join("book.bin");
+    #[cfg(feature = "serde")]
+    config.file_descriptor_set_path(&descriptor_path);
+    config
+        .compile_protos(&["proto/book.proto"], &["proto/"])
+        .unwrap();
+
+    // serde implementations following the canonical JSON mapping of proto3
+    #[cfg(feature = "serde")]
+    {
+        let descriptor_set = std::fs::read(descriptor_path).unwrap();
+        pbjson_build::Builder::new()
+            .register_descriptors(&descriptor_set)
+            .unwrap()
+            .btree_map(["."])
+            .build(&[".protobook"])
+            .unwrap();
+    }
 }
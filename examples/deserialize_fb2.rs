@@ -15,7 +15,7 @@ fn main() {
         .iter()
         .map(|binary| (binary.id.clone(), Uuid::new_v4()))
         .collect();
-    let book = Book::from_fb2(book, book_id, &binary_ids);
+    let book = Book::from_fb2(book, book_id, &binary_ids).unwrap();
 
     println!("{}", book.id);
     println!("{}", book.short_title);
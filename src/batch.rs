@@ -0,0 +1,417 @@
+use prost::Message;
+use rayon::iter::{ParallelBridge, ParallelIterator};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use uuid::Uuid;
+use walkdir::WalkDir;
+use zip::ZipArchive;
+
+use crate::{
+    Book, Container, ConvertOptions, ConvertReport, ImportError, LimitError, Resource, ResourceSink,
+};
+
+/// Name of the summary of a batch conversion in its output directory
+pub const SUMMARY_FILE: &str = "summary.jsonl";
+
+/// Options of a conversion of many books
+#[derive(Clone, Debug, Default)]
+pub struct BatchOptions {
+    /// Options of the conversion of each book
+    pub convert: ConvertOptions,
+    /// Number of books converted at once. The number of CPUs for 0, by default
+    pub threads: usize,
+}
+
+/// Numbers of books of a batch conversion by their outcome
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchSummary {
+    pub converted: usize,
+    pub failed: usize,
+    /// Books that an earlier run into the same output has converted or failed to convert
+    pub skipped: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchStatus {
+    Converted,
+    Failed,
+}
+
+/// Outcome of a book of a batch conversion, a line of its summary
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchEntry {
+    /// Path of the book relative to the source, where books of a zip archive follow the path of
+    /// the archive, such as "library.zip/book.fb2"
+    pub source: String,
+    pub status: BatchStatus,
+    /// Path of the container relative to the output, if the book was converted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    /// Why the book wasn't converted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Problems of the converted book, such as rejected links
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub diagnostics: Vec<String>,
+}
+
+/// Converts the FB2 books of a directory, including the books of zip archives in it, or of a
+/// single zip archive into [`Container`]s in the output directory, at the paths of the books with
+/// the "pbook" extension.
+///
+/// Books are converted in parallel and read one by one, so that only the books being converted
+/// are held in memory. The outcome of each book is appended to [`SUMMARY_FILE`] in the output as
+/// soon as it's known, so that an interrupted conversion is resumed by running it again: the books
+/// that the summary has are skipped.
+pub fn convert_library(
+    source: &Path,
+    output: &Path,
+    options: &BatchOptions,
+) -> io::Result<BatchSummary> {
+    fs::create_dir_all(output)?;
+    let summary_path = output.join(SUMMARY_FILE);
+    let done = read_summary(&summary_path)?;
+    let mut file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(&summary_path)?;
+    // an interrupted run may have left a partial line
+    if file.metadata()?.len() > 0 {
+        let mut last = [0];
+        file.seek(SeekFrom::End(-1))?;
+        file.read_exact(&mut last)?;
+        if last[0] != b'\n' {
+            file.write_all(b"\n")?;
+        }
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(options.threads)
+        .build()
+        .map_err(io::Error::other)?;
+    let skipped = AtomicUsize::new(0);
+    let summary = Mutex::new(Summary {
+        file,
+        counts: BatchSummary::default(),
+    });
+    let jobs = Jobs {
+        root: source.to_path_buf(),
+        walk: WalkDir::new(source).sort_by_file_name().into_iter(),
+        archive: None,
+        done: &done,
+        skipped: &skipped,
+        max_size: options.convert.limits.max_resource_size,
+    };
+    pool.install(|| {
+        jobs.par_bridge().try_for_each(|job| {
+            let entry = job.convert(output, &options.convert);
+            summary.lock().unwrap().record(&entry)
+        })
+    })?;
+
+    let mut counts = summary.into_inner().unwrap().counts;
+    counts.skipped = skipped.into_inner();
+    Ok(counts)
+}
+
+/// Sources of the books that the summary has, ignoring a partial line of an interrupted run
+fn read_summary(path: &Path) -> io::Result<HashSet<String>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashSet::new()),
+        Err(e) => return Err(e),
+    };
+    let mut done = HashSet::new();
+    for line in BufReader::new(file).lines() {
+        if let Ok(entry) = serde_json::from_str::<BatchEntry>(&line?) {
+            done.insert(entry.source);
+        }
+    }
+    Ok(done)
+}
+
+struct Summary {
+    file: File,
+    counts: BatchSummary,
+}
+
+impl Summary {
+    fn record(&mut self, entry: &BatchEntry) -> io::Result<()> {
+        let mut line = serde_json::to_vec(entry).map_err(io::Error::other)?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+        match entry.status {
+            BatchStatus::Converted => self.counts.converted += 1,
+            BatchStatus::Failed => self.counts.failed += 1,
+        }
+        Ok(())
+    }
+}
+
+/// A book to convert
+struct Job {
+    source: String,
+    content: Result<Content, String>,
+}
+
+enum Content {
+    File(PathBuf),
+    /// A book of an archive, read beforehand as archives are read sequentially
+    Archived(Vec<u8>),
+}
+
+impl Job {
+    fn convert(self, output: &Path, options: &ConvertOptions) -> BatchEntry {
+        let path = Path::new(&self.source).with_extension("pbook");
+        let result = self.content.and_then(|content| {
+            let (container, report) = match content {
+                Content::File(path) => File::open(path)
+                    .map_err(ImportError::Io)
+                    .and_then(|file| convert_book(BufReader::new(file), options)),
+                Content::Archived(content) => convert_book(Cursor::new(content), options),
+            }
+            .map_err(|e| e.to_string())?;
+            write_container(&output.join(&path), &container).map_err(|e| e.to_string())?;
+            Ok(report)
+        });
+        match result {
+            Ok(report) => BatchEntry {
+                source: self.source,
+                status: BatchStatus::Converted,
+                output: Some(path.to_string_lossy().into_owned()),
+                error: None,
+                diagnostics: report
+                    .rejected_links
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect(),
+            },
+            Err(error) => BatchEntry {
+                source: self.source,
+                status: BatchStatus::Failed,
+                output: None,
+                error: Some(error),
+                diagnostics: vec![],
+            },
+        }
+    }
+}
+
+fn convert_book(
+    reader: impl BufRead + Seek,
+    options: &ConvertOptions,
+) -> Result<(Container, ConvertReport), ImportError> {
+    let mut resources = Resources::default();
+    let (book, report) = Book::from_fb2_reader(reader, Uuid::new_v4(), &mut resources, options)?;
+    let container = Container {
+        book: Some(book),
+        resources: resources.0.take(),
+    };
+    Ok((container, report))
+}
+
+/// Writes the container in full or not at all, so that an interrupted run leaves no partial
+/// containers
+fn write_container(path: &Path, container: &Container) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temporary = path.with_extension("pbook.tmp");
+    fs::write(&temporary, container.encode_to_vec())?;
+    fs::rename(temporary, path)
+}
+
+/// Resources of a book being converted
+#[derive(Default)]
+struct Resources(Rc<RefCell<Vec<Resource>>>);
+
+struct ResourceWriter {
+    resources: Rc<RefCell<Vec<Resource>>>,
+    index: usize,
+}
+
+impl ResourceSink for Resources {
+    type Writer = ResourceWriter;
+
+    fn create(&mut self, _id: &str, content_type: &str) -> io::Result<(Uuid, ResourceWriter)> {
+        let id = Uuid::new_v4();
+        let mut resources = self.0.borrow_mut();
+        resources.push(Resource {
+            id: id.to_string(),
+            content_type: content_type.to_string(),
+            content: vec![],
+        });
+        let writer = ResourceWriter {
+            resources: self.0.clone(),
+            index: resources.len() - 1,
+        };
+        Ok((id, writer))
+    }
+}
+
+impl Write for ResourceWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut resources = self.resources.borrow_mut();
+        resources[self.index].content.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Books of the source in the order of their paths, found as they're needed
+struct Jobs<'a> {
+    root: PathBuf,
+    walk: walkdir::IntoIter,
+    archive: Option<Archive>,
+    done: &'a HashSet<String>,
+    skipped: &'a AtomicUsize,
+    max_size: usize,
+}
+
+/// An archive whose books are being read
+struct Archive {
+    source: String,
+    zip: ZipArchive<BufReader<File>>,
+    next: usize,
+}
+
+impl Jobs<'_> {
+    fn is_done(&self, source: &str) -> bool {
+        let skip = self.done.contains(source);
+        if skip {
+            self.skipped.fetch_add(1, Ordering::Relaxed);
+        }
+        skip
+    }
+
+    fn source(&self, path: &Path) -> String {
+        let relative = match path.strip_prefix(&self.root) {
+            Ok(relative) if !relative.as_os_str().is_empty() => relative,
+            _ => Path::new(path.file_name().unwrap_or_default()),
+        };
+        let components: Vec<_> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect();
+        components.join("/")
+    }
+
+    fn next_archived(&mut self) -> Option<Job> {
+        let archive = self.archive.as_mut()?;
+        while archive.next < archive.zip.len() {
+            let index = archive.next;
+            archive.next += 1;
+            let mut file = match archive.zip.by_index(index) {
+                Ok(file) => file,
+                Err(e) => {
+                    return Some(Job {
+                        source: format!("{}/{index}", archive.source),
+                        content: Err(e.to_string()),
+                    });
+                }
+            };
+            if !file.is_file() || !has_extension(file.name(), "fb2") {
+                continue;
+            }
+            let source = format!("{}/{}", archive.source, file.name());
+            if self.done.contains(&source) {
+                self.skipped.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+            let content = if file.enclosed_name().is_none() {
+                Err("path outside of the archive".to_string())
+            } else if file.size() > self.max_size as u64 {
+                Err(too_large(&source, self.max_size))
+            } else {
+                let mut content = vec![];
+                match file.read_to_end(&mut content) {
+                    Ok(_) => Ok(Content::Archived(content)),
+                    Err(e) => Err(e.to_string()),
+                }
+            };
+            return Some(Job { source, content });
+        }
+        self.archive = None;
+        None
+    }
+}
+
+/// The error of a book, loose or archived, larger than the limit of resources, as archived books
+/// are read into memory
+fn too_large(source: &str, max_size: usize) -> String {
+    LimitError::ResourceSize(source.to_string(), max_size).to_string()
+}
+
+impl Iterator for Jobs<'_> {
+    type Item = Job;
+
+    fn next(&mut self) -> Option<Job> {
+        loop {
+            if let Some(job) = self.next_archived() {
+                return Some(job);
+            }
+            let entry = match self.walk.next()? {
+                Ok(entry) => entry,
+                Err(e) => {
+                    let source = e.path().map(|p| self.source(p)).unwrap_or_default();
+                    return Some(Job {
+                        source,
+                        content: Err(e.to_string()),
+                    });
+                }
+            };
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let source = self.source(entry.path());
+            if has_extension(&source, "fb2") {
+                if !self.is_done(&source) {
+                    let content = match entry.metadata() {
+                        Ok(m) if m.len() > self.max_size as u64 => {
+                            Err(too_large(&source, self.max_size))
+                        }
+                        _ => Ok(Content::File(entry.into_path())),
+                    };
+                    return Some(Job { source, content });
+                }
+            } else if has_extension(&source, "zip") {
+                let zip = File::open(entry.path())
+                    .map(BufReader::new)
+                    .and_then(|file| ZipArchive::new(file).map_err(io::Error::from));
+                match zip {
+                    Ok(zip) => {
+                        self.archive = Some(Archive {
+                            source,
+                            zip,
+                            next: 0,
+                        })
+                    }
+                    Err(e) => {
+                        return Some(Job {
+                            source,
+                            content: Err(e.to_string()),
+                        })
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn has_extension(path: &str, extension: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case(extension))
+}
@@ -0,0 +1,41 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use protobook::{convert_library, BatchOptions};
+
+const USAGE: &str = "usage: protobook-batch [--threads N] <source> <output>";
+
+/// Converts a library of FB2 books, printing the numbers of books by their outcome as JSON.
+/// The outcome of each book is in the summary in the output directory
+fn main() -> ExitCode {
+    let mut options = BatchOptions::default();
+    let mut paths = vec![];
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--threads" {
+            match args.next().and_then(|n| n.parse().ok()) {
+                Some(threads) => options.threads = threads,
+                None => {
+                    eprintln!("{USAGE}");
+                    return ExitCode::FAILURE;
+                }
+            }
+        } else {
+            paths.push(PathBuf::from(arg));
+        }
+    }
+    let [source, output] = paths.as_slice() else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+    match convert_library(source, output, &options) {
+        Ok(summary) => {
+            println!("{}", serde_json::to_string(&summary).unwrap());
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("failed to convert the library: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
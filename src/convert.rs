@@ -0,0 +1,448 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use url::Url;
+
+use crate::{
+    link, span, BaselineShift, Book, FontStyle, LimitError, Limits, Span, Text, TextDecoration,
+    BOLD_WEIGHT,
+};
+
+/// Settings of the conversion from other book formats
+#[derive(Clone, Debug)]
+pub struct ConvertOptions {
+    /// Whether to collapse whitespace of the text as in [`Book::collapse_whitespace`], which is
+    /// the way XML and HTML are displayed. Enabled by default
+    pub collapse_whitespace: bool,
+    /// Whether to replace typewriter punctuation with typographic one as in
+    /// [`Book::apply_typography`]. Disabled by default
+    pub typography: bool,
+    /// Names of FB2 bodies with notes, where `*` matches any characters, regardless of case.
+    /// "notes", "footnotes", "примечания" and "сноски" by default
+    pub note_bodies: Vec<String>,
+    /// Names of FB2 bodies with comments, matched like [`ConvertOptions::note_bodies`].
+    /// "comments" and "комментарии" by default
+    pub comment_bodies: Vec<String>,
+    /// Whether FB2 sections targeted by links of `type="note"` become notes, even if they are
+    /// chapters of the main body or sections of other bodies. Enabled by default
+    pub note_link_targets: bool,
+    /// Schemes of remote links that are kept, regardless of case. Links with other schemes,
+    /// such as `javascript:` or `file:`, become text. "http", "https" and "mailto" by default
+    pub link_schemes: Vec<String>,
+    /// URL against which relative links, such as "../about.html", are resolved. Relative links
+    /// become text if it's not set
+    pub base_url: Option<Url>,
+    /// Bounds on the converted book and on the source, see [`Limits`]
+    pub limits: Limits,
+    /// Text attributes of FB2 named styles, such as `<style name="smallcaps">`, by their names.
+    /// Text of a style in the map gets its attributes, and text of other styles gets their names
+    /// as [`Text::classes`]. Empty by default
+    pub style_map: HashMap<String, TextStyle>,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        ConvertOptions {
+            collapse_whitespace: true,
+            typography: false,
+            note_bodies: ["notes", "footnotes", "примечания", "сноски"]
+                .map(String::from)
+                .to_vec(),
+            comment_bodies: ["comments", "комментарии"].map(String::from).to_vec(),
+            note_link_targets: true,
+            link_schemes: ["http", "https", "mailto"].map(String::from).to_vec(),
+            base_url: None,
+            limits: Limits::default(),
+            style_map: HashMap::new(),
+        }
+    }
+}
+
+impl ConvertOptions {
+    /// Brings the converted book to its final form according to the options
+    pub(crate) fn finish(&self, book: &mut Book) {
+        if self.collapse_whitespace {
+            book.collapse_whitespace();
+        }
+        if self.typography {
+            book.apply_typography();
+        }
+        book.merge_text_spans();
+        book.assign_directions();
+    }
+
+    /// Classifies the href of a link: "#id" links to a part of the book, absolute URLs and
+    /// relative ones resolved against [`ConvertOptions::base_url`] link to remote resources,
+    /// normalized with their hosts in ASCII and the rest percent-encoded. An empty href is no
+    /// link at all
+    pub(crate) fn href(&self, href: &str) -> Result<Option<link::Href>, LinkRejection> {
+        let href = href.trim();
+        if href.is_empty() {
+            return Ok(None);
+        }
+        if let Some(id) = href.strip_prefix('#') {
+            return Ok(non_empty(id.to_string()).map(link::Href::Local));
+        }
+        let url = match Url::parse(href) {
+            Ok(url) => url,
+            Err(url::ParseError::RelativeUrlWithoutBase) => match &self.base_url {
+                Some(base_url) => base_url.join(href).map_err(LinkRejection::Invalid)?,
+                None => return Err(LinkRejection::Relative),
+            },
+            Err(e) => return Err(LinkRejection::Invalid(e)),
+        };
+        if !self
+            .link_schemes
+            .iter()
+            .any(|s| s.eq_ignore_ascii_case(url.scheme()))
+        {
+            return Err(LinkRejection::Scheme(url.scheme().to_string()));
+        }
+        Ok(Some(link::Href::Remote(url.into())))
+    }
+}
+
+/// Usage of [`Limits`] during a conversion, which stops descending into the source once a limit
+/// is exceeded
+pub(crate) struct Budget<'l> {
+    limits: &'l Limits,
+    depth: Cell<usize>,
+    chapters: Cell<usize>,
+    spans: Cell<usize>,
+    #[cfg(feature = "fb2")]
+    footnotes: Cell<usize>,
+    // the first exceeded limit
+    error: RefCell<Option<LimitError>>,
+}
+
+/// A level of nesting, left when dropped
+pub(crate) struct Depth<'b> {
+    depth: &'b Cell<usize>,
+}
+
+impl Drop for Depth<'_> {
+    fn drop(&mut self) {
+        self.depth.set(self.depth.get() - 1);
+    }
+}
+
+impl<'l> Budget<'l> {
+    pub(crate) fn new(limits: &'l Limits) -> Budget<'l> {
+        Budget {
+            limits,
+            depth: Cell::new(0),
+            chapters: Cell::new(0),
+            spans: Cell::new(0),
+            #[cfg(feature = "fb2")]
+            footnotes: Cell::new(0),
+            error: RefCell::new(None),
+        }
+    }
+
+    /// Enters a level of nesting, unless it's too deep or another limit is exceeded
+    pub(crate) fn enter(&self) -> Option<Depth<'_>> {
+        if self.depth.get() >= self.limits.max_depth {
+            self.fail(LimitError::Depth(self.limits.max_depth));
+        }
+        if self.exceeded() {
+            return None;
+        }
+        self.depth.set(self.depth.get() + 1);
+        Some(Depth { depth: &self.depth })
+    }
+
+    /// Counts a chapter, returning whether it fits
+    pub(crate) fn chapter(&self) -> bool {
+        self.count(
+            &self.chapters,
+            1,
+            self.limits.max_chapters,
+            LimitError::Chapters,
+        )
+    }
+
+    /// Counts spans of a paragraph, returning whether they fit
+    pub(crate) fn spans(&self, spans: usize) -> bool {
+        self.count(&self.spans, spans, self.limits.max_spans, LimitError::Spans)
+    }
+
+    /// Counts a note or a comment, returning whether it fits
+    #[cfg(feature = "fb2")]
+    pub(crate) fn footnote(&self) -> bool {
+        self.count(
+            &self.footnotes,
+            1,
+            self.limits.max_footnotes,
+            LimitError::Footnotes,
+        )
+    }
+
+    /// Whether the text fits
+    pub(crate) fn text(&self, value: &str) -> bool {
+        if value.len() > self.limits.max_text_length {
+            self.fail(LimitError::TextLength(self.limits.max_text_length));
+        }
+        !self.exceeded()
+    }
+
+    /// Whether a resource of the source, by its id, or the source itself, by an empty id, fits
+    pub(crate) fn resource(&self, id: &str, size: usize) -> bool {
+        if size > self.limits.max_resource_size {
+            self.fail(LimitError::ResourceSize(
+                id.to_string(),
+                self.limits.max_resource_size,
+            ));
+        }
+        !self.exceeded()
+    }
+
+    /// Fails with the first exceeded limit
+    #[cfg(feature = "fb2")]
+    pub(crate) fn check(&self) -> Result<(), LimitError> {
+        match &*self.error.borrow() {
+            Some(e) => Err(e.clone()),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn finish(self) -> Result<(), LimitError> {
+        match self.error.into_inner() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn count(
+        &self,
+        counter: &Cell<usize>,
+        n: usize,
+        limit: usize,
+        error: fn(usize) -> LimitError,
+    ) -> bool {
+        counter.set(counter.get().saturating_add(n));
+        if counter.get() > limit {
+            self.fail(error(limit));
+        }
+        !self.exceeded()
+    }
+
+    fn fail(&self, error: LimitError) {
+        self.error.borrow_mut().get_or_insert(error);
+    }
+
+    fn exceeded(&self) -> bool {
+        self.error.borrow().is_some()
+    }
+}
+
+/// Text attributes of a named style, see [`ConvertOptions::style_map`]. They are set only on
+/// text that doesn't have them from an inner element, so that `<strong>` within a style stays
+/// bold, and decorations are added to the ones of the text
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TextStyle {
+    pub font_weight: Option<u32>,
+    pub font_style: Option<FontStyle>,
+    pub baseline_shift: Option<BaselineShift>,
+    pub decorations: Vec<TextDecoration>,
+}
+
+/// What happened during a conversion, besides the converted book
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConvertReport {
+    /// Links that became text, in the order they were converted
+    pub rejected_links: Vec<RejectedLink>,
+}
+
+/// A link that became text
+#[derive(Clone, Debug, PartialEq)]
+pub struct RejectedLink {
+    /// The href as written in the source
+    pub href: String,
+    /// The text of the link
+    pub text: String,
+    pub reason: LinkRejection,
+}
+
+/// Why a link became text
+#[derive(Clone, Debug, PartialEq)]
+pub enum LinkRejection {
+    /// The href is not a valid URL
+    Invalid(url::ParseError),
+    /// The scheme of the URL is not one of [`ConvertOptions::link_schemes`]
+    Scheme(String),
+    /// The href is relative, and [`ConvertOptions::base_url`] is not set
+    Relative,
+}
+
+impl Display for LinkRejection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkRejection::Invalid(e) => write!(f, "invalid URL: {e}"),
+            LinkRejection::Scheme(scheme) => write!(f, "scheme not allowed: {scheme}"),
+            LinkRejection::Relative => write!(f, "relative URL without a base URL"),
+        }
+    }
+}
+
+impl Display for RejectedLink {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "link \"{}\" to {} rejected: {}",
+            self.text, self.href, self.reason
+        )
+    }
+}
+
+/// Splits the content of a link into its text, which stays inside the link, and images, which
+/// are placed after it
+pub(crate) fn split_link_content(
+    content: impl IntoIterator<Item = Span>,
+) -> (Vec<Text>, Vec<Span>) {
+    let mut images = vec![];
+    let mut text = vec![];
+    for span in content {
+        match span.span.unwrap() {
+            span::Span::Footnote(_) | span::Span::Link(_) => {}
+            span::Span::Image(i) => images.push(Span {
+                span: Some(span::Span::Image(i)),
+            }),
+            span::Span::Text(t) => text.push(t),
+        }
+    }
+    (text, images)
+}
+
+pub(crate) fn concat_text(text: impl IntoIterator<Item = Text>) -> String {
+    text.into_iter()
+        .map(|t| t.value)
+        .fold(String::new(), |mut a, b| {
+            a.push_str(&b);
+            a
+        })
+}
+
+#[cfg(feature = "html")]
+pub(crate) fn text_span(value: String) -> Option<Span> {
+    plain_text(value).map(|text| Span {
+        span: Some(span::Span::Text(text)),
+    })
+}
+
+pub(crate) fn plain_text(value: String) -> Option<Text> {
+    non_empty(value).map(|value| Text {
+        value,
+        font_weight: None,
+        font_style: None,
+        baseline_shift: None,
+        decorations: vec![],
+        language: String::new(),
+        classes: vec![],
+    })
+}
+
+pub(crate) fn bold_text(span: Span) -> Span {
+    hydrate_text(span, |text| text.font_weight = Some(BOLD_WEIGHT))
+}
+
+pub(crate) fn subscript_text(span: Span) -> Span {
+    hydrate_text(span, |text| {
+        text.set_baseline_shift(BaselineShift::Subscript);
+    })
+}
+
+pub(crate) fn superscript_text(span: Span) -> Span {
+    hydrate_text(span, |text| {
+        text.set_baseline_shift(BaselineShift::Superscript);
+    })
+}
+
+pub(crate) fn italic_text(span: Span) -> Span {
+    hydrate_text(span, |text| {
+        text.set_font_style(FontStyle::Italic);
+    })
+}
+
+pub(crate) fn code_text(span: Span) -> Span {
+    hydrate_text(span, |text| {
+        text.set_font_style(FontStyle::Code);
+    })
+}
+
+pub(crate) fn strikethrough_text(span: Span) -> Span {
+    hydrate_text(span, |text| {
+        text.push_decorations(TextDecoration::LineThrough);
+    })
+}
+
+/// Sets the language of the texts of a span, unless they have their own from an inner element
+#[cfg(feature = "fb2")]
+pub(crate) fn language_text(language: String) -> impl Fn(Span) -> Span {
+    move |span| {
+        hydrate_text(span, |text| {
+            if text.language.is_empty() {
+                text.language.clone_from(&language);
+            }
+        })
+    }
+}
+
+/// Styles the texts of a span as the named style, unless they have its attributes from an inner
+/// element, see [`ConvertOptions::style_map`]
+#[cfg(feature = "fb2")]
+pub(crate) fn named_style_text<'o>(
+    options: &'o ConvertOptions,
+    name: &'o str,
+) -> impl Fn(Span) -> Span + 'o {
+    let style = options.style_map.get(name);
+    move |span| {
+        hydrate_text(span, |text| match style {
+            Some(style) => {
+                if text.font_weight.is_none() {
+                    text.font_weight = style.font_weight;
+                }
+                if text.font_style.is_none() {
+                    text.font_style = style.font_style.map(Into::into);
+                }
+                if text.baseline_shift.is_none() {
+                    text.baseline_shift = style.baseline_shift.map(Into::into);
+                }
+                for &decoration in &style.decorations {
+                    if !text.decorations().any(|d| d == decoration) {
+                        text.push_decorations(decoration);
+                    }
+                }
+            }
+            None if !name.is_empty() => text.classes.insert(0, name.to_string()),
+            None => {}
+        })
+    }
+}
+
+fn hydrate_text(mut span: Span, mut modifier: impl FnMut(&mut Text)) -> Span {
+    match span.span.as_mut().unwrap() {
+        span::Span::Footnote(f) => {
+            for text in &mut f.content {
+                modifier(text);
+            }
+        }
+        span::Span::Link(l) => {
+            for text in &mut l.content {
+                modifier(text);
+            }
+        }
+        span::Span::Image(_) => {}
+        span::Span::Text(t) => {
+            modifier(t);
+        }
+    }
+    span
+}
+
+pub(crate) fn non_empty(value: String) -> Option<String> {
+    if value.is_empty() {
+        None
+    } else {
+        Some(value)
+    }
+}
@@ -0,0 +1,310 @@
+use std::fmt::{self, Display, Formatter};
+
+use crate::{Date, DatePrecision};
+
+/// Words before a date that make it approximate, in lowercase
+const CIRCA: [&str; 7] = ["около", "ок.", "circa", "ca.", "c.", "прибл.", "~"];
+
+/// Words after a year, in lowercase, longer ones first
+const YEAR_WORDS: [&str; 5] = ["года", "годы", "год", "гг.", "г."];
+
+/// A date known to the year, the month or the day.
+///
+/// Dates are ordered chronologically, a less precise date going before the more precise ones
+/// within it, so that "1936" < "1936-01" < "1936-01-01"
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PartialDate {
+    pub year: i32,
+    /// From 1 to 12
+    pub month: Option<u32>,
+    /// From 1 to the number of days in the month, only along with the month
+    pub day: Option<u32>,
+}
+
+impl PartialDate {
+    pub fn year(year: i32) -> PartialDate {
+        PartialDate {
+            year,
+            month: None,
+            day: None,
+        }
+    }
+
+    /// Fails if there's no such month
+    pub fn month(year: i32, month: u32) -> Option<PartialDate> {
+        (1..=12).contains(&month).then_some(PartialDate {
+            year,
+            month: Some(month),
+            day: None,
+        })
+    }
+
+    /// Fails if there's no such day
+    pub fn day(year: i32, month: u32, day: u32) -> Option<PartialDate> {
+        let month = PartialDate::month(year, month)?;
+        (1..=days_in_month(year, month.month?))
+            .contains(&day)
+            .then_some(PartialDate {
+                day: Some(day),
+                ..month
+            })
+    }
+
+    /// Parses an ISO 8601 date of any precision: "1936", "1936-05" or "1936-05-12"
+    pub fn parse_iso(value: &str) -> Option<PartialDate> {
+        let parts: Vec<_> = value.split('-').collect();
+        if parts[0].len() != 4 || parts[1..].iter().any(|p| p.len() != 2) {
+            return None;
+        }
+        let year = number(parts[0])? as i32;
+        match parts[1..] {
+            [] => Some(PartialDate::year(year)),
+            [month] => PartialDate::month(year, number(month)?),
+            [month, day] => PartialDate::day(year, number(month)?, number(day)?),
+            _ => None,
+        }
+    }
+
+    pub fn precision(&self) -> DatePrecision {
+        match (self.month, self.day) {
+            (Some(_), Some(_)) => DatePrecision::Day,
+            (Some(_), None) => DatePrecision::Month,
+            _ => DatePrecision::Year,
+        }
+    }
+
+    /// The date known at most to the precision, unchanged if it's less precise already
+    pub fn truncate(self, precision: DatePrecision) -> PartialDate {
+        match precision {
+            DatePrecision::Year => PartialDate::year(self.year),
+            DatePrecision::Month => PartialDate { day: None, ..self },
+            DatePrecision::Day | DatePrecision::Unknown => self,
+        }
+    }
+
+    /// The first day within the date
+    pub fn first_day(&self) -> PartialDate {
+        PartialDate {
+            year: self.year,
+            month: Some(self.month.unwrap_or(1)),
+            day: Some(self.day.unwrap_or(1)),
+        }
+    }
+
+    /// The last day within the date
+    pub fn last_day(&self) -> PartialDate {
+        let month = self.month.unwrap_or(12);
+        PartialDate {
+            year: self.year,
+            month: Some(month),
+            day: Some(self.day.unwrap_or_else(|| days_in_month(self.year, month))),
+        }
+    }
+
+    /// The year, the month and the day of a date known to the day
+    #[cfg(any(feature = "chrono", feature = "time"))]
+    fn ymd(&self) -> (i32, u32, u32) {
+        (self.year, self.month.unwrap_or(1), self.day.unwrap_or(1))
+    }
+
+    /// The first day within the date, if chrono can represent it
+    #[cfg(feature = "chrono")]
+    pub fn first_naive_date(&self) -> Option<chrono::NaiveDate> {
+        let (year, month, day) = self.first_day().ymd();
+        chrono::NaiveDate::from_ymd_opt(year, month, day)
+    }
+
+    /// The last day within the date, if chrono can represent it
+    #[cfg(feature = "chrono")]
+    pub fn last_naive_date(&self) -> Option<chrono::NaiveDate> {
+        let (year, month, day) = self.last_day().ymd();
+        chrono::NaiveDate::from_ymd_opt(year, month, day)
+    }
+
+    /// The first day within the date, if time can represent it
+    #[cfg(feature = "time")]
+    pub fn first_date(&self) -> Option<time::Date> {
+        let (year, month, day) = self.first_day().ymd();
+        let month = time::Month::try_from(month as u8).ok()?;
+        time::Date::from_calendar_date(year, month, day as u8).ok()
+    }
+
+    /// The last day within the date, if time can represent it
+    #[cfg(feature = "time")]
+    pub fn last_date(&self) -> Option<time::Date> {
+        let (year, month, day) = self.last_day().ymd();
+        let month = time::Month::try_from(month as u8).ok()?;
+        time::Date::from_calendar_date(year, month, day as u8).ok()
+    }
+}
+
+/// As ISO 8601, to the precision of the date
+impl Display for PartialDate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}", self.year)?;
+        if let Some(month) = self.month {
+            write!(f, "-{month:02}")?;
+        }
+        if let Some(day) = self.day {
+            write!(f, "-{day:02}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<chrono::NaiveDate> for PartialDate {
+    fn from(date: chrono::NaiveDate) -> PartialDate {
+        use chrono::Datelike;
+        PartialDate {
+            year: date.year(),
+            month: Some(date.month()),
+            day: Some(date.day()),
+        }
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<time::Date> for PartialDate {
+    fn from(date: time::Date) -> PartialDate {
+        PartialDate {
+            year: date.year(),
+            month: Some(date.month() as u32),
+            day: Some(date.day() as u32),
+        }
+    }
+}
+
+impl Date {
+    /// The date, displayed as ISO 8601
+    pub fn new(date: PartialDate) -> Date {
+        Date {
+            iso_date: date.to_string(),
+            display_date: date.to_string(),
+            precision: date.precision().into(),
+            ..Default::default()
+        }
+    }
+
+    /// The range from the start to the end, both known to the precision of the less precise one
+    pub fn range(start: PartialDate, end: PartialDate) -> Date {
+        let precision = start.precision().min(end.precision());
+        let (start, end) = (start.truncate(precision), end.truncate(precision));
+        let display_date = format!("{start}–{end}");
+        Date {
+            iso_date: start.to_string(),
+            display_date,
+            precision: precision.into(),
+            iso_end_date: end.to_string(),
+            approximate: false,
+        }
+    }
+
+    /// Understands a date as written for readers, keeping its precision: a year, a month or a day
+    /// as ISO 8601 or as "MM.YYYY" and "DD.MM.YYYY", and a range of them. Takes "около", "circa"
+    /// and alike as an approximate date, and skips "г." and alike after years. Fails for other
+    /// dates, such as ones with names of months
+    pub fn parse(display_date: &str) -> Option<Date> {
+        let mut value = display_date.trim().to_lowercase();
+        let mut approximate = false;
+        if let Some(rest) = CIRCA.iter().find_map(|c| value.strip_prefix(c)) {
+            value = rest.trim_start().to_string();
+            approximate = true;
+        }
+        let mut date = match partial_date(&value) {
+            Some(date) => Date::new(date),
+            None => {
+                let (start, end) = ["–", "—", " - ", "-"]
+                    .into_iter()
+                    .filter_map(|separator| value.split_once(separator))
+                    .find_map(|(start, end)| Some((partial_date(start)?, partial_date(end)?)))?;
+                if start.truncate(end.precision()) > end.truncate(start.precision()) {
+                    return None;
+                }
+                Date::range(start, end)
+            }
+        };
+        date.display_date = display_date.to_string();
+        date.approximate = approximate;
+        Some(date)
+    }
+
+    /// The date, or the start of the range, known to the precision of the date. Also reads dates
+    /// without a precision that have an ISO 8601 date, as set before the precision was added
+    pub fn start(&self) -> Option<PartialDate> {
+        self.partial_date(&self.iso_date)
+    }
+
+    /// The end of the range, or the date itself if it's not a range
+    pub fn end(&self) -> Option<PartialDate> {
+        if self.iso_end_date.is_empty() {
+            self.start()
+        } else {
+            self.partial_date(&self.iso_end_date)
+        }
+    }
+
+    /// The first day of the date, for sorting, if chrono can represent it
+    #[cfg(feature = "chrono")]
+    pub fn first_naive_date(&self) -> Option<chrono::NaiveDate> {
+        self.start()?.first_naive_date()
+    }
+
+    /// The last day of the date, if chrono can represent it
+    #[cfg(feature = "chrono")]
+    pub fn last_naive_date(&self) -> Option<chrono::NaiveDate> {
+        self.end()?.last_naive_date()
+    }
+
+    /// The first day of the date, for sorting, if time can represent it
+    #[cfg(feature = "time")]
+    pub fn first_date(&self) -> Option<time::Date> {
+        self.start()?.first_date()
+    }
+
+    /// The last day of the date, if time can represent it
+    #[cfg(feature = "time")]
+    pub fn last_date(&self) -> Option<time::Date> {
+        self.end()?.last_date()
+    }
+
+    fn partial_date(&self, iso_date: &str) -> Option<PartialDate> {
+        Some(PartialDate::parse_iso(iso_date)?.truncate(self.precision()))
+    }
+}
+
+/// A date as ISO 8601, "MM.YYYY" or "DD.MM.YYYY", possibly followed by a word for a year
+fn partial_date(value: &str) -> Option<PartialDate> {
+    let mut value = value.trim();
+    if let Some(rest) = YEAR_WORDS.iter().find_map(|w| value.strip_suffix(w)) {
+        value = rest.trim_end();
+    }
+    let parts: Vec<_> = value.split('.').collect();
+    let year = |y: &str| number(y).filter(|_| y.len() == 4).map(|y| y as i32);
+    match parts.as_slice() {
+        [_] => PartialDate::parse_iso(value),
+        [month, y] if month.len() == 2 => PartialDate::month(year(y)?, number(month)?),
+        [day, month, y] if day.len() <= 2 && month.len() == 2 => {
+            PartialDate::day(year(y)?, number(month)?, number(day)?)
+        }
+        _ => None,
+    }
+}
+
+fn number(value: &str) -> Option<u32> {
+    if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) {
+        value.parse().ok()
+    } else {
+        None
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
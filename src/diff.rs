@@ -0,0 +1,557 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::{self, Display, Formatter};
+
+use crate::visit::{for_each_paragraph, paragraph_text, Visit};
+use crate::{
+    span, title_element, Author, Book, BookDiff, Change, ChangeKind, ChangeTarget, Chapter,
+    Container, DatePrecision, FootnoteType, Image, Locator, Span, Title,
+};
+
+/// Greatest number of edits between two sequences that are aligned. Aligning takes memory
+/// quadratic in the number of edits, so sequences with more edits are considered entirely
+/// different
+const MAX_EDITS: usize = 1000;
+
+impl Book {
+    /// Changes from this version of the book to the other one.
+    ///
+    /// Chapters are aligned by their anchors, or by their titles if they have no anchors, and
+    /// paragraphs of aligned chapters by their text. Chapters and paragraphs that aren't aligned,
+    /// but are at the same place in both versions, are modified, and the rest are added or
+    /// removed. Footnotes are aligned by their ids. Resources are compared by their ids only, use
+    /// [`Container::diff`] to compare their contents.
+    pub fn diff(&self, other: &Book) -> BookDiff {
+        let mut changes = Differ::new(self, other).diff();
+        let old = resource_ids(self);
+        let new = resource_ids(other);
+        for id in old.difference(&new) {
+            changes.push(resource(ChangeKind::Removed, id));
+        }
+        for id in new.difference(&old) {
+            changes.push(resource(ChangeKind::Added, id));
+        }
+        BookDiff { changes }
+    }
+}
+
+impl Container {
+    /// Changes from this version of the book to the other one, as [`Book::diff`], but resources
+    /// are compared by their contents, so that resources of a book imported again, which have new
+    /// ids, are unchanged
+    pub fn diff(&self, other: &Container) -> BookDiff {
+        let default = Book::default();
+        let old_book = self.book.as_ref().unwrap_or(&default);
+        let new_book = other.book.as_ref().unwrap_or(&default);
+        let mut changes = Differ::new(old_book, new_book).diff();
+
+        let old: HashMap<_, _> = self.resources.iter().map(|r| (&r.id, r)).collect();
+        let new: HashMap<_, _> = other.resources.iter().map(|r| (&r.id, r)).collect();
+        let old_contents: HashSet<_> = self.resources.iter().map(|r| &r.content).collect();
+        let new_contents: HashSet<_> = other.resources.iter().map(|r| &r.content).collect();
+        for r in &self.resources {
+            if !new.contains_key(&r.id) && !new_contents.contains(&r.content) {
+                changes.push(resource(ChangeKind::Removed, &r.id));
+            }
+        }
+        for r in &other.resources {
+            match old.get(&r.id) {
+                Some(old) if old.content != r.content => {
+                    changes.push(resource(ChangeKind::Modified, &r.id));
+                }
+                None if !old_contents.contains(&r.content) => {
+                    changes.push(resource(ChangeKind::Added, &r.id));
+                }
+                _ => {}
+            }
+        }
+        BookDiff { changes }
+    }
+}
+
+impl Display for BookDiff {
+    /// One change per line
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{change}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Change {
+    /// The kind of the change as "+", "-" or "~", the changed part and its text, such as
+    /// "~ paragraph 2.1¶3: old text → new text"
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let locator = match self.kind() {
+            ChangeKind::Removed => self.old.as_ref(),
+            _ => self.new.as_ref().or(self.old.as_ref()),
+        };
+        let default = Locator::default();
+        let locator = locator.unwrap_or(&default);
+        let sign = match self.kind() {
+            ChangeKind::Added => "+",
+            ChangeKind::Removed => "-",
+            ChangeKind::Modified => "~",
+            ChangeKind::Unknown => "?",
+        };
+        write!(f, "{sign} ")?;
+        match self.target() {
+            ChangeTarget::Metadata => write!(f, "{}", self.name)?,
+            ChangeTarget::Chapter => write!(f, "chapter {}", chapter_number(locator))?,
+            ChangeTarget::Paragraph => write!(
+                f,
+                "paragraph {}¶{}",
+                chapter_number(locator),
+                locator.paragraph + 1
+            )?,
+            ChangeTarget::Footnote if locator.footnote_type() == FootnoteType::Comment => {
+                write!(f, "comment {}", locator.footnote)?
+            }
+            ChangeTarget::Footnote => write!(f, "note {}", locator.footnote)?,
+            ChangeTarget::Resource => write!(f, "resource {}", self.name)?,
+            ChangeTarget::Unknown => write!(f, "unknown")?,
+        }
+        match self.kind() {
+            ChangeKind::Added if !self.new_text.is_empty() => write!(f, ": {}", self.new_text),
+            ChangeKind::Removed if !self.old_text.is_empty() => write!(f, ": {}", self.old_text),
+            ChangeKind::Modified if self.old_text != self.new_text => {
+                write!(f, ": {} → {}", self.old_text, self.new_text)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Numbers of the chapters of the path starting with 1, such as "2.1"
+fn chapter_number(locator: &Locator) -> String {
+    let numbers: Vec<_> = locator
+        .chapters
+        .iter()
+        .map(|i| (i + 1).to_string())
+        .collect();
+    numbers.join(".")
+}
+
+fn change(kind: ChangeKind, target: ChangeTarget) -> Change {
+    Change {
+        kind: kind.into(),
+        target: target.into(),
+        ..Change::default()
+    }
+}
+
+fn resource(kind: ChangeKind, id: &str) -> Change {
+    Change {
+        name: id.to_string(),
+        ..change(kind, ChangeTarget::Resource)
+    }
+}
+
+/// Paragraphs of a book as located and as their text
+#[derive(Default)]
+struct Paragraphs {
+    /// Paragraphs of each chapter without its sub-chapters by the path of the chapter, and the
+    /// paragraphs outside of chapters by the empty path
+    chapters: HashMap<Vec<u32>, Vec<(Locator, String)>>,
+    /// The text of each footnote in the order of the book
+    footnotes: Vec<(Locator, String)>,
+}
+
+impl Paragraphs {
+    fn new(book: &Book) -> Paragraphs {
+        let mut paragraphs = Paragraphs::default();
+        for_each_paragraph(book, |locator, spans| {
+            let text = paragraph_text(spans);
+            if locator.footnote_type() == FootnoteType::Unknown {
+                let chapter = paragraphs.chapters.entry(locator.chapters.clone());
+                chapter.or_default().push((locator, text));
+            } else if !locator.footnote.is_empty() {
+                match paragraphs.footnotes.last_mut() {
+                    Some((last, footnote)) if same_footnote(last, &locator) => {
+                        footnote.push('\n');
+                        footnote.push_str(&text);
+                    }
+                    _ => paragraphs.footnotes.push((
+                        Locator {
+                            paragraph: 0,
+                            ..locator
+                        },
+                        text,
+                    )),
+                }
+            }
+        });
+        paragraphs
+    }
+
+    fn chapter(&self, path: &[u32]) -> &[(Locator, String)] {
+        self.chapters
+            .get(path)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+}
+
+fn same_footnote(a: &Locator, b: &Locator) -> bool {
+    a.footnote == b.footnote && a.footnote_type == b.footnote_type
+}
+
+struct Differ<'a> {
+    old: &'a Book,
+    new: &'a Book,
+    old_paragraphs: Paragraphs,
+    new_paragraphs: Paragraphs,
+    changes: Vec<Change>,
+}
+
+impl<'a> Differ<'a> {
+    fn new(old: &'a Book, new: &'a Book) -> Differ<'a> {
+        Differ {
+            old,
+            new,
+            old_paragraphs: Paragraphs::new(old),
+            new_paragraphs: Paragraphs::new(new),
+            changes: vec![],
+        }
+    }
+
+    /// Changes of everything but resources
+    fn diff(mut self) -> Vec<Change> {
+        for ((name, old), (_, new)) in metadata(self.old).into_iter().zip(metadata(self.new)) {
+            let kind = match (old.is_empty(), new.is_empty()) {
+                _ if old == new => continue,
+                (true, _) => ChangeKind::Added,
+                (_, true) => ChangeKind::Removed,
+                _ => ChangeKind::Modified,
+            };
+            self.changes.push(Change {
+                name: name.to_string(),
+                old_text: old,
+                new_text: new,
+                ..change(kind, ChangeTarget::Metadata)
+            });
+        }
+        let changes = self.diff_paragraphs(&[], &[]);
+        self.changes.extend(changes);
+        self.diff_chapters(&self.old.chapters, &self.new.chapters, &[], &[]);
+        self.diff_footnotes();
+        self.changes
+    }
+
+    fn diff_chapters(
+        &mut self,
+        old: &[Chapter],
+        new: &[Chapter],
+        old_path: &[u32],
+        new_path: &[u32],
+    ) {
+        let key = |c: &Chapter| match c.anchor.as_str() {
+            "" => title_text(c.title.as_ref()),
+            id => format!("#{id}"),
+        };
+        let old_keys: Vec<_> = old.iter().map(key).collect();
+        let new_keys: Vec<_> = new.iter().map(key).collect();
+        for step in align(&old_keys, &new_keys) {
+            match step {
+                Step::Same(i, j) | Step::Modified(i, j) => {
+                    let old_path = [old_path, &[i as u32]].concat();
+                    let new_path = [new_path, &[j as u32]].concat();
+                    let old_title = title_text(old[i].title.as_ref());
+                    let new_title = title_text(new[j].title.as_ref());
+                    let paragraphs = self.diff_paragraphs(&old_path, &new_path);
+                    if !paragraphs.is_empty() || old_title != new_title {
+                        self.changes.push(Change {
+                            old: Some(chapter_locator(&old_path)),
+                            new: Some(chapter_locator(&new_path)),
+                            old_text: old_title,
+                            new_text: new_title,
+                            ..change(ChangeKind::Modified, ChangeTarget::Chapter)
+                        });
+                    }
+                    self.changes.extend(paragraphs);
+                    self.diff_chapters(
+                        &old[i].sub_chapters,
+                        &new[j].sub_chapters,
+                        &old_path,
+                        &new_path,
+                    );
+                }
+                Step::Removed(i) => self.changes.push(Change {
+                    old: Some(chapter_locator(&[old_path, &[i as u32]].concat())),
+                    old_text: title_text(old[i].title.as_ref()),
+                    ..change(ChangeKind::Removed, ChangeTarget::Chapter)
+                }),
+                Step::Added(j) => self.changes.push(Change {
+                    new: Some(chapter_locator(&[new_path, &[j as u32]].concat())),
+                    new_text: title_text(new[j].title.as_ref()),
+                    ..change(ChangeKind::Added, ChangeTarget::Chapter)
+                }),
+            }
+        }
+    }
+
+    /// Changes of the paragraphs of aligned chapters, without their sub-chapters
+    fn diff_paragraphs(&self, old_path: &[u32], new_path: &[u32]) -> Vec<Change> {
+        let old = self.old_paragraphs.chapter(old_path);
+        let new = self.new_paragraphs.chapter(new_path);
+        let old_texts: Vec<_> = old.iter().map(|(_, text)| text).collect();
+        let new_texts: Vec<_> = new.iter().map(|(_, text)| text).collect();
+        align(&old_texts, &new_texts)
+            .into_iter()
+            .filter_map(|step| match step {
+                Step::Same(..) => None,
+                Step::Modified(i, j) => Some(Change {
+                    old: Some(old[i].0.clone()),
+                    new: Some(new[j].0.clone()),
+                    old_text: old[i].1.clone(),
+                    new_text: new[j].1.clone(),
+                    ..change(ChangeKind::Modified, ChangeTarget::Paragraph)
+                }),
+                Step::Removed(i) => Some(Change {
+                    old: Some(old[i].0.clone()),
+                    old_text: old[i].1.clone(),
+                    ..change(ChangeKind::Removed, ChangeTarget::Paragraph)
+                }),
+                Step::Added(j) => Some(Change {
+                    new: Some(new[j].0.clone()),
+                    new_text: new[j].1.clone(),
+                    ..change(ChangeKind::Added, ChangeTarget::Paragraph)
+                }),
+            })
+            .collect()
+    }
+
+    fn diff_footnotes(&mut self) {
+        let old = &self.old_paragraphs.footnotes;
+        let new = &self.new_paragraphs.footnotes;
+        let key = |l: &Locator| (l.footnote_type, l.footnote.clone());
+        let old_keys: HashMap<_, _> = old.iter().map(|(l, text)| (key(l), text)).collect();
+        let new_keys: HashSet<_> = new.iter().map(|(l, _)| key(l)).collect();
+        for (locator, text) in old {
+            if !new_keys.contains(&key(locator)) {
+                self.changes.push(Change {
+                    old: Some(locator.clone()),
+                    old_text: text.clone(),
+                    ..change(ChangeKind::Removed, ChangeTarget::Footnote)
+                });
+            }
+        }
+        for (locator, text) in new {
+            match old_keys.get(&key(locator)) {
+                Some(old_text) if *old_text != text => self.changes.push(Change {
+                    old: Some(locator.clone()),
+                    new: Some(locator.clone()),
+                    old_text: old_text.to_string(),
+                    new_text: text.clone(),
+                    ..change(ChangeKind::Modified, ChangeTarget::Footnote)
+                }),
+                Some(_) => {}
+                None => self.changes.push(Change {
+                    new: Some(locator.clone()),
+                    new_text: text.clone(),
+                    ..change(ChangeKind::Added, ChangeTarget::Footnote)
+                }),
+            }
+        }
+    }
+}
+
+fn chapter_locator(path: &[u32]) -> Locator {
+    Locator {
+        chapters: path.to_vec(),
+        ..Locator::default()
+    }
+}
+
+/// Names of date precisions as text, empty for an unknown one
+pub(crate) const PRECISIONS: [(DatePrecision, &str); 4] = [
+    (DatePrecision::Unknown, ""),
+    (DatePrecision::Year, "year"),
+    (DatePrecision::Month, "month"),
+    (DatePrecision::Day, "day"),
+];
+
+/// Fields of the description of a book, by their names, as text. An approximate date is "true",
+/// and an exact one is empty
+pub(crate) fn metadata(book: &Book) -> [(&'static str, String); 8] {
+    let date = book.date.clone().unwrap_or_default();
+    let precision = PRECISIONS
+        .iter()
+        .find(|(p, _)| *p == date.precision())
+        .map_or("", |(_, name)| name);
+    let approximate = if date.approximate { "true" } else { "" };
+    let authors: Vec<_> = book.authors.iter().map(author_name).collect();
+    [
+        ("short_title", book.short_title.clone()),
+        ("date.iso_date", date.iso_date),
+        ("date.iso_end_date", date.iso_end_date),
+        ("date.precision", precision.to_string()),
+        ("date.approximate", approximate.to_string()),
+        ("date.display_date", date.display_date),
+        ("authors", authors.join("; ")),
+        ("language", book.language.clone()),
+    ]
+}
+
+fn author_name(author: &Author) -> String {
+    if !author.full_name.is_empty() {
+        return author.full_name.clone();
+    }
+    let names = [&author.given_name, &author.middle_name, &author.family_name];
+    let names: Vec<_> = names
+        .into_iter()
+        .filter(|n| !n.is_empty())
+        .cloned()
+        .collect();
+    names.join(" ")
+}
+
+fn title_text(title: Option<&Title>) -> String {
+    let lines: Vec<_> = title
+        .iter()
+        .flat_map(|t| &t.content)
+        .filter_map(|e| match &e.title_element {
+            Some(title_element::TitleElement::Paragraph(p)) => Some(paragraph_text(&p.content)),
+            _ => None,
+        })
+        .collect();
+    lines.join(" ")
+}
+
+/// Ids of the images of the book, including its cover
+fn resource_ids(book: &Book) -> BTreeSet<&str> {
+    let mut ids = ResourceIds(BTreeSet::new());
+    ids.visit_book(book);
+    if let Some(cover) = &book.cover {
+        ids.0.insert(&cover.id);
+    }
+    ids.0.remove("");
+    ids.0
+}
+
+struct ResourceIds<'a>(BTreeSet<&'a str>);
+
+impl<'a> Visit<'a> for ResourceIds<'a> {
+    fn visit_image(&mut self, image: &'a Image) {
+        self.0.insert(&image.id);
+    }
+
+    fn visit_spans(&mut self, spans: &'a [Span]) {
+        for span in spans {
+            if let Some(span::Span::Image(image)) = &span.span {
+                self.0.insert(&image.id);
+            }
+        }
+    }
+}
+
+/// A step from the old sequence to the new one
+#[derive(Debug, PartialEq)]
+enum Step {
+    Same(usize, usize),
+    /// An item that isn't the same, but is at the same place in both sequences
+    Modified(usize, usize),
+    Removed(usize),
+    Added(usize),
+}
+
+/// Aligns two sequences by their longest common subsequence. Items between the same items are
+/// paired up as modified, as many as there are in both sequences
+fn align<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Step> {
+    let mut steps = vec![];
+    let (mut i, mut j) = (0, 0);
+    for (next_i, next_j) in matches(old, new)
+        .into_iter()
+        .chain([(old.len(), new.len())])
+    {
+        let paired = (next_i - i).min(next_j - j);
+        steps.extend((0..paired).map(|k| Step::Modified(i + k, j + k)));
+        steps.extend((i + paired..next_i).map(Step::Removed));
+        steps.extend((j + paired..next_j).map(Step::Added));
+        if next_i < old.len() {
+            steps.push(Step::Same(next_i, next_j));
+        }
+        (i, j) = (next_i + 1, next_j + 1);
+    }
+    steps
+}
+
+/// Pairs of indices of the same items of the longest common subsequence
+fn matches<T: PartialEq>(old: &[T], new: &[T]) -> Vec<(usize, usize)> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let middle = myers(
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+    let mut matches: Vec<_> = (0..prefix).map(|i| (i, i)).collect();
+    matches.extend(middle.into_iter().map(|(i, j)| (prefix + i, prefix + j)));
+    let (old_suffix, new_suffix) = (old.len() - suffix, new.len() - suffix);
+    matches.extend((0..suffix).map(|k| (old_suffix + k, new_suffix + k)));
+    matches
+}
+
+/// The longest common subsequence by the Myers algorithm, or nothing if there are more than
+/// [`MAX_EDITS`] edits
+fn myers<T: PartialEq>(a: &[T], b: &[T]) -> Vec<(usize, usize)> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (n + m).min(MAX_EDITS as isize);
+    // the furthest x on each diagonal k = x - y, shifted by `offset`
+    let offset = max + 1;
+    let mut v = vec![0; 2 * max as usize + 3];
+    let mut trace = vec![];
+    for d in 0..=max {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if down(&v, k, d, offset) {
+                v[(offset + k + 1) as usize]
+            } else {
+                v[(offset + k - 1) as usize] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[(offset + k) as usize] = x;
+            if x >= n && y >= m {
+                return backtrack(&trace, n, m, offset);
+            }
+        }
+    }
+    vec![]
+}
+
+/// Whether the path to the diagonal `k` in `d` edits comes from the diagonal above
+fn down(v: &[isize], k: isize, d: isize, offset: isize) -> bool {
+    k == -d || (k != d && v[(offset + k - 1) as usize] < v[(offset + k + 1) as usize])
+}
+
+fn backtrack(
+    trace: &[Vec<isize>],
+    mut x: isize,
+    mut y: isize,
+    offset: isize,
+) -> Vec<(usize, usize)> {
+    let mut matches = vec![];
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let previous_k = if down(v, k, d, offset) { k + 1 } else { k - 1 };
+        let previous_x = v[(offset + previous_k) as usize];
+        let previous_y = previous_x - previous_k;
+        while x > previous_x && y > previous_y {
+            x -= 1;
+            y -= 1;
+            matches.push((x as usize, y as usize));
+        }
+        (x, y) = (previous_x, previous_y);
+    }
+    matches.reverse();
+    matches
+}
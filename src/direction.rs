@@ -0,0 +1,131 @@
+use crate::language::locate;
+use crate::visit::{paragraph_text, visit_chapter_mut, VisitMut};
+use crate::{Book, Chapter, Direction, Locator, Paragraph};
+
+/// Scripts written from right to left, as ISO 15924 codes
+const RTL_SCRIPTS: [&str; 10] = [
+    "adlm", "arab", "hebr", "mand", "nkoo", "rohg", "samr", "syrc", "thaa", "yezi",
+];
+
+/// Languages written from right to left, unless their tags name another script
+const RTL_LANGUAGES: [&str; 18] = [
+    "ar", "arc", "ckb", "dv", "fa", "he", "iw", "ji", "ks", "lrc", "mzn", "pnb", "ps", "sd", "syr",
+    "ug", "ur", "yi",
+];
+
+impl Direction {
+    /// The direction of text in the language: right to left for tags with a right-to-left script
+    /// or of languages usually written in one, left to right for other tags, unknown for an empty
+    /// tag
+    pub fn of_language(language: &str) -> Direction {
+        let mut subtags = language.split(['-', '_']).map(str::to_ascii_lowercase);
+        let primary = subtags.next().unwrap_or_default();
+        if primary.is_empty() {
+            return Direction::Unknown;
+        }
+        // the script follows the primary language and extended languages, before singletons
+        let script = subtags
+            .take_while(|s| s.len() > 1)
+            .find(|s| s.len() == 4 && s.chars().all(|c| c.is_ascii_alphabetic()));
+        let rtl = match script {
+            Some(script) => RTL_SCRIPTS.contains(&script.as_str()),
+            None => RTL_LANGUAGES.contains(&primary.as_str()),
+        };
+        if rtl {
+            Direction::Rtl
+        } else {
+            Direction::Ltr
+        }
+    }
+}
+
+impl Book {
+    /// Sets the direction of the book, its chapters and paragraphs that don't have one.
+    ///
+    /// The book and chapters get the direction of their languages, and paragraphs the direction
+    /// of their first strong character according to the Unicode bidirectional algorithm (UAX #9),
+    /// or of their language if they have no such character. Chapters and paragraphs are left
+    /// without a direction if it's the same as the direction around them. It's run by converters
+    /// after the conversion.
+    pub fn assign_directions(&mut self) {
+        if self.direction() == Direction::Unknown {
+            self.set_direction(Direction::of_language(&self.language));
+        }
+        let direction = match self.direction() {
+            Direction::Unknown => Direction::Ltr,
+            direction => direction,
+        };
+        AssignDirections {
+            directions: vec![direction],
+        }
+        .visit_book_mut(self);
+    }
+
+    /// The direction of the paragraph at the locator: its own, of the innermost chapter around
+    /// it that has one, or of the book. Books without directions, such as ones not converted by
+    /// this crate, get the direction of the language at the locator instead. Left to right if
+    /// nothing says otherwise
+    pub fn direction_at(&self, locator: &Locator) -> Direction {
+        let mut directions = vec![self.direction()];
+        if let Some(located) = locate(self, locator) {
+            directions.extend(located.chapters.iter().map(|c| c.direction()));
+            directions.extend(located.paragraph.map(|p| p.direction()));
+        }
+        let direction = directions
+            .into_iter()
+            .rev()
+            .find(|d| *d != Direction::Unknown)
+            .unwrap_or_else(|| Direction::of_language(self.language_at(locator)));
+        match direction {
+            Direction::Unknown => Direction::Ltr,
+            direction => direction,
+        }
+    }
+}
+
+struct AssignDirections {
+    // directions of the book and of the chapters being visited
+    directions: Vec<Direction>,
+}
+
+impl VisitMut for AssignDirections {
+    fn visit_chapter_mut(&mut self, chapter: &mut Chapter) {
+        let parent = *self.directions.last().unwrap();
+        if chapter.direction() == Direction::Unknown {
+            let direction = Direction::of_language(&chapter.language);
+            if direction != Direction::Unknown && direction != parent {
+                chapter.set_direction(direction);
+            }
+        }
+        let direction = match chapter.direction() {
+            Direction::Unknown => parent,
+            direction => direction,
+        };
+        self.directions.push(direction);
+        visit_chapter_mut(self, chapter);
+        self.directions.pop();
+    }
+
+    fn visit_paragraph_mut(&mut self, paragraph: &mut Paragraph) {
+        if paragraph.direction() != Direction::Unknown {
+            return;
+        }
+        let text = paragraph_text(&paragraph.content);
+        let direction = match unicode_bidi::get_base_direction(text.as_str()) {
+            unicode_bidi::Direction::Ltr => Direction::Ltr,
+            unicode_bidi::Direction::Rtl => Direction::Rtl,
+            unicode_bidi::Direction::Mixed => Direction::of_language(&paragraph.language),
+        };
+        if direction != Direction::Unknown && direction != *self.directions.last().unwrap() {
+            paragraph.set_direction(direction);
+        }
+    }
+}
+
+/// Whether the character is an invisible mark or control of the Unicode bidirectional algorithm
+pub(crate) fn is_bidi_control(c: char) -> bool {
+    matches!(
+        c,
+        '\u{061C}' | '\u{200E}' | '\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}'
+    )
+}
@@ -1,161 +1,494 @@
 use language_tags::LanguageTag;
+use std::cell::{OnceCell, RefCell};
 use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
 
+use crate::convert::{
+    bold_text, code_text, concat_text, italic_text, language_text, named_style_text, non_empty,
+    plain_text, split_link_content, strikethrough_text, subscript_text, superscript_text, Budget,
+};
+use crate::visit::{for_each_paragraph, paragraph_text, VisitMut};
 use crate::{
     annotation_element, cite_element, content, epigraph_element, link, poem_element, span,
-    title_element, Annotation, AnnotationElement, Author, BaselineShift, Book, Chapter, Cite,
-    CiteElement, Content, Date, EmptyLine, Epigraph, EpigraphElement, FontStyle, Footnote,
-    FootnoteLink, FootnoteType, Footnotes, Image, InlineImage, Link, Paragraph, Poem, PoemElement,
-    Span, Stanza, Table, TableCell, TableRow, Text, TextDecoration, Title, TitleElement,
+    title_element, Annotation, AnnotationElement, Author, Book, Chapter, Cite, CiteElement,
+    Content, ConvertOptions, ConvertReport, Date, Direction, EmptyLine, Epigraph, EpigraphElement,
+    Footnote, FootnoteLink, FootnoteType, Footnotes, Image, InlineImage, LimitError, Link,
+    Paragraph, PartialDate, Poem, PoemElement, RejectedLink, Span, Stanza, Table, TableCell,
+    TableRow, Text, Title, TitleElement,
 };
 
-const BOLD_WEIGHT: u32 = 600;
-
 impl Book {
     pub fn from_fb2(
         book: fb2::FictionBook,
         book_id: Uuid,
         binary_ids: &HashMap<String, Uuid>,
-    ) -> Book {
-        let short_title = book.description.title_info.book_title.value;
-        let date = book.description.title_info.date.map(|d| Date {
-            iso_date: d.iso_date.map(|date| date.to_string()).unwrap_or_default(),
-            display_date: d.display_date.unwrap_or_default(),
-        });
-        let authors = book
-            .description
-            .title_info
+    ) -> Result<Book, LimitError> {
+        Book::from_fb2_with_options(book, book_id, binary_ids, &ConvertOptions::default())
+    }
+
+    pub fn from_fb2_with_options(
+        book: fb2::FictionBook,
+        book_id: Uuid,
+        binary_ids: &HashMap<String, Uuid>,
+        options: &ConvertOptions,
+    ) -> Result<Book, LimitError> {
+        Book::from_fb2_with_report(book, book_id, binary_ids, options).map(|(book, _)| book)
+    }
+
+    /// Like [`Book::from_fb2_with_options`], but also reports what was lost in the conversion,
+    /// such as links that became text. Fails if the book or its binaries exceed
+    /// [`ConvertOptions::limits`]
+    pub fn from_fb2_with_report(
+        book: fb2::FictionBook,
+        book_id: Uuid,
+        binary_ids: &HashMap<String, Uuid>,
+        options: &ConvertOptions,
+    ) -> Result<(Book, ConvertReport), LimitError> {
+        let mut conversion = Fb2Conversion::new(binary_ids, options);
+        for binary in &book.binaries {
+            conversion.resource(&binary.id, binary.content.len())?;
+        }
+        let kinds = body_kinds(book.bodies.iter().map(|b| b.name.as_deref()), options);
+        let mut bodies = book.bodies.into_iter().zip(kinds).collect::<Vec<_>>();
+        for (body, kind) in &mut bodies {
+            let footnote_type = match kind {
+                BodyKind::Notes => FootnoteType::Note,
+                BodyKind::Comments => FootnoteType::Comment,
+                _ => continue,
+            };
+            if let Some(title) = body.title.take() {
+                conversion.footnotes_title(footnote_type, title);
+            }
+            for section in std::mem::take(&mut body.sections) {
+                conversion.footnote(footnote_type, section);
+            }
+        }
+        conversion.description(book.description);
+        for (body, kind) in bodies {
+            match kind {
+                BodyKind::Main => {
+                    conversion.main_body(body.lang);
+                    if let Some(title) = body.title {
+                        conversion.main_title(title);
+                    }
+                    for epigraph in body.epigraphs {
+                        conversion.main_epigraph(epigraph);
+                    }
+                    for section in body.sections {
+                        conversion.main_section(section);
+                    }
+                }
+                BodyKind::Other => {
+                    for section in body.sections {
+                        conversion.other_section(section);
+                    }
+                }
+                BodyKind::Notes | BodyKind::Comments => {}
+            }
+        }
+        conversion.finish(book_id)
+    }
+}
+
+/// What a body of an FB2 book is converted into
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum BodyKind {
+    Main,
+    Notes,
+    Comments,
+    /// A body whose sections are only looked into for the targets of links
+    Other,
+}
+
+/// Kinds of the bodies by their names, the first unnamed body being the main one
+pub(crate) fn body_kinds<'n>(
+    names: impl IntoIterator<Item = Option<&'n str>>,
+    options: &ConvertOptions,
+) -> Vec<BodyKind> {
+    let matches =
+        |patterns: &[String], name: &str| patterns.iter().any(|p| matches_pattern(p, name));
+    let mut main = false;
+    names
+        .into_iter()
+        .map(|name| match name {
+            None if !main => {
+                main = true;
+                BodyKind::Main
+            }
+            Some(name) if matches(&options.note_bodies, name) => BodyKind::Notes,
+            Some(name) if matches(&options.comment_bodies, name) => BodyKind::Comments,
+            _ => BodyKind::Other,
+        })
+        .collect()
+}
+
+/// An FB2 book converted part by part, either from a deserialized book or while it's being read.
+/// Notes and comments come first, as links of the rest of the book are converted according to
+/// them
+pub(crate) struct Fb2Conversion<'a> {
+    binaries: &'a HashMap<String, Uuid>,
+    options: &'a ConvertOptions,
+    budget: Budget<'a>,
+    rejected_links: RefCell<Vec<RejectedLink>>,
+    // no footnotes, for the links of footnotes
+    no_ids: HashSet<String>,
+    // ids of notes and comments, once the rest of the book is being converted
+    footnote_ids: OnceCell<(HashSet<String>, HashSet<String>)>,
+    notes: Footnotes,
+    comments: Footnotes,
+    description: Option<Description>,
+    language: Option<String>,
+    title: Option<Title>,
+    epigraphs: Vec<Epigraph>,
+    chapters: Vec<Chapter>,
+    others: Vec<Chapter>,
+}
+
+/// The converted description of a book
+struct Description {
+    short_title: String,
+    date: Option<Date>,
+    authors: Vec<Author>,
+    language: String,
+    cover: Option<InlineImage>,
+    annotation: Option<Annotation>,
+}
+
+impl<'a> Fb2Conversion<'a> {
+    pub(crate) fn new(
+        binaries: &'a HashMap<String, Uuid>,
+        options: &'a ConvertOptions,
+    ) -> Fb2Conversion<'a> {
+        Fb2Conversion {
+            binaries,
+            options,
+            budget: Budget::new(&options.limits),
+            rejected_links: RefCell::new(vec![]),
+            no_ids: HashSet::new(),
+            footnote_ids: OnceCell::new(),
+            notes: Footnotes::default(),
+            comments: Footnotes::default(),
+            description: None,
+            language: None,
+            title: None,
+            epigraphs: vec![],
+            chapters: vec![],
+            others: vec![],
+        }
+    }
+
+    /// Checks the size of a binary
+    pub(crate) fn resource(&self, id: &str, size: usize) -> Result<(), LimitError> {
+        self.budget.resource(id, size);
+        self.check()
+    }
+
+    /// Fails with the first exceeded limit, so that a book being read isn't read any further
+    pub(crate) fn check(&self) -> Result<(), LimitError> {
+        self.budget.check()
+    }
+
+    /// The title of a body of notes or comments, the first one being the title of all of them
+    pub(crate) fn footnotes_title(&mut self, footnote_type: FootnoteType, title: fb2::Title) {
+        let title = Title::from_fb2(title, &self.footnotes_context());
+        let footnotes = self.footnotes_mut(footnote_type);
+        if footnotes.title.is_none() {
+            footnotes.title = title;
+        }
+    }
+
+    pub(crate) fn footnote(&mut self, footnote_type: FootnoteType, section: fb2::Section) {
+        let footnote = Footnote::from_fb2(section, &self.footnotes_context());
+        self.footnotes_mut(footnote_type).content.extend(footnote);
+    }
+
+    pub(crate) fn description(&mut self, description: fb2::Description) {
+        let title_info = description.title_info;
+        let date = title_info.date.map(Date::from_fb2);
+        let authors = title_info
             .authors
             .into_iter()
-            .filter_map(|a| Author::from_fb2(a))
+            .filter_map(Author::from_fb2)
             .collect();
-        let language = non_empty(book.description.title_info.lang)
+        let language = non_empty(title_info.lang)
             .filter(|lang| lang.parse::<LanguageTag>().is_ok())
             .unwrap_or_default();
-        let cover = book
-            .description
-            .title_info
+        let cover = title_info
             .cover_page
             .into_iter()
             .flat_map(|c| c.images)
-            .filter_map(|i| InlineImage::from_fb2(i, binary_ids))
+            .filter_map(|i| InlineImage::from_fb2(i, self.binaries))
             .next();
+        let annotation = title_info
+            .annotation
+            .and_then(|a| Annotation::from_fb2(a, &self.context()));
+        self.description = Some(Description {
+            short_title: title_info.book_title.value,
+            date,
+            authors,
+            language,
+            cover,
+            annotation,
+        });
+    }
+
+    /// Starts the main body, whose language overrides the language of the description
+    pub(crate) fn main_body(&mut self, language: Option<LanguageTag>) {
+        self.language = language.map(|l| l.to_string());
+    }
+
+    pub(crate) fn main_title(&mut self, title: fb2::Title) {
+        self.title = Title::from_fb2(title, &self.context());
+    }
+
+    pub(crate) fn main_epigraph(&mut self, epigraph: fb2::Epigraph) {
+        let epigraph = Epigraph::from_fb2(epigraph, &self.context());
+        self.epigraphs.extend(epigraph);
+    }
 
-        let mut bodies: HashMap<Option<String>, Vec<fb2::Body>> = HashMap::new();
-        for body in book.bodies {
-            bodies.entry(body.name.clone()).or_default().push(body);
+    pub(crate) fn main_section(&mut self, section: fb2::Section) {
+        let chapter = Chapter::from_fb2(section, &self.context());
+        self.chapters.extend(chapter);
+    }
+
+    /// A section of a body that is neither the main one nor one of footnotes
+    pub(crate) fn other_section(&mut self, section: fb2::Section) {
+        if self.options.note_link_targets {
+            let chapter = Chapter::from_fb2(section, &self.context());
+            self.others.extend(chapter);
         }
-        let body = bodies.remove(&None).unwrap_or_default().into_iter().next();
-        let notes = bodies
-            .remove(&Some("notes".to_string()))
-            .unwrap_or_default()
-            .into_iter()
-            .next()
-            .and_then(|b| {
-                Footnotes::from_fb2(
-                    b,
-                    &Context {
-                        binaries: binary_ids,
-                        notes: HashSet::new(),
-                        comments: HashSet::new(),
-                    },
-                )
-            });
-        let comments = bodies
-            .remove(&Some("comments".to_string()))
-            .unwrap_or_default()
-            .into_iter()
-            .next()
-            .and_then(|b| {
-                Footnotes::from_fb2(
-                    b,
-                    &Context {
-                        binaries: binary_ids,
-                        notes: HashSet::new(),
-                        comments: HashSet::new(),
-                    },
-                )
-            });
+    }
 
-        let note_ids = notes
-            .as_ref()
-            .map(|n| n.content.keys().map(|k| k.to_string()).collect())
-            .unwrap_or_default();
-        let comment_ids = comments
-            .as_ref()
-            .map(|c| c.content.keys().map(|k| k.to_string()).collect())
-            .unwrap_or_default();
-        let ctx = Context {
-            binaries: binary_ids,
-            notes: note_ids,
-            comments: comment_ids,
+    pub(crate) fn finish(self, book_id: Uuid) -> Result<(Book, ConvertReport), LimitError> {
+        let description = self.description.unwrap_or_else(|| Description {
+            short_title: String::new(),
+            date: None,
+            authors: vec![],
+            language: String::new(),
+            cover: None,
+            annotation: None,
+        });
+        let non_empty_footnotes = |f: Footnotes| (!f.content.is_empty()).then_some(f);
+        let mut book = Book {
+            id: book_id.to_string(),
+            language: self.language.unwrap_or(description.language),
+            short_title: description.short_title,
+            date: description.date,
+            authors: description.authors,
+            cover: description.cover,
+            annotation: description.annotation,
+            title: self.title,
+            epigraphs: self.epigraphs,
+            chapters: self.chapters,
+            notes: non_empty_footnotes(self.notes),
+            comments: non_empty_footnotes(self.comments),
+            statistics: None,
+            media_overlay: None,
+            direction: Direction::Unknown.into(),
+        };
+        if self.options.note_link_targets {
+            let mut others = self.others;
+            resolve_note_links(&mut book, &mut others, &self.budget);
+        }
+        for footnotes in [&mut book.notes, &mut book.comments].into_iter().flatten() {
+            for (footnote, number) in footnotes.content.iter_mut().zip(1..) {
+                if footnote.label.is_empty() {
+                    footnote.label = number.to_string();
+                }
+            }
+        }
+        self.options.finish(&mut book);
+        self.budget.finish()?;
+        let report = ConvertReport {
+            rejected_links: self.rejected_links.into_inner(),
         };
+        Ok((book, report))
+    }
 
-        let annotation = book
-            .description
-            .title_info
-            .annotation
-            .and_then(|a| Annotation::from_fb2(a, &ctx));
+    fn footnotes_mut(&mut self, footnote_type: FootnoteType) -> &mut Footnotes {
+        match footnote_type {
+            FootnoteType::Comment => &mut self.comments,
+            _ => &mut self.notes,
+        }
+    }
 
-        let (chapters, language, title, epigraphs) = if let Some(body) = body {
-            let chapters = body
-                .sections
-                .into_iter()
-                .filter_map(|s| Chapter::from_fb2(s, &ctx))
-                .collect();
-            let language = body.lang.map(|l| l.to_string()).unwrap_or(language);
-            let title = body.title.and_then(|t| Title::from_fb2(t, &ctx));
-            let epigraphs = body
-                .epigraphs
-                .into_iter()
-                .filter_map(|e| Epigraph::from_fb2(e, &ctx))
-                .collect();
-            (chapters, language, title, epigraphs)
-        } else {
-            (vec![], language, None, vec![])
-        };
+    /// Context of notes and comments, whose links aren't links to footnotes
+    fn footnotes_context(&self) -> Context<'_> {
+        Context {
+            binaries: self.binaries,
+            notes: &self.no_ids,
+            comments: &self.no_ids,
+            options: self.options,
+            rejected_links: &self.rejected_links,
+            budget: &self.budget,
+        }
+    }
 
-        Book {
-            id: book_id.to_string(),
-            language,
-            short_title,
-            date,
-            authors,
-            cover,
-            annotation,
-            title,
-            epigraphs,
-            chapters,
+    /// Context of the rest of the book
+    fn context(&self) -> Context<'_> {
+        let (notes, comments) = self.footnote_ids.get_or_init(|| {
+            let ids = |f: &Footnotes| f.content.iter().map(|f| f.id.clone()).collect();
+            (ids(&self.notes), ids(&self.comments))
+        });
+        Context {
+            binaries: self.binaries,
             notes,
             comments,
+            options: self.options,
+            rejected_links: &self.rejected_links,
+            budget: &self.budget,
         }
     }
 }
 
 struct Context<'a> {
     binaries: &'a HashMap<String, Uuid>,
-    notes: HashSet<String>,
-    comments: HashSet<String>,
+    notes: &'a HashSet<String>,
+    comments: &'a HashSet<String>,
+    options: &'a ConvertOptions,
+    // links that became text
+    rejected_links: &'a RefCell<Vec<RejectedLink>>,
+    budget: &'a Budget<'a>,
 }
 
-impl Footnotes {
-    fn from_fb2(body: fb2::Body, ctx: &Context) -> Option<Footnotes> {
-        let content = body
-            .sections
+/// Moves sections targeted by links to unknown notes, made for links of `type="note"`, from
+/// `others` or from chapters into notes, if they have nothing but a title and content. Links to
+/// chapters that stay in place become local links, and links to sections that can't be found
+/// become text
+fn resolve_note_links(book: &mut Book, others: &mut Vec<Chapter>, budget: &Budget) {
+    let mut note_ids = book
+        .notes
+        .iter()
+        .flat_map(|n| &n.content)
+        .map(|f| f.id.clone())
+        .collect::<HashSet<_>>();
+    let mut targets = vec![];
+    for_each_paragraph(book, |_, spans| {
+        for span in spans {
+            if let Some(span::Span::Footnote(link)) = &span.span {
+                if link.r#type() == FootnoteType::Note
+                    && !note_ids.contains(&link.id)
+                    && !targets.contains(&link.id)
+                {
+                    targets.push(link.id.clone());
+                }
+            }
+        }
+    });
+    let mut chapter_ids = HashSet::new();
+    for id in targets {
+        let Some(chapter) =
+            take_chapter(others, &id).or_else(|| take_chapter(&mut book.chapters, &id))
+        else {
+            if has_chapter(&book.chapters, &id) {
+                chapter_ids.insert(id);
+            }
+            continue;
+        };
+        if !budget.footnote() {
+            break;
+        }
+        book.notes
+            .get_or_insert_with(Footnotes::default)
+            .content
+            .push(Footnote::from_chapter(chapter));
+        note_ids.insert(id);
+    }
+    UnresolvedNoteLinks {
+        note_ids,
+        chapter_ids,
+    }
+    .visit_book_mut(book);
+}
+
+/// Removes the chapter with the anchor from the chapters or their sub-chapters, if a footnote
+/// can hold all of it
+fn take_chapter(chapters: &mut Vec<Chapter>, anchor: &str) -> Option<Chapter> {
+    if let Some(i) = chapters.iter().position(|c| c.anchor == anchor) {
+        return Footnote::holds(&chapters[i]).then(|| chapters.remove(i));
+    }
+    chapters
+        .iter_mut()
+        .find_map(|c| take_chapter(&mut c.sub_chapters, anchor))
+}
+
+/// Whether there's a chapter with the anchor among the chapters or their sub-chapters
+fn has_chapter(chapters: &[Chapter], anchor: &str) -> bool {
+    chapters
+        .iter()
+        .any(|c| c.anchor == anchor || has_chapter(&c.sub_chapters, anchor))
+}
+
+/// Turns links to notes that don't exist into local links to the chapters they target, or into
+/// their text
+struct UnresolvedNoteLinks {
+    note_ids: HashSet<String>,
+    chapter_ids: HashSet<String>,
+}
+
+impl VisitMut for UnresolvedNoteLinks {
+    fn visit_spans_mut(&mut self, spans: &mut Vec<Span>) {
+        let resolved = |span: &Span| match &span.span {
+            Some(span::Span::Footnote(link)) => {
+                link.r#type() != FootnoteType::Note || self.note_ids.contains(&link.id)
+            }
+            _ => true,
+        };
+        if spans.iter().all(resolved) {
+            return;
+        }
+        *spans = std::mem::take(spans)
             .into_iter()
-            .filter_map(|s| Footnote::from_fb2(s, ctx))
-            .collect::<HashMap<_, _>>();
-        if content.is_empty() {
-            return None;
+            .flat_map(|span| match span.span {
+                Some(span::Span::Footnote(link)) if !resolved(&span) => {
+                    if self.chapter_ids.contains(&link.id) {
+                        vec![Span {
+                            span: Some(span::Span::Link(Link {
+                                href: Some(link::Href::Local(link.id)),
+                                content: link.content,
+                            })),
+                        }]
+                    } else {
+                        link.content
+                            .into_iter()
+                            .map(|t| Span {
+                                span: Some(span::Span::Text(t)),
+                            })
+                            .collect()
+                    }
+                }
+                _ => vec![span],
+            })
+            .collect();
+    }
+}
+
+/// Whether the name of a body matches the pattern, where `*` matches any characters, regardless
+/// of case
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let name = name.to_lowercase();
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts = parts.collect::<Vec<_>>();
+    let Some(last) = parts.pop() else {
+        // no wildcards
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
         }
-        let title = body.title.and_then(|t| Title::from_fb2(t, ctx));
-        Some(Footnotes { title, content })
     }
+    rest.ends_with(last)
 }
 
 impl Footnote {
-    fn from_fb2(value: fb2::Section, ctx: &Context) -> Option<(String, Footnote)> {
+    fn from_fb2(value: fb2::Section, ctx: &Context) -> Option<Footnote> {
         let id = value.id.and_then(non_empty)?;
         let section_content = value.content?;
         let content = section_content
@@ -163,11 +496,84 @@ impl Footnote {
             .into_iter()
             .filter_map(|c| Content::from_fb2(c, ctx))
             .collect::<Vec<_>>();
-        if content.is_empty() {
+        if content.is_empty() || !ctx.budget.footnote() {
             return None;
         }
         let title = section_content.title.and_then(|t| Title::from_fb2(t, ctx));
-        Some((id, Footnote { title, content }))
+        Some(Footnote {
+            label: footnote_label(title.as_ref()),
+            title,
+            content,
+            id,
+        })
+    }
+
+    /// Whether a footnote can hold all of the chapter: it has no sub-chapters, annotation, cover
+    /// or epigraphs
+    fn holds(chapter: &Chapter) -> bool {
+        chapter.sub_chapters.is_empty()
+            && chapter.annotation.is_none()
+            && chapter.cover.is_none()
+            && chapter.epigraphs.is_empty()
+    }
+
+    /// Makes a footnote of a section converted as a chapter that it [holds](Footnote::holds)
+    fn from_chapter(chapter: Chapter) -> Footnote {
+        Footnote {
+            label: footnote_label(chapter.title.as_ref()),
+            title: chapter.title,
+            content: chapter.content,
+            id: chapter.anchor,
+        }
+    }
+}
+
+/// The text of the title of a footnote, which is usually its number
+fn footnote_label(title: Option<&Title>) -> String {
+    let label = title
+        .iter()
+        .flat_map(|t| &t.content)
+        .filter_map(|e| match &e.title_element {
+            Some(title_element::TitleElement::Paragraph(p)) => Some(paragraph_text(&p.content)),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    label.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The language of an element, empty if it has the language of the surrounding text
+fn language(lang: Option<LanguageTag>) -> String {
+    lang.map(|l| l.to_string()).unwrap_or_default()
+}
+
+impl Date {
+    /// Keeps the precision, the range and the approximation of the displayed date if it agrees
+    /// with the ISO date, as FB2 requires a full ISO date even if only the year is known
+    fn from_fb2(value: fb2::Date) -> Date {
+        let display_date = value.display_date.unwrap_or_default();
+        let displayed = Date::parse(&display_date);
+        let Some(iso_date) = value.iso_date else {
+            return displayed.unwrap_or(Date {
+                display_date,
+                ..Default::default()
+            });
+        };
+        // a full ISO date is the first day of the less precise dates it stands for
+        let iso_date = PartialDate::parse_iso(&iso_date.to_string());
+        match displayed {
+            Some(date)
+                if date
+                    .start()
+                    .is_some_and(|s| Some(s.first_day()) == iso_date) =>
+            {
+                date
+            }
+            _ => Date {
+                display_date,
+                ..iso_date.map(Date::new).unwrap_or_default()
+            },
+        }
     }
 }
 
@@ -221,6 +627,10 @@ impl Author {
 impl Chapter {
     fn from_fb2(section: fb2::Section, ctx: &Context) -> Option<Chapter> {
         let section_content = section.content?;
+        if !ctx.budget.chapter() {
+            return None;
+        }
+        let _depth = ctx.budget.enter()?;
 
         let content = section_content
             .content
@@ -257,6 +667,9 @@ impl Chapter {
             epigraphs,
             content,
             sub_chapters,
+            statistics: None,
+            language: language(section.lang),
+            direction: Direction::Unknown.into(),
         })
     }
 }
@@ -291,10 +704,18 @@ impl Content {
 
 impl Annotation {
     fn from_fb2(value: fb2::Annotation, ctx: &Context) -> Option<Annotation> {
+        let language = language(value.lang);
         let content = value
             .elements
             .into_iter()
             .filter_map(|a| AnnotationElement::from_fb2(a, ctx))
+            .map(|mut e| {
+                use annotation_element::AnnotationElement::{Paragraph, Subtitle};
+                if let Some(Paragraph(p) | Subtitle(p)) = &mut e.annotation_element {
+                    *p = std::mem::take(p).inherit_language(&language);
+                }
+                e
+            })
             .collect::<Vec<_>>();
         if content.is_empty() {
             return None;
@@ -358,6 +779,7 @@ impl Epigraph {
             anchor: value.id.unwrap_or_default(),
             authors,
             content,
+            language: String::new(),
         })
     }
 }
@@ -385,6 +807,7 @@ impl EpigraphElement {
 
 impl Poem {
     fn from_fb2(value: fb2::Poem, ctx: &Context) -> Option<Poem> {
+        let _depth = ctx.budget.enter()?;
         let content = value
             .stanzas
             .into_iter()
@@ -410,6 +833,7 @@ impl Poem {
             epigraphs,
             authors,
             content,
+            language: language(value.lang),
         })
     }
 }
@@ -429,16 +853,21 @@ impl PoemElement {
 
 impl Stanza {
     fn from_fb2(value: fb2::Stanza, ctx: &Context) -> Option<Stanza> {
+        let language = language(value.lang);
         let content = value
             .lines
             .into_iter()
             .filter_map(|l| Paragraph::from_fb2(l, ctx))
+            .map(|l| l.inherit_language(&language))
             .collect::<Vec<_>>();
         if content.is_empty() {
             return None;
         }
         let title = value.title.and_then(|t| Title::from_fb2(t, ctx));
-        let subtitle = value.subtitle.and_then(|s| Paragraph::from_fb2(s, ctx));
+        let subtitle = value
+            .subtitle
+            .and_then(|s| Paragraph::from_fb2(s, ctx))
+            .map(|s| s.inherit_language(&language));
         Some(Stanza {
             title,
             subtitle,
@@ -449,6 +878,7 @@ impl Stanza {
 
 impl Cite {
     fn from_fb2(value: fb2::Cite, ctx: &Context) -> Option<Cite> {
+        let _depth = ctx.budget.enter()?;
         let content = value
             .elements
             .into_iter()
@@ -466,6 +896,7 @@ impl Cite {
             anchor: value.id.unwrap_or_default(),
             content,
             authors,
+            language: language(value.lang),
         })
     }
 }
@@ -494,10 +925,17 @@ impl CiteElement {
 
 impl Title {
     fn from_fb2(value: fb2::Title, ctx: &Context) -> Option<Title> {
+        let language = language(value.lang);
         let content = value
             .elements
             .into_iter()
             .filter_map(|e| TitleElement::from_fb2(e, ctx))
+            .map(|mut e| {
+                if let Some(title_element::TitleElement::Paragraph(p)) = &mut e.title_element {
+                    *p = std::mem::take(p).inherit_language(&language);
+                }
+                e
+            })
             .collect::<Vec<_>>();
         if content.is_empty() {
             return None;
@@ -522,16 +960,41 @@ impl TitleElement {
 impl Paragraph {
     fn from_fb2(paragraph: fb2::Paragraph, ctx: &Context) -> Option<Paragraph> {
         let anchor = paragraph.id.unwrap_or_default();
+        let language = language(paragraph.lang);
+        // styles in the style map apply to the text, and the rest become classes of the paragraph
+        let style = paragraph.style.unwrap_or_default();
+        let (styles, classes): (Vec<_>, Vec<_>) = style
+            .split_whitespace()
+            .partition(|name| ctx.options.style_map.contains_key(*name));
         let content = paragraph
             .elements
             .into_iter()
             .flat_map(|e| Span::from_fb2(e, ctx))
+            .map(|span| {
+                styles
+                    .iter()
+                    .fold(span, |span, name| named_style_text(ctx.options, name)(span))
+            })
             .collect::<Vec<_>>();
-        if content.is_empty() {
+        if content.is_empty() || !ctx.budget.spans(content.len()) {
             None
         } else {
-            Some(Paragraph { anchor, content })
+            Some(Paragraph {
+                anchor,
+                content,
+                language,
+                direction: Direction::Unknown.into(),
+                classes: classes.into_iter().map(String::from).collect(),
+            })
+        }
+    }
+
+    /// Sets the language of the element the paragraph belongs to, unless it has its own
+    fn inherit_language(mut self, language: &str) -> Paragraph {
+        if self.language.is_empty() {
+            self.language = language.to_string();
         }
+        self
     }
 }
 
@@ -590,13 +1053,15 @@ impl Table {
 impl TableCell {
     // we don't return Option<TableCell> because it can break layout
     fn from_fb2(value: fb2::TableCell, ctx: &Context) -> TableCell {
+        let content = value
+            .elements
+            .into_iter()
+            .flat_map(|e| Span::from_fb2(e, ctx))
+            .collect::<Vec<_>>();
+        ctx.budget.spans(content.len());
         TableCell {
             anchor: value.id.unwrap_or_default(),
-            content: value
-                .elements
-                .into_iter()
-                .flat_map(|e| Span::from_fb2(e, ctx))
-                .collect(),
+            content,
         }
     }
 }
@@ -619,49 +1084,55 @@ impl Image {
 impl Span {
     fn from_fb2(element: fb2::StyleElement, ctx: &Context) -> Vec<Span> {
         let mut spans = vec![];
+        let Some(_depth) = ctx.budget.enter() else {
+            return spans;
+        };
         match element {
             fb2::StyleElement::Strong(s) => spans.extend(
                 s.elements
                     .into_iter()
                     .flat_map(|e| Span::from_fb2(e, ctx))
-                    .map(bold_text),
+                    .map(bold_text)
+                    .map(language_text(language(s.lang))),
             ),
             fb2::StyleElement::Emphasis(e) => spans.extend(
                 e.elements
                     .into_iter()
                     .flat_map(|e| Span::from_fb2(e, ctx))
-                    .map(italic_text),
+                    .map(italic_text)
+                    .map(language_text(language(e.lang))),
+            ),
+            fb2::StyleElement::Style(s) => spans.extend(
+                s.elements
+                    .into_iter()
+                    .flat_map(|e| Span::from_fb2(e, ctx))
+                    .map(named_style_text(ctx.options, s.name.trim()))
+                    .map(language_text(language(s.lang))),
             ),
-            fb2::StyleElement::Style(s) => {
-                spans.extend(s.elements.into_iter().flat_map(|e| Span::from_fb2(e, ctx)))
-            }
             fb2::StyleElement::Link(l) => {
-                let href = l.href.and_then(link::Href::from_fb2);
                 let content = l
                     .elements
                     .into_iter()
-                    .flat_map(|e| Span::from_fb2_link(e, ctx.binaries));
+                    .flat_map(|e| Span::from_fb2_link(e, ctx));
+                let href = match l.href.as_deref().map(|href| ctx.options.href(href)) {
+                    Some(Ok(href)) => href,
+                    Some(Err(reason)) => {
+                        let content = content.collect::<Vec<_>>();
+                        ctx.rejected_links.borrow_mut().push(RejectedLink {
+                            href: l.href.unwrap_or_default(),
+                            text: concat_text(split_link_content(content.clone()).0),
+                            reason,
+                        });
+                        spans.extend(content);
+                        return spans;
+                    }
+                    None => None,
+                };
 
                 if let Some(href) = href {
-                    let mut images = vec![];
-                    let mut text = vec![];
-                    for span in content {
-                        match span.span.unwrap() {
-                            span::Span::Footnote(_) | span::Span::Link(_) => {}
-                            span::Span::Image(i) => images.push(Span {
-                                span: Some(span::Span::Image(i)),
-                            }),
-                            span::Span::Text(t) => text.push(t),
-                        }
-                    }
+                    let (text, images) = split_link_content(content);
                     if let Some(id) = ctx.binaries.get(href.as_ref()) {
-                        let alt =
-                            text.into_iter()
-                                .map(|t| t.value)
-                                .fold(String::new(), |mut a, b| {
-                                    a.push_str(&b);
-                                    a
-                                });
+                        let alt = concat_text(text);
                         spans.push(Span {
                             span: Some(span::Span::Image(InlineImage {
                                 id: id.clone().to_string(),
@@ -670,25 +1141,36 @@ impl Span {
                         });
                     } else if !text.is_empty() {
                         if ctx.notes.contains(href.as_ref()) {
-                            let mut footnote_link = FootnoteLink::default();
-                            footnote_link.id = href.as_ref().to_string();
-                            footnote_link.set_type(FootnoteType::Note);
-                            footnote_link.content = text;
                             spans.push(Span {
-                                span: Some(span::Span::Footnote(footnote_link)),
+                                span: Some(span::Span::Footnote(FootnoteLink {
+                                    id: href.as_ref().to_string(),
+                                    r#type: FootnoteType::Note.into(),
+                                    content: text,
+                                })),
                             });
                         } else if ctx.comments.contains(href.as_ref()) {
-                            let mut footnote_link = FootnoteLink::default();
-                            footnote_link.id = href.as_ref().to_string();
-                            footnote_link.set_type(FootnoteType::Comment);
-                            footnote_link.content = text;
                             spans.push(Span {
-                                span: Some(span::Span::Footnote(footnote_link)),
+                                span: Some(span::Span::Footnote(FootnoteLink {
+                                    id: href.as_ref().to_string(),
+                                    r#type: FootnoteType::Comment.into(),
+                                    content: text,
+                                })),
                             });
                         } else if "note" == l.kind.and_then(non_empty).unwrap_or_default() {
-                            spans.extend(text.into_iter().map(|t| Span {
-                                span: Some(span::Span::Text(t)),
-                            }));
+                            if ctx.options.note_link_targets && matches!(href, link::Href::Local(_))
+                            {
+                                spans.push(Span {
+                                    span: Some(span::Span::Footnote(FootnoteLink {
+                                        id: href.as_ref().to_string(),
+                                        r#type: FootnoteType::Note.into(),
+                                        content: text,
+                                    })),
+                                });
+                            } else {
+                                spans.extend(text.into_iter().map(|t| Span {
+                                    span: Some(span::Span::Text(t)),
+                                }));
+                            }
                         } else {
                             spans.push(Span {
                                 span: Some(span::Span::Link(Link {
@@ -707,25 +1189,29 @@ impl Span {
                 s.elements
                     .into_iter()
                     .flat_map(|e| Span::from_fb2(e, ctx))
-                    .map(strikethrough_text),
+                    .map(strikethrough_text)
+                    .map(language_text(language(s.lang))),
             ),
             fb2::StyleElement::Subscript(s) => spans.extend(
                 s.elements
                     .into_iter()
                     .flat_map(|e| Span::from_fb2(e, ctx))
-                    .map(subscript_text),
+                    .map(subscript_text)
+                    .map(language_text(language(s.lang))),
             ),
             fb2::StyleElement::Superscript(s) => spans.extend(
                 s.elements
                     .into_iter()
                     .flat_map(|e| Span::from_fb2(e, ctx))
-                    .map(superscript_text),
+                    .map(superscript_text)
+                    .map(language_text(language(s.lang))),
             ),
             fb2::StyleElement::Code(c) => spans.extend(
                 c.elements
                     .into_iter()
                     .flat_map(|e| Span::from_fb2(e, ctx))
-                    .map(code_text),
+                    .map(code_text)
+                    .map(language_text(language(c.lang))),
             ),
             fb2::StyleElement::Image(i) => {
                 if let Some(i) = InlineImage::from_fb2(i, ctx.binaries) {
@@ -735,7 +1221,7 @@ impl Span {
                 }
             }
             fb2::StyleElement::Text(t) => {
-                if let Some(text) = Text::from_fb2(t) {
+                if let Some(text) = Text::from_fb2(t, ctx) {
                     spans.push(Span {
                         span: Some(span::Span::Text(text)),
                     });
@@ -745,62 +1231,62 @@ impl Span {
         spans
     }
 
-    fn from_fb2_link(
-        element: fb2::StyleLinkElement,
-        binary_ids: &HashMap<String, Uuid>,
-    ) -> Vec<Span> {
+    fn from_fb2_link(element: fb2::StyleLinkElement, ctx: &Context) -> Vec<Span> {
         let mut spans = vec![];
+        let Some(_depth) = ctx.budget.enter() else {
+            return spans;
+        };
         match element {
             fb2::StyleLinkElement::Strong { elements } => spans.extend(
                 elements
                     .into_iter()
-                    .flat_map(|e| Span::from_fb2_link(e, binary_ids))
+                    .flat_map(|e| Span::from_fb2_link(e, ctx))
                     .map(bold_text),
             ),
             fb2::StyleLinkElement::Emphasis { elements } => spans.extend(
                 elements
                     .into_iter()
-                    .flat_map(|e| Span::from_fb2_link(e, binary_ids))
+                    .flat_map(|e| Span::from_fb2_link(e, ctx))
                     .map(italic_text),
             ),
             fb2::StyleLinkElement::Style { elements } => spans.extend(
                 elements
                     .into_iter()
-                    .flat_map(|e| Span::from_fb2_link(e, binary_ids)),
+                    .flat_map(|e| Span::from_fb2_link(e, ctx)),
             ),
             fb2::StyleLinkElement::Strikethrough { elements } => spans.extend(
                 elements
                     .into_iter()
-                    .flat_map(|e| Span::from_fb2_link(e, binary_ids))
+                    .flat_map(|e| Span::from_fb2_link(e, ctx))
                     .map(strikethrough_text),
             ),
             fb2::StyleLinkElement::Subscript { elements } => spans.extend(
                 elements
                     .into_iter()
-                    .flat_map(|e| Span::from_fb2_link(e, binary_ids))
+                    .flat_map(|e| Span::from_fb2_link(e, ctx))
                     .map(subscript_text),
             ),
             fb2::StyleLinkElement::Superscript { elements } => spans.extend(
                 elements
                     .into_iter()
-                    .flat_map(|e| Span::from_fb2_link(e, binary_ids))
+                    .flat_map(|e| Span::from_fb2_link(e, ctx))
                     .map(superscript_text),
             ),
             fb2::StyleLinkElement::Code { elements } => spans.extend(
                 elements
                     .into_iter()
-                    .flat_map(|e| Span::from_fb2_link(e, binary_ids))
+                    .flat_map(|e| Span::from_fb2_link(e, ctx))
                     .map(code_text),
             ),
             fb2::StyleLinkElement::Image(i) => {
-                if let Some(i) = InlineImage::from_fb2(i, binary_ids) {
+                if let Some(i) = InlineImage::from_fb2(i, ctx.binaries) {
                     spans.push(Span {
                         span: Some(span::Span::Image(i)),
                     });
                 }
             }
             fb2::StyleLinkElement::Text(t) => {
-                if let Some(text) = Text::from_fb2(t) {
+                if let Some(text) = Text::from_fb2(t, ctx) {
                     spans.push(Span {
                         span: Some(span::Span::Text(text)),
                     });
@@ -811,60 +1297,6 @@ impl Span {
     }
 }
 
-fn bold_text(span: Span) -> Span {
-    hydrate_text(span, |text| text.font_weight = Some(BOLD_WEIGHT))
-}
-
-fn subscript_text(span: Span) -> Span {
-    hydrate_text(span, |text| {
-        text.set_baseline_shift(BaselineShift::Subscript);
-    })
-}
-
-fn superscript_text(span: Span) -> Span {
-    hydrate_text(span, |text| {
-        text.set_baseline_shift(BaselineShift::Superscript);
-    })
-}
-
-fn italic_text(span: Span) -> Span {
-    hydrate_text(span, |text| {
-        text.set_font_style(FontStyle::Italic);
-    })
-}
-
-fn code_text(span: Span) -> Span {
-    hydrate_text(span, |text| {
-        text.set_font_style(FontStyle::Code);
-    })
-}
-
-fn strikethrough_text(span: Span) -> Span {
-    hydrate_text(span, |text| {
-        text.push_decorations(TextDecoration::LineThrough);
-    })
-}
-
-fn hydrate_text(mut span: Span, mut modifier: impl FnMut(&mut Text)) -> Span {
-    match span.span.as_mut().unwrap() {
-        span::Span::Footnote(f) => {
-            for text in &mut f.content {
-                modifier(text);
-            }
-        }
-        span::Span::Link(l) => {
-            for text in &mut l.content {
-                modifier(text);
-            }
-        }
-        span::Span::Image(_) => {}
-        span::Span::Text(t) => {
-            modifier(t);
-        }
-    }
-    span
-}
-
 impl InlineImage {
     fn from_fb2(
         value: fb2::InlineImage,
@@ -881,36 +1313,11 @@ impl InlineImage {
     }
 }
 
-impl link::Href {
-    fn from_fb2(href: String) -> Option<link::Href> {
-        non_empty(href).and_then(|href| {
-            if let Some(href) = href.strip_prefix('#') {
-                Some(link::Href::Local(href.to_string()))
-            } else if let Ok(url) = href.parse() {
-                Some(link::Href::Remote(url))
-            } else {
-                None
-            }
-        })
-    }
-}
-
 impl Text {
-    fn from_fb2(value: String) -> Option<Text> {
-        non_empty(value).map(|value| Text {
-            value,
-            font_weight: None,
-            font_style: None,
-            baseline_shift: None,
-            decorations: vec![],
-        })
-    }
-}
-
-fn non_empty(value: String) -> Option<String> {
-    if value.is_empty() {
-        None
-    } else {
-        Some(value)
+    fn from_fb2(value: String, ctx: &Context) -> Option<Text> {
+        if !ctx.budget.text(&value) {
+            return None;
+        }
+        plain_text(value)
     }
 }
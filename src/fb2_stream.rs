@@ -0,0 +1,456 @@
+use base64::Engine;
+use language_tags::LanguageTag;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::{Reader, Writer};
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, BufRead, Seek, SeekFrom, Write};
+use uuid::Uuid;
+
+use crate::fb2::{body_kinds, BodyKind, Fb2Conversion};
+use crate::{Book, ConvertOptions, ConvertReport, FootnoteType, LimitError};
+
+/// Number of base64 characters decoded at once
+const BASE64_CHUNK: usize = 64 << 10;
+
+/// Destination of the binaries of an FB2 book, such as images, as they are read
+pub trait ResourceSink {
+    type Writer: Write;
+
+    /// Creates a resource for a binary of the book by its FB2 id and content type, returning the
+    /// id that the converted book refers to it by and a writer of its decoded content
+    fn create(&mut self, id: &str, content_type: &str) -> io::Result<(Uuid, Self::Writer)>;
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    Io(io::Error),
+    Xml(quick_xml::Error),
+    Deserialize(quick_xml::DeError),
+    /// A binary whose content is not base64, by its id
+    Binary(String),
+    /// A book without a description
+    Description,
+    Limit(LimitError),
+}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Io(e) => write!(f, "failed to read the book: {e}"),
+            ImportError::Xml(e) => write!(f, "failed to parse the book: {e}"),
+            ImportError::Deserialize(e) => write!(f, "failed to deserialize the book: {e}"),
+            ImportError::Binary(id) => write!(f, "binary {id} is not base64"),
+            ImportError::Description => write!(f, "the book has no description"),
+            ImportError::Limit(e) => write!(f, "the book exceeds the limits: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImportError::Io(e) => Some(e),
+            ImportError::Xml(e) => Some(e),
+            ImportError::Deserialize(e) => Some(e),
+            ImportError::Limit(e) => Some(e),
+            ImportError::Binary(_) | ImportError::Description => None,
+        }
+    }
+}
+
+impl Book {
+    /// Converts an FB2 book while reading it, without holding the whole document in memory.
+    ///
+    /// The book is read three times: binaries are decoded into the `sink` first, so that images
+    /// refer to them, then notes and comments are converted, so that links refer to them, and
+    /// then the rest of the book is converted section by section. The converted book is the same
+    /// as the one of [`Book::from_fb2_with_report`] with the ids returned by the `sink`.
+    pub fn from_fb2_reader<R: BufRead + Seek, S: ResourceSink>(
+        reader: R,
+        book_id: Uuid,
+        sink: &mut S,
+        options: &ConvertOptions,
+    ) -> Result<(Book, ConvertReport), ImportError> {
+        let mut reader = Fb2Reader::new(reader);
+        let mut binary_ids = HashMap::new();
+        let mut body_names = vec![];
+        reader.enter_root()?;
+        while let Some((element, empty)) = reader.next_child()? {
+            match element.local_name().as_ref() {
+                b"body" => body_names.push(attribute(&element, "name")?),
+                b"binary" => {
+                    let id = attribute(&element, "id")?.unwrap_or_default();
+                    let content_type = attribute(&element, "content-type")?.unwrap_or_default();
+                    let (uuid, mut writer) =
+                        sink.create(&id, &content_type).map_err(ImportError::Io)?;
+                    if !empty {
+                        reader.read_binary(&id, &mut writer, options)?;
+                    }
+                    writer.flush().map_err(ImportError::Io)?;
+                    binary_ids.insert(id, uuid);
+                }
+                _ => {}
+            }
+            if !empty {
+                reader.skip(&element)?;
+            }
+        }
+
+        let kinds = body_kinds(body_names.iter().map(Option::as_deref), options);
+        let mut conversion = Fb2Conversion::new(&binary_ids, options);
+        reader = reader.rewind()?;
+        let mut bodies = kinds.iter();
+        while let Some((element, empty)) = reader.next_child()? {
+            let footnote_type = match element.local_name().as_ref() {
+                b"body" => match bodies.next() {
+                    Some(BodyKind::Notes) => Some(FootnoteType::Note),
+                    Some(BodyKind::Comments) => Some(FootnoteType::Comment),
+                    _ => None,
+                },
+                _ => None,
+            };
+            let Some(footnote_type) = footnote_type else {
+                if !empty {
+                    reader.skip(&element)?;
+                }
+                continue;
+            };
+            reader.read_body(element, empty, |part| {
+                match part {
+                    BodyPart::Title(title) => conversion.footnotes_title(footnote_type, title),
+                    BodyPart::Section(section) => conversion.footnote(footnote_type, *section),
+                    BodyPart::Header(_) | BodyPart::Epigraph(_) => {}
+                }
+                conversion.check().map_err(ImportError::Limit)
+            })?;
+        }
+
+        reader = reader.rewind()?;
+        let mut bodies = kinds.iter();
+        let mut description = false;
+        while let Some((element, empty)) = reader.next_child()? {
+            let kind = match element.local_name().as_ref() {
+                b"description" => {
+                    let xml = reader.capture(element, empty)?;
+                    conversion.description(deserialize(&xml)?);
+                    description = true;
+                    continue;
+                }
+                b"body" => bodies.next().copied(),
+                _ => None,
+            };
+            match kind {
+                Some(BodyKind::Main) => reader.read_body(element, empty, |part| {
+                    match part {
+                        BodyPart::Header(body) => conversion.main_body(body.lang),
+                        BodyPart::Title(title) => conversion.main_title(title),
+                        BodyPart::Epigraph(epigraph) => conversion.main_epigraph(epigraph),
+                        BodyPart::Section(section) => conversion.main_section(*section),
+                    }
+                    conversion.check().map_err(ImportError::Limit)
+                })?,
+                Some(BodyKind::Other) => reader.read_body(element, empty, |part| {
+                    if let BodyPart::Section(section) = part {
+                        conversion.other_section(*section);
+                    }
+                    conversion.check().map_err(ImportError::Limit)
+                })?,
+                _ if !empty => reader.skip(&element)?,
+                _ => {}
+            }
+        }
+        if !description {
+            return Err(ImportError::Description);
+        }
+        conversion.finish(book_id).map_err(ImportError::Limit)
+    }
+}
+
+/// A part of a body, split the way the `fb2` crate splits bodies: the first title and image
+/// and the epigraphs before any section belong to the body, and anything else becomes a section
+enum BodyPart {
+    /// The attributes of the body
+    Header(BodyHeader),
+    Title(fb2::Title),
+    Epigraph(fb2::Epigraph),
+    Section(Box<fb2::Section>),
+}
+
+#[derive(serde::Deserialize)]
+struct BodyHeader {
+    #[serde(rename = "@lang")]
+    lang: Option<LanguageTag>,
+}
+
+/// What a body has had so far
+#[derive(Default)]
+struct BodyState {
+    image: bool,
+    title: bool,
+    sections: bool,
+}
+
+impl BodyState {
+    /// Splits a body of a single element into parts of the body being read
+    fn parts(&mut self, body: fb2::Body) -> Vec<BodyPart> {
+        let mut parts = vec![];
+        if let Some(image) = body.image {
+            if self.sections || self.image {
+                parts.push(section(fb2::SectionContent {
+                    image: Some(image),
+                    ..empty_section_content()
+                }));
+            }
+            self.image = true;
+        }
+        if let Some(title) = body.title {
+            if self.sections || self.title {
+                parts.push(section(fb2::SectionContent {
+                    title: Some(title),
+                    ..empty_section_content()
+                }));
+            } else {
+                parts.push(BodyPart::Title(title));
+            }
+            self.title = true;
+        }
+        for epigraph in body.epigraphs {
+            if self.sections {
+                parts.push(section(fb2::SectionContent {
+                    epigraphs: vec![epigraph],
+                    ..empty_section_content()
+                }));
+            } else {
+                parts.push(BodyPart::Epigraph(epigraph));
+            }
+        }
+        parts.extend(
+            body.sections
+                .into_iter()
+                .map(|s| BodyPart::Section(Box::new(s))),
+        );
+        self.sections |= parts.iter().any(|p| matches!(p, BodyPart::Section(_)));
+        parts
+    }
+}
+
+/// A section of its own, for what comes after the sections of a body
+fn section(content: fb2::SectionContent) -> BodyPart {
+    BodyPart::Section(Box::new(fb2::Section {
+        id: None,
+        lang: None,
+        content: Some(content),
+    }))
+}
+
+fn empty_section_content() -> fb2::SectionContent {
+    fb2::SectionContent {
+        title: None,
+        epigraphs: vec![],
+        image: None,
+        annotation: None,
+        content: vec![],
+        sections: vec![],
+    }
+}
+
+struct Fb2Reader<R> {
+    reader: Reader<R>,
+    buf: Vec<u8>,
+}
+
+impl<R: BufRead + Seek> Fb2Reader<R> {
+    fn new(reader: R) -> Fb2Reader<R> {
+        Fb2Reader {
+            reader: Reader::from_reader(reader),
+            buf: vec![],
+        }
+    }
+
+    /// Reads up to the root element, `<FictionBook>`
+    fn enter_root(&mut self) -> Result<(), ImportError> {
+        loop {
+            self.buf.clear();
+            match self.reader.read_event_into(&mut self.buf) {
+                Ok(Event::Start(_)) | Ok(Event::Eof) => return Ok(()),
+                Ok(_) => {}
+                Err(e) => return Err(ImportError::Xml(e)),
+            }
+        }
+    }
+
+    /// Starts reading the book again
+    fn rewind(self) -> Result<Fb2Reader<R>, ImportError> {
+        let mut inner = self.reader.into_inner();
+        inner.seek(SeekFrom::Start(0)).map_err(ImportError::Io)?;
+        let mut reader = Fb2Reader::new(inner);
+        reader.enter_root()?;
+        Ok(reader)
+    }
+
+    /// The next child element of the current element, and whether it's empty, or nothing at the
+    /// end of the current element. The children of a non-empty element have to be read or
+    /// skipped
+    fn next_child(&mut self) -> Result<Option<(BytesStart<'static>, bool)>, ImportError> {
+        loop {
+            self.buf.clear();
+            match self.reader.read_event_into(&mut self.buf) {
+                Ok(Event::Start(e)) => return Ok(Some((e.into_owned(), false))),
+                Ok(Event::Empty(e)) => return Ok(Some((e.into_owned(), true))),
+                Ok(Event::End(_)) | Ok(Event::Eof) => return Ok(None),
+                Ok(_) => {}
+                Err(e) => return Err(ImportError::Xml(e)),
+            }
+        }
+    }
+
+    /// Skips the rest of the element
+    fn skip(&mut self, element: &BytesStart) -> Result<(), ImportError> {
+        self.buf.clear();
+        self.reader
+            .read_to_end_into(element.to_end().name(), &mut self.buf)
+            .map_err(ImportError::Xml)?;
+        Ok(())
+    }
+
+    /// Reads the rest of the element as a standalone document
+    fn capture(&mut self, element: BytesStart, empty: bool) -> Result<Vec<u8>, ImportError> {
+        let mut writer = Writer::new(vec![]);
+        if empty {
+            write(&mut writer, Event::Empty(element))?;
+            return Ok(writer.into_inner());
+        }
+        write(&mut writer, Event::Start(element))?;
+        let mut depth = 1;
+        while depth > 0 {
+            self.buf.clear();
+            let event = self
+                .reader
+                .read_event_into(&mut self.buf)
+                .map_err(ImportError::Xml)?;
+            match &event {
+                Event::Start(_) => depth += 1,
+                Event::End(_) => depth -= 1,
+                Event::Eof => {
+                    return Err(ImportError::Io(io::ErrorKind::UnexpectedEof.into()));
+                }
+                _ => {}
+            }
+            write(&mut writer, event)?;
+        }
+        Ok(writer.into_inner())
+    }
+
+    /// Reads a body element by element, each deserialized as a body of its own
+    fn read_body(
+        &mut self,
+        element: BytesStart,
+        empty: bool,
+        mut part: impl FnMut(BodyPart) -> Result<(), ImportError>,
+    ) -> Result<(), ImportError> {
+        let mut header = Writer::new(vec![]);
+        write(&mut header, Event::Empty(element.borrow()))?;
+        part(BodyPart::Header(deserialize(header.get_ref())?))?;
+        if empty {
+            return Ok(());
+        }
+        let mut state = BodyState::default();
+        loop {
+            self.buf.clear();
+            let event = self
+                .reader
+                .read_event_into(&mut self.buf)
+                .map_err(ImportError::Xml)?
+                .into_owned();
+            let child = match event {
+                Event::Start(e) => self.capture(e, false)?,
+                Event::Empty(e) => self.capture(e, true)?,
+                Event::Text(t) if t.iter().any(|b| !b.is_ascii_whitespace()) => {
+                    let mut writer = Writer::new(vec![]);
+                    write(&mut writer, Event::Text(t))?;
+                    writer.into_inner()
+                }
+                Event::CData(c) => {
+                    let mut writer = Writer::new(vec![]);
+                    write(&mut writer, Event::CData(c))?;
+                    writer.into_inner()
+                }
+                Event::End(_) | Event::Eof => return Ok(()),
+                _ => continue,
+            };
+            let mut xml = b"<body>".to_vec();
+            xml.extend(child);
+            xml.extend(b"</body>");
+            for p in state.parts(deserialize(&xml)?) {
+                part(p)?;
+            }
+        }
+    }
+
+    /// Decodes the base64 content of a binary into the writer as it's read, right after its
+    /// start tag
+    fn read_binary(
+        &mut self,
+        id: &str,
+        writer: &mut impl Write,
+        options: &ConvertOptions,
+    ) -> Result<(), ImportError> {
+        let limit = options.limits.max_resource_size;
+        let invalid = |_| ImportError::Binary(id.to_string());
+        let mut size = 0;
+        let mut pending = vec![];
+        let mut decoded = vec![];
+        loop {
+            let inner = self.reader.get_mut();
+            let available = inner.fill_buf().map_err(ImportError::Io)?;
+            if available.is_empty() {
+                break;
+            }
+            let end = available.iter().position(|b| *b == b'<');
+            let text = &available[..end.unwrap_or(available.len())];
+            pending.extend(text.iter().filter(|b| !b.is_ascii_whitespace()));
+            let consumed = text.len();
+            inner.consume(consumed);
+
+            size += consumed;
+            if size > limit {
+                let error = LimitError::ResourceSize(id.to_string(), limit);
+                return Err(ImportError::Limit(error));
+            }
+            if pending.len() >= BASE64_CHUNK {
+                let chunk = pending.len() / 4 * 4;
+                decoded.clear();
+                base64::engine::general_purpose::STANDARD
+                    .decode_vec(&pending[..chunk], &mut decoded)
+                    .map_err(invalid)?;
+                writer.write_all(&decoded).map_err(ImportError::Io)?;
+                pending.drain(..chunk);
+            }
+            if end.is_some() {
+                break;
+            }
+        }
+        decoded.clear();
+        base64::engine::general_purpose::STANDARD
+            .decode_vec(&pending, &mut decoded)
+            .map_err(invalid)?;
+        writer.write_all(&decoded).map_err(ImportError::Io)
+    }
+}
+
+fn write(writer: &mut Writer<Vec<u8>>, event: Event) -> Result<(), ImportError> {
+    writer.write_event(event).map_err(ImportError::Xml)
+}
+
+fn deserialize<T: serde::de::DeserializeOwned>(xml: &[u8]) -> Result<T, ImportError> {
+    quick_xml::de::from_reader(xml).map_err(ImportError::Deserialize)
+}
+
+fn attribute(element: &BytesStart, name: &str) -> Result<Option<String>, ImportError> {
+    let Some(attribute) = element.try_get_attribute(name).map_err(ImportError::Xml)? else {
+        return Ok(None);
+    };
+    let value = attribute.unescape_value().map_err(ImportError::Xml)?;
+    Ok(Some(value.into_owned()))
+}
@@ -0,0 +1,20 @@
+use prost::Message;
+use sha2::{Digest, Sha256};
+
+use crate::{Book, Chapter};
+
+impl Book {
+    /// SHA-256 of the encoded book, the same for equal books, as fields are encoded in the order
+    /// of their tags and the book has no maps
+    pub fn fingerprint(&self) -> [u8; 32] {
+        Sha256::digest(self.encode_to_vec()).into()
+    }
+}
+
+impl Chapter {
+    /// SHA-256 of the encoded chapter along with its sub-chapters, so that unchanged chapters can
+    /// be told apart from changed ones after a book is imported again
+    pub fn fingerprint(&self) -> [u8; 32] {
+        Sha256::digest(self.encode_to_vec()).into()
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+
+use crate::visit::for_each_paragraph;
+use crate::{span, Book, Footnote, FootnoteType, Footnotes, Locator, Text};
+
+impl Footnotes {
+    /// The footnote with the id
+    pub fn get(&self, id: &str) -> Option<&Footnote> {
+        self.content.iter().find(|f| f.id == id)
+    }
+
+    /// Positions of the footnotes in [`Footnotes::content`] by their ids, to look up many
+    /// footnotes faster than with [`Footnotes::get`]
+    pub fn index(&self) -> HashMap<&str, usize> {
+        self.content
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (f.id.as_str(), i))
+            .collect()
+    }
+}
+
+/// Links to footnotes of a book, see [`Book::footnote_references`]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FootnoteReferences {
+    /// Locations of the links to each footnote, by its type and id, in the order of reading
+    pub links: HashMap<(FootnoteType, String), Vec<Locator>>,
+    /// Footnotes without any link to them, by their type and id
+    pub unreferenced: Vec<(FootnoteType, String)>,
+    /// Locations of the links to footnotes that don't exist
+    pub dangling: Vec<Locator>,
+}
+
+impl FootnoteReferences {
+    /// Locations of the links to the footnote, so that a reader can return to the text
+    pub fn links_to(&self, footnote_type: FootnoteType, id: &str) -> &[Locator] {
+        self.links
+            .get(&(footnote_type, id.to_string()))
+            .map_or(&[], |l| l.as_slice())
+    }
+}
+
+impl Book {
+    /// Finds every [`FootnoteLink`] of the book, including links in footnotes, and the footnotes
+    /// they point to in [`Book::notes`] or [`Book::comments`]. A link of an unknown type points to
+    /// a note, or to a comment if there's no such note.
+    ///
+    /// Each link is located by the offset of its first character in the paragraph.
+    ///
+    /// [`FootnoteLink`]: crate::FootnoteLink
+    pub fn footnote_references(&self) -> FootnoteReferences {
+        let notes = self
+            .notes
+            .as_ref()
+            .map(Footnotes::index)
+            .unwrap_or_default();
+        let comments = self
+            .comments
+            .as_ref()
+            .map(Footnotes::index)
+            .unwrap_or_default();
+        let mut references = FootnoteReferences::default();
+        for_each_paragraph(self, |locator, spans| {
+            let mut offset = 0;
+            for span in spans {
+                match &span.span {
+                    Some(span::Span::Footnote(link)) => {
+                        let footnote_type = match link.r#type() {
+                            FootnoteType::Unknown if notes.contains_key(link.id.as_str()) => {
+                                FootnoteType::Note
+                            }
+                            FootnoteType::Unknown => FootnoteType::Comment,
+                            footnote_type => footnote_type,
+                        };
+                        let exists = match footnote_type {
+                            FootnoteType::Note => notes.contains_key(link.id.as_str()),
+                            _ => comments.contains_key(link.id.as_str()),
+                        };
+                        let locator = Locator {
+                            offset,
+                            ..locator.clone()
+                        };
+                        if exists {
+                            references
+                                .links
+                                .entry((footnote_type, link.id.clone()))
+                                .or_default()
+                                .push(locator);
+                        } else {
+                            references.dangling.push(locator);
+                        }
+                        offset += text_length(&link.content);
+                    }
+                    Some(span::Span::Text(t)) => offset += t.value.chars().count() as u32,
+                    Some(span::Span::Link(l)) => offset += text_length(&l.content),
+                    Some(span::Span::Image(_)) => offset += 1,
+                    None => {}
+                }
+            }
+        });
+
+        for (footnote_type, footnotes) in [
+            (FootnoteType::Note, &self.notes),
+            (FootnoteType::Comment, &self.comments),
+        ] {
+            for footnote in footnotes.iter().flat_map(|f| &f.content) {
+                let key = (footnote_type, footnote.id.clone());
+                if !references.links.contains_key(&key) {
+                    references.unreferenced.push(key);
+                }
+            }
+        }
+        references
+    }
+}
+
+fn text_length(text: &[Text]) -> u32 {
+    text.iter().map(|t| t.value.chars().count() as u32).sum()
+}
@@ -0,0 +1,677 @@
+use language_tags::LanguageTag;
+use scraper::{ElementRef, Html, Node, Selector};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::convert::{
+    bold_text, code_text, concat_text, italic_text, non_empty, split_link_content,
+    strikethrough_text, subscript_text, superscript_text, text_span, Budget,
+};
+use crate::{
+    cite_element, content, span, title_element, Author, Book, Chapter, Cite, CiteElement, Content,
+    ConvertOptions, ConvertReport, Direction, EmptyLine, Image, InlineImage, LimitError, Link,
+    Paragraph, RejectedLink, Span, Table, TableCell, TableRow, Title, TitleElement,
+};
+
+impl Book {
+    /// Converts an HTML document, usually a saved web article, into a book.
+    ///
+    /// Headings `h1`–`h6` open chapters nested according to their level, the content preceding
+    /// the first heading becomes an untitled chapter. Images are included only if their `src`,
+    /// exactly as written in the document, is present in `image_ids`.
+    pub fn from_html(
+        html: &str,
+        book_id: Uuid,
+        image_ids: &HashMap<String, Uuid>,
+    ) -> Result<Book, LimitError> {
+        Book::from_html_with_options(html, book_id, image_ids, &ConvertOptions::default())
+    }
+
+    pub fn from_html_with_options(
+        html: &str,
+        book_id: Uuid,
+        image_ids: &HashMap<String, Uuid>,
+        options: &ConvertOptions,
+    ) -> Result<Book, LimitError> {
+        Book::from_html_with_report(html, book_id, image_ids, options).map(|(book, _)| book)
+    }
+
+    /// Like [`Book::from_html_with_options`], but also reports what was lost in the conversion,
+    /// such as links that became text. Fails if the document or the book exceeds
+    /// [`ConvertOptions::limits`]
+    pub fn from_html_with_report(
+        html: &str,
+        book_id: Uuid,
+        image_ids: &HashMap<String, Uuid>,
+        options: &ConvertOptions,
+    ) -> Result<(Book, ConvertReport), LimitError> {
+        let budget = Budget::new(&options.limits);
+        if !budget.resource("", html.len()) {
+            return Err(budget.finish().unwrap_err());
+        }
+        let document = Html::parse_document(html);
+        let root = document.root_element();
+        let ctx = Context {
+            images: image_ids,
+            options,
+            rejected_links: RefCell::new(vec![]),
+            budget,
+        };
+
+        let language = language(root);
+        let short_title = root
+            .select(&selector("head > title"))
+            .next()
+            .map(|t| collapse_whitespace(&t.text().collect::<String>()))
+            .unwrap_or_default();
+        let authors = root
+            .select(&selector("meta[name=author]"))
+            .filter_map(|m| m.attr("content"))
+            .filter_map(|a| non_empty(collapse_whitespace(a)))
+            .map(|full_name| Author {
+                id: Uuid::nil().to_string(),
+                full_name,
+                ..Default::default()
+            })
+            .collect();
+
+        let body = root.select(&selector("body")).next().unwrap_or(root);
+        let mut blocks = vec![];
+        Block::from_html(body, &ctx, &mut blocks);
+        let mut chapters = Chapters::default();
+        for block in blocks {
+            match block {
+                Block::Heading(level, heading) => chapters.open(level, heading, &ctx),
+                Block::Content(content) => chapters.push(content),
+            }
+        }
+        let chapters = chapters.finish();
+
+        let short_title = non_empty(short_title)
+            .or_else(|| chapters.first().and_then(title_text))
+            .unwrap_or_default();
+
+        let mut book = Book {
+            id: book_id.to_string(),
+            language,
+            short_title,
+            date: None,
+            authors,
+            cover: None,
+            annotation: None,
+            title: None,
+            epigraphs: vec![],
+            chapters,
+            notes: None,
+            comments: None,
+            statistics: None,
+            media_overlay: None,
+            direction: match direction(body) {
+                Direction::Unknown => direction(root),
+                direction => direction,
+            }
+            .into(),
+        };
+        options.finish(&mut book);
+        ctx.budget.finish()?;
+        let report = ConvertReport {
+            rejected_links: ctx.rejected_links.into_inner(),
+        };
+        Ok((book, report))
+    }
+}
+
+struct Context<'a> {
+    images: &'a HashMap<String, Uuid>,
+    options: &'a ConvertOptions,
+    rejected_links: RefCell<Vec<RejectedLink>>,
+    budget: Budget<'a>,
+}
+
+/// Chapters opened by headings, with the innermost one at the top of the stack
+#[derive(Default)]
+struct Chapters {
+    // heading level and the chapter it opened, 0 for the untitled chapter before any heading
+    open: Vec<(usize, Chapter)>,
+    closed: Vec<Chapter>,
+}
+
+impl Chapters {
+    fn open(&mut self, level: usize, heading: ElementRef, ctx: &Context) {
+        if !ctx.budget.chapter() {
+            return;
+        }
+        self.close(level);
+        let title = Paragraph::from_html(heading, ctx).map(|p| Title {
+            content: vec![TitleElement {
+                title_element: Some(title_element::TitleElement::Paragraph(p)),
+            }],
+        });
+        let chapter = Chapter {
+            anchor: heading.attr("id").unwrap_or_default().to_string(),
+            title,
+            ..Default::default()
+        };
+        self.open.push((level, chapter));
+    }
+
+    fn push(&mut self, content: Content) {
+        if self.open.is_empty() {
+            self.open.push((0, Chapter::default()));
+        }
+        self.open.last_mut().unwrap().1.content.push(content);
+    }
+
+    fn close(&mut self, level: usize) {
+        while let Some((open_level, _)) = self.open.last() {
+            if *open_level != 0 && *open_level < level {
+                break;
+            }
+            let (_, chapter) = self.open.pop().unwrap();
+            if chapter.title.is_none()
+                && chapter.content.is_empty()
+                && chapter.sub_chapters.is_empty()
+            {
+                continue;
+            }
+            match self.open.last_mut() {
+                Some((_, parent)) => parent.sub_chapters.push(chapter),
+                None => self.closed.push(chapter),
+            }
+        }
+    }
+
+    fn finish(mut self) -> Vec<Chapter> {
+        self.close(0);
+        self.closed
+    }
+}
+
+enum Block<'a> {
+    Heading(usize, ElementRef<'a>),
+    Content(Content),
+}
+
+impl<'a> Block<'a> {
+    /// Converts the children of the element. Elements that only wrap blocks, such as `<div>`,
+    /// are walked without nesting, so that only elements that become structures of the book,
+    /// such as cites, count towards the depth limit
+    fn from_html(element: ElementRef<'a>, ctx: &Context, blocks: &mut Vec<Block<'a>>) {
+        let Some(_depth) = ctx.budget.enter() else {
+            return;
+        };
+        let mut inline = vec![];
+        // children of the element and of the wrappers being walked
+        let mut levels = vec![element.children()];
+        while let Some(children) = levels.last_mut() {
+            let Some(child) = children.next() else {
+                levels.pop();
+                flush_paragraph(&mut inline, ctx, blocks);
+                continue;
+            };
+            let child = match child.value() {
+                Node::Text(t) => {
+                    if ctx.budget.text(t) {
+                        inline.extend(text_span(t.to_string()));
+                    }
+                    continue;
+                }
+                Node::Element(_) => ElementRef::wrap(child).unwrap(),
+                _ => continue,
+            };
+            let name = child.value().name();
+            let standalone_image = name == "img" && !has_text(&inline);
+            if !standalone_image && !is_block(name) {
+                inline.extend(Span::from_html(child, ctx));
+                continue;
+            }
+            flush_paragraph(&mut inline, ctx, blocks);
+            match name {
+                "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => blocks.push(Block::Heading(
+                    usize::from(name.as_bytes()[1] - b'0'),
+                    child,
+                )),
+                "p" => blocks.extend(
+                    Paragraph::from_html(child, ctx).map(|p| block(content::Content::Paragraph(p))),
+                ),
+                "pre" => blocks.extend(Paragraph::from_html(child, ctx).map(|mut p| {
+                    p.content = p.content.into_iter().map(code_text).collect();
+                    block(content::Content::Paragraph(p))
+                })),
+                "figcaption" => blocks.extend(
+                    Paragraph::from_html(child, ctx).map(|s| block(content::Content::Subtitle(s))),
+                ),
+                "blockquote" => blocks
+                    .extend(Cite::from_html(child, ctx).map(|c| block(content::Content::Cite(c)))),
+                "table" => blocks.extend(
+                    Table::from_html(child, ctx).map(|t| block(content::Content::Table(t))),
+                ),
+                "img" => blocks.extend(
+                    Image::from_html(child, None, ctx).map(|i| block(content::Content::Image(i))),
+                ),
+                "figure" => {
+                    let image = child
+                        .select(&selector("img"))
+                        .next()
+                        .and_then(|i| Image::from_html(i, Some(child), ctx));
+                    if let Some(image) = image {
+                        blocks.push(block(content::Content::Image(image)));
+                    } else {
+                        levels.push(child.children());
+                    }
+                }
+                "hr" => blocks.push(block(content::Content::EmptyLine(EmptyLine {}))),
+                _ if is_skipped(name) => {}
+                _ => levels.push(child.children()),
+            }
+        }
+    }
+}
+
+fn flush_paragraph(inline: &mut Vec<Span>, ctx: &Context, blocks: &mut Vec<Block>) {
+    let spans = std::mem::take(inline);
+    if !ctx.budget.spans(spans.len()) {
+        return;
+    }
+    if has_text(&spans)
+        || spans
+            .iter()
+            .any(|s| matches!(s.span, Some(span::Span::Image(_))))
+    {
+        blocks.push(block(content::Content::Paragraph(Paragraph {
+            anchor: String::new(),
+            language: String::new(),
+            direction: Direction::Unknown.into(),
+            classes: vec![],
+            content: spans,
+        })));
+    }
+}
+
+fn block<'a>(content: content::Content) -> Block<'a> {
+    Block::Content(Content {
+        content: Some(content),
+    })
+}
+
+impl Cite {
+    fn from_html(element: ElementRef, ctx: &Context) -> Option<Cite> {
+        let mut blocks = vec![];
+        Block::from_html(element, ctx, &mut blocks);
+        let mut elements = vec![];
+        for block in blocks {
+            let element = match block {
+                Block::Heading(_, heading) => {
+                    Paragraph::from_html(heading, ctx).map(cite_element::CiteElement::Subtitle)
+                }
+                Block::Content(c) => match c.content.unwrap() {
+                    content::Content::EmptyLine(e) => Some(cite_element::CiteElement::EmptyLine(e)),
+                    content::Content::Paragraph(p) => Some(cite_element::CiteElement::Paragraph(p)),
+                    content::Content::Poem(p) => Some(cite_element::CiteElement::Poem(p)),
+                    content::Content::Subtitle(s) => Some(cite_element::CiteElement::Subtitle(s)),
+                    content::Content::Table(t) => Some(cite_element::CiteElement::Table(t)),
+                    // a quotation can't be nested, but its content can
+                    content::Content::Cite(c) => {
+                        elements.extend(c.content);
+                        None
+                    }
+                    // a quotation can't contain images
+                    content::Content::Image(_) => None,
+                },
+            };
+            elements.extend(element.map(|e| CiteElement {
+                cite_element: Some(e),
+            }));
+        }
+        if elements.is_empty() {
+            return None;
+        }
+        Some(Cite {
+            anchor: element.attr("id").unwrap_or_default().to_string(),
+            authors: vec![],
+            content: elements,
+            language: language(element),
+        })
+    }
+}
+
+impl Paragraph {
+    fn from_html(element: ElementRef, ctx: &Context) -> Option<Paragraph> {
+        let content = Span::from_html_children(element, ctx);
+        if content.is_empty() || !ctx.budget.spans(content.len()) {
+            None
+        } else {
+            Some(Paragraph {
+                anchor: element.attr("id").unwrap_or_default().to_string(),
+                content,
+                language: language(element),
+                direction: direction(element).into(),
+                classes: vec![],
+            })
+        }
+    }
+}
+
+impl Table {
+    fn from_html(element: ElementRef, ctx: &Context) -> Option<Table> {
+        let rows = element
+            .child_elements()
+            .flat_map(|c| match c.value().name() {
+                "thead" | "tbody" | "tfoot" => c.child_elements().collect(),
+                _ => vec![c],
+            })
+            .filter(|r| r.value().name() == "tr")
+            .map(|r| {
+                r.child_elements()
+                    .filter(|c| matches!(c.value().name(), "th" | "td"))
+                    .collect::<Vec<_>>()
+            })
+            .filter(|cells| !cells.is_empty())
+            .collect::<Vec<_>>();
+        if rows.is_empty() {
+            return None;
+        }
+
+        let is_head = |row: usize, cell: usize| {
+            rows.get(row)
+                .and_then(|r| r.get(cell))
+                .map(|c| c.value().name() == "th")
+                .unwrap_or(false)
+        };
+        let header_column = is_head(0, 0) && is_head(1, 0);
+        let header_row = is_head(0, 0) && is_head(0, 1);
+
+        let rows = rows
+            .iter()
+            .map(|cells| TableRow {
+                cells: cells
+                    .iter()
+                    .map(|c| {
+                        let content = Span::from_html_children(*c, ctx);
+                        ctx.budget.spans(content.len());
+                        TableCell {
+                            anchor: c.attr("id").unwrap_or_default().to_string(),
+                            content,
+                        }
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Some(Table {
+            anchor: element.attr("id").unwrap_or_default().to_string(),
+            header_column,
+            header_row,
+            rows,
+        })
+    }
+}
+
+impl Image {
+    /// `figure` is the element wrapping the image, its caption becomes the title of the image
+    fn from_html(element: ElementRef, figure: Option<ElementRef>, ctx: &Context) -> Option<Image> {
+        let id = element.attr("src").and_then(|src| ctx.images.get(src))?;
+        let caption = figure
+            .and_then(|f| f.select(&selector("figcaption")).next())
+            .map(|c| collapse_whitespace(&c.text().collect::<String>()))
+            .and_then(non_empty);
+        let title = caption.or_else(|| element.attr("title").map(collapse_whitespace));
+        let anchor = figure
+            .and_then(|f| f.attr("id"))
+            .or_else(|| element.attr("id"))
+            .unwrap_or_default();
+        Some(Image {
+            id: id.to_string(),
+            anchor: anchor.to_string(),
+            alt: element.attr("alt").unwrap_or_default().to_string(),
+            title: title.unwrap_or_default(),
+        })
+    }
+}
+
+impl InlineImage {
+    fn from_html(element: ElementRef, ctx: &Context) -> Option<InlineImage> {
+        let id = element.attr("src").and_then(|src| ctx.images.get(src))?;
+        Some(InlineImage {
+            id: id.to_string(),
+            alt: element.attr("alt").unwrap_or_default().to_string(),
+        })
+    }
+}
+
+impl Span {
+    fn from_html(element: ElementRef, ctx: &Context) -> Vec<Span> {
+        let mut spans = vec![];
+        match element.value().name() {
+            "strong" | "b" => spans.extend(
+                Span::from_html_nested(element, ctx)
+                    .into_iter()
+                    .map(bold_text),
+            ),
+            "em" | "i" => spans.extend(
+                Span::from_html_nested(element, ctx)
+                    .into_iter()
+                    .map(italic_text),
+            ),
+            "s" | "strike" | "del" => spans.extend(
+                Span::from_html_nested(element, ctx)
+                    .into_iter()
+                    .map(strikethrough_text),
+            ),
+            "sub" => spans.extend(
+                Span::from_html_nested(element, ctx)
+                    .into_iter()
+                    .map(subscript_text),
+            ),
+            "sup" => spans.extend(
+                Span::from_html_nested(element, ctx)
+                    .into_iter()
+                    .map(superscript_text),
+            ),
+            "code" | "kbd" | "samp" | "tt" => spans.extend(
+                Span::from_html_nested(element, ctx)
+                    .into_iter()
+                    .map(code_text),
+            ),
+            "a" => {
+                let content = Span::from_html_nested(element, ctx);
+                let href = match element.attr("href").map(|href| ctx.options.href(href)) {
+                    Some(Ok(href)) => href,
+                    Some(Err(reason)) => {
+                        ctx.rejected_links.borrow_mut().push(RejectedLink {
+                            href: element.attr("href").unwrap_or_default().to_string(),
+                            text: concat_text(split_link_content(content.clone()).0),
+                            reason,
+                        });
+                        None
+                    }
+                    None => None,
+                };
+                if let Some(href) = href {
+                    let (text, images) = split_link_content(content);
+                    if !text.is_empty() {
+                        spans.push(Span {
+                            span: Some(span::Span::Link(Link {
+                                href: Some(href),
+                                content: text,
+                            })),
+                        });
+                    }
+                    spans.extend(images);
+                } else {
+                    spans.extend(content);
+                }
+            }
+            "img" => {
+                if let Some(i) = InlineImage::from_html(element, ctx) {
+                    spans.push(Span {
+                        span: Some(span::Span::Image(i)),
+                    });
+                }
+            }
+            "br" => spans.extend(text_span("\n".to_string())),
+            name if is_skipped(name) => {}
+            _ => spans.extend(Span::from_html_children(element, ctx)),
+        }
+        spans
+    }
+
+    /// Converts the children of an element that adds a level of nesting, such as a style or a
+    /// link
+    fn from_html_nested(element: ElementRef, ctx: &Context) -> Vec<Span> {
+        let Some(_depth) = ctx.budget.enter() else {
+            return vec![];
+        };
+        Span::from_html_children(element, ctx)
+    }
+
+    /// Converts the children of the element, walking elements that only wrap text, such as
+    /// `<span>`, without nesting
+    fn from_html_children(element: ElementRef, ctx: &Context) -> Vec<Span> {
+        let mut spans = vec![];
+        // children of the element and of the wrappers being walked
+        let mut levels = vec![element.children()];
+        while let Some(children) = levels.last_mut() {
+            let Some(child) = children.next() else {
+                levels.pop();
+                continue;
+            };
+            match child.value() {
+                Node::Text(t) if ctx.budget.text(t) => spans.extend(text_span(t.to_string())),
+                Node::Element(e) if is_inline_wrapper(e.name()) => levels.push(child.children()),
+                Node::Element(_) => {
+                    spans.extend(Span::from_html(ElementRef::wrap(child).unwrap(), ctx))
+                }
+                _ => {}
+            }
+        }
+        spans
+    }
+}
+
+/// Whether an inline element has no style or link of its own and only wraps its children
+fn is_inline_wrapper(name: &str) -> bool {
+    !is_skipped(name)
+        && !matches!(
+            name,
+            "strong"
+                | "b"
+                | "em"
+                | "i"
+                | "s"
+                | "strike"
+                | "del"
+                | "sub"
+                | "sup"
+                | "code"
+                | "kbd"
+                | "samp"
+                | "tt"
+                | "a"
+                | "img"
+                | "br"
+        )
+}
+
+fn is_block(name: &str) -> bool {
+    matches!(
+        name,
+        "address"
+            | "article"
+            | "aside"
+            | "blockquote"
+            | "body"
+            | "center"
+            | "dd"
+            | "details"
+            | "div"
+            | "dl"
+            | "dt"
+            | "fieldset"
+            | "figcaption"
+            | "figure"
+            | "footer"
+            | "form"
+            | "h1"
+            | "h2"
+            | "h3"
+            | "h4"
+            | "h5"
+            | "h6"
+            | "header"
+            | "hr"
+            | "li"
+            | "main"
+            | "nav"
+            | "ol"
+            | "p"
+            | "pre"
+            | "section"
+            | "summary"
+            | "table"
+            | "ul"
+    ) || is_skipped(name)
+}
+
+/// Elements that never contain readable text
+fn is_skipped(name: &str) -> bool {
+    matches!(
+        name,
+        "script" | "style" | "noscript" | "template" | "head" | "iframe" | "svg" | "canvas"
+    )
+}
+
+fn has_text(spans: &[Span]) -> bool {
+    spans.iter().any(|s| match &s.span {
+        Some(span::Span::Text(t)) => !t.value.trim().is_empty(),
+        Some(span::Span::Link(_)) | Some(span::Span::Footnote(_)) => true,
+        _ => false,
+    })
+}
+
+fn title_text(chapter: &Chapter) -> Option<String> {
+    let title = chapter.title.as_ref()?;
+    let text = title
+        .content
+        .iter()
+        .filter_map(|e| match &e.title_element {
+            Some(title_element::TitleElement::Paragraph(p)) => Some(p),
+            _ => None,
+        })
+        .flat_map(|p| &p.content)
+        .flat_map(|s| match &s.span {
+            Some(span::Span::Text(t)) => vec![t],
+            Some(span::Span::Link(l)) => l.content.iter().collect(),
+            _ => vec![],
+        })
+        .map(|t| t.value.as_str())
+        .collect::<String>();
+    non_empty(collapse_whitespace(&text))
+}
+
+/// The `lang` attribute of the element, if it's a valid language tag
+fn language(element: ElementRef) -> String {
+    element
+        .attr("lang")
+        .map(|lang| lang.trim().to_string())
+        .filter(|lang| lang.parse::<LanguageTag>().is_ok())
+        .unwrap_or_default()
+}
+
+/// The `dir` attribute of the element, unknown if it's missing or `auto`
+fn direction(element: ElementRef) -> Direction {
+    match element.attr("dir").map(str::trim) {
+        Some(dir) if dir.eq_ignore_ascii_case("ltr") => Direction::Ltr,
+        Some(dir) if dir.eq_ignore_ascii_case("rtl") => Direction::Rtl,
+        _ => Direction::Unknown,
+    }
+}
+
+fn collapse_whitespace(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn selector(selector: &str) -> Selector {
+    Selector::parse(selector).unwrap()
+}
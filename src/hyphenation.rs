@@ -0,0 +1,164 @@
+use hypher::Lang;
+use std::collections::HashMap;
+
+use crate::visit::{
+    visit_chapter_mut, visit_cite_mut, visit_epigraph_mut, visit_poem_mut, VisitMut,
+};
+use crate::{link, span, Book, Chapter, Cite, Epigraph, FontStyle, Paragraph, Poem, Span, Text};
+
+const SOFT_HYPHEN: char = '\u{00AD}';
+
+/// Settings of [`Book::hyphenate`]
+#[derive(Clone, Debug)]
+pub struct HyphenationOptions {
+    /// Words with fewer letters are left as is. 5 by default
+    pub min_word_length: usize,
+    /// Languages to hyphenate the chapters with these anchors, and their sub-chapters, in instead
+    /// of the ones from [`Book::language_at`]. Empty by default
+    pub chapter_languages: HashMap<String, String>,
+}
+
+impl Default for HyphenationOptions {
+    fn default() -> Self {
+        HyphenationOptions {
+            min_word_length: 5,
+            chapter_languages: HashMap::new(),
+        }
+    }
+}
+
+impl Book {
+    /// Inserts soft hyphens (U+00AD) into words at the points where they can be broken between
+    /// lines, so that readers don't have to hyphenate justified text while rendering a page.
+    ///
+    /// Words are hyphenated with TeX patterns of the language of the text, as in
+    /// [`Book::language_at`]: of the text itself or of the innermost element around it, from the
+    /// paragraph to the book. Text in an unsupported language is left as is. Text styled as
+    /// [`FontStyle::Code`] and text of links to remote resources, usually their addresses, are
+    /// never hyphenated.
+    pub fn hyphenate(&mut self, options: &HyphenationOptions) {
+        let language = lang(&self.language);
+        Hyphenation {
+            options,
+            languages: vec![language],
+        }
+        .visit_book_mut(self);
+    }
+}
+
+struct Hyphenation<'a> {
+    options: &'a HyphenationOptions,
+    // languages of the elements being visited, starting with the language of the book
+    languages: Vec<Option<Lang>>,
+}
+
+impl VisitMut for Hyphenation<'_> {
+    fn visit_chapter_mut(&mut self, chapter: &mut Chapter) {
+        let language = match self.options.chapter_languages.get(&chapter.anchor) {
+            Some(language) => self.language(language),
+            None => self.language(&chapter.language),
+        };
+        self.within(language, |h| visit_chapter_mut(h, chapter));
+    }
+
+    fn visit_epigraph_mut(&mut self, epigraph: &mut Epigraph) {
+        let language = self.language(&epigraph.language);
+        self.within(language, |h| visit_epigraph_mut(h, epigraph));
+    }
+
+    fn visit_poem_mut(&mut self, poem: &mut Poem) {
+        let language = self.language(&poem.language);
+        self.within(language, |h| visit_poem_mut(h, poem));
+    }
+
+    fn visit_cite_mut(&mut self, cite: &mut Cite) {
+        let language = self.language(&cite.language);
+        self.within(language, |h| visit_cite_mut(h, cite));
+    }
+
+    fn visit_paragraph_mut(&mut self, paragraph: &mut Paragraph) {
+        let language = self.language(&paragraph.language);
+        self.within(language, |h| h.visit_spans_mut(&mut paragraph.content));
+    }
+
+    fn visit_spans_mut(&mut self, spans: &mut Vec<Span>) {
+        let language = *self.languages.last().unwrap();
+        for span in spans {
+            match &mut span.span {
+                Some(span::Span::Text(t)) => self.hyphenate(t, language),
+                Some(span::Span::Link(l)) => {
+                    if !matches!(l.href, Some(link::Href::Remote(_))) {
+                        for text in &mut l.content {
+                            self.hyphenate(text, language);
+                        }
+                    }
+                }
+                Some(span::Span::Footnote(f)) => {
+                    for text in &mut f.content {
+                        self.hyphenate(text, language);
+                    }
+                }
+                Some(span::Span::Image(_)) | None => {}
+            }
+        }
+    }
+}
+
+impl Hyphenation<'_> {
+    /// The language of an element, or of the element around it if it has none
+    fn language(&self, language: &str) -> Option<Lang> {
+        if language.is_empty() {
+            *self.languages.last().unwrap()
+        } else {
+            lang(language)
+        }
+    }
+
+    fn within(&mut self, language: Option<Lang>, visit: impl FnOnce(&mut Self)) {
+        self.languages.push(language);
+        visit(self);
+        self.languages.pop();
+    }
+
+    /// Hyphenates the text in its own language, or in the language around it
+    fn hyphenate(&self, text: &mut Text, language: Option<Lang>) {
+        let language = if text.language.is_empty() {
+            language
+        } else {
+            lang(&text.language)
+        };
+        let Some(language) = language else {
+            return;
+        };
+        if text.font_style() == FontStyle::Code {
+            return;
+        }
+        let mut value = String::with_capacity(text.value.len());
+        let mut rest = text.value.as_str();
+        while let Some(start) = rest.find(is_word_char) {
+            value.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let end = rest.find(|c| !is_word_char(c)).unwrap_or(rest.len());
+            let word = &rest[..end];
+            // a word with soft hyphens is already hyphenated
+            if word.chars().count() < self.options.min_word_length || word.contains(SOFT_HYPHEN) {
+                value.push_str(word);
+            } else {
+                value.push_str(&hypher::hyphenate(word, language).join("\u{00AD}"));
+            }
+            rest = &rest[end..];
+        }
+        value.push_str(rest);
+        text.value = value;
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphabetic() || c == SOFT_HYPHEN
+}
+
+fn lang(language: &str) -> Option<Lang> {
+    let primary = language.split('-').next().unwrap_or_default();
+    let code: [u8; 2] = primary.to_ascii_lowercase().as_bytes().try_into().ok()?;
+    Lang::from_iso(code)
+}
@@ -0,0 +1,174 @@
+use crate::visit::{
+    visit_book, visit_chapter, visit_cite, visit_epigraph, visit_footnote, visit_footnotes,
+    visit_poem, Visit,
+};
+use crate::{
+    span, Book, Chapter, Cite, Epigraph, Footnote, FootnoteType, Footnotes, Locator, Paragraph,
+    Poem, Span,
+};
+
+impl Book {
+    /// The language of the text at the locator: the language of the innermost element around it,
+    /// from the text at the offset to the book, that has one.
+    ///
+    /// Falls back to the language of the book if the locator points outside of the book, and is
+    /// empty if the book has no language either
+    pub fn language_at(&self, locator: &Locator) -> &str {
+        let Some(located) = locate(self, locator) else {
+            return &self.language;
+        };
+        [self.language.as_str()]
+            .into_iter()
+            .chain(located.chapters.iter().map(|c| c.language.as_str()))
+            .chain(located.languages)
+            .rev()
+            .find(|l| !l.is_empty())
+            .unwrap_or_default()
+    }
+}
+
+/// Elements of a book around the text at a locator
+pub(crate) struct Located<'a> {
+    /// Chapters from the top-level one to the located one, empty outside of chapters
+    pub(crate) chapters: Vec<&'a Chapter>,
+    /// Languages of the elements of the chapter or the footnote around the text, from the
+    /// outermost one to the text itself. Empty if there's no such paragraph
+    pub(crate) languages: Vec<&'a str>,
+    /// The paragraph with the text, unless it's in a table cell
+    pub(crate) paragraph: Option<&'a Paragraph>,
+}
+
+/// Finds the elements around the text at the locator, if its chapter or footnote exists
+pub(crate) fn locate<'a>(book: &'a Book, locator: &Locator) -> Option<Located<'a>> {
+    let mut resolver = Resolver {
+        remaining: locator.paragraph,
+        offset: locator.offset as usize,
+        languages: vec![],
+        found: None,
+        paragraph: None,
+    };
+    let mut chapters = vec![];
+    match locator.footnote_type() {
+        FootnoteType::Unknown if locator.chapters.is_empty() => visit_book(&mut resolver, book),
+        FootnoteType::Unknown => {
+            let mut siblings = &book.chapters;
+            for index in &locator.chapters {
+                let chapter = siblings.get(*index as usize)?;
+                chapters.push(chapter);
+                siblings = &chapter.sub_chapters;
+            }
+            visit_chapter(&mut resolver, chapters.last()?);
+        }
+        footnote_type => {
+            let footnotes = match footnote_type {
+                FootnoteType::Note => book.notes.as_ref()?,
+                _ => book.comments.as_ref()?,
+            };
+            if locator.footnote.is_empty() {
+                visit_footnotes(&mut resolver, footnote_type, footnotes);
+            } else {
+                let footnote = footnotes
+                    .content
+                    .iter()
+                    .find(|f| f.id == locator.footnote)?;
+                visit_footnote(&mut resolver, footnote);
+            }
+        }
+    }
+    Some(Located {
+        chapters,
+        languages: resolver.found.unwrap_or_default(),
+        paragraph: resolver.paragraph,
+    })
+}
+
+/// Finds the elements around the text at the offset of the paragraph with the index `remaining`
+/// of a chapter or a footnote, without its sub-chapters
+struct Resolver<'a> {
+    remaining: u32,
+    offset: usize,
+    // languages of the elements being visited, from the outermost one
+    languages: Vec<&'a str>,
+    // languages around the located text, from the outermost element to the text
+    found: Option<Vec<&'a str>>,
+    paragraph: Option<&'a Paragraph>,
+}
+
+impl<'a> Resolver<'a> {
+    fn within(&mut self, language: &'a str, visit: impl FnOnce(&mut Self)) {
+        self.languages.push(language);
+        visit(self);
+        self.languages.pop();
+    }
+}
+
+impl<'a> Visit<'a> for Resolver<'a> {
+    fn visit_chapter(&mut self, _chapter: &'a Chapter) {}
+
+    fn visit_footnotes(&mut self, _type: FootnoteType, _footnotes: &'a Footnotes) {}
+
+    fn visit_footnote(&mut self, _type: FootnoteType, _footnote: &'a Footnote) {}
+
+    fn visit_epigraph(&mut self, epigraph: &'a Epigraph) {
+        self.within(&epigraph.language, |r| visit_epigraph(r, epigraph));
+    }
+
+    fn visit_poem(&mut self, poem: &'a Poem) {
+        self.within(&poem.language, |r| visit_poem(r, poem));
+    }
+
+    fn visit_cite(&mut self, cite: &'a Cite) {
+        self.within(&cite.language, |r| visit_cite(r, cite));
+    }
+
+    fn visit_paragraph(&mut self, paragraph: &'a Paragraph) {
+        let searching = self.found.is_none();
+        self.within(&paragraph.language, |r| r.visit_spans(&paragraph.content));
+        if searching && self.found.is_some() {
+            self.paragraph = Some(paragraph);
+        }
+    }
+
+    fn visit_spans(&mut self, spans: &'a [Span]) {
+        if self.found.is_some() {
+            return;
+        }
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            return;
+        }
+        let mut found = self.languages.clone();
+        found.extend(text_language(spans, self.offset));
+        self.found = Some(found);
+    }
+}
+
+/// The language of the text with the character at the offset, or of the last text if the offset
+/// is at the end of the spans. Images have no language of their own
+fn text_language(spans: &[Span], offset: usize) -> Option<&str> {
+    let mut position = 0;
+    let mut last = None;
+    for span in spans {
+        let texts = match &span.span {
+            Some(span::Span::Text(t)) => std::slice::from_ref(t),
+            Some(span::Span::Link(l)) => l.content.as_slice(),
+            Some(span::Span::Footnote(f)) => f.content.as_slice(),
+            Some(span::Span::Image(_)) => {
+                position += 1;
+                if offset < position {
+                    return None;
+                }
+                continue;
+            }
+            None => continue,
+        };
+        for text in texts {
+            position += text.value.chars().count();
+            if offset < position {
+                return Some(&text.language);
+            }
+            last = Some(text.language.as_str());
+        }
+    }
+    last
+}
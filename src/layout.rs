@@ -0,0 +1,493 @@
+use unicode_linebreak::{linebreaks, BreakOpportunity};
+
+use crate::direction::is_bidi_control;
+use crate::visit::{paragraph_text, visit_chapter, visit_stanza, Position, Visit};
+use crate::{
+    span, Book, Chapter, Footnote, FootnoteType, Footnotes, Image, Locator, Span, Stanza, Table,
+    Text,
+};
+
+/// Size of the screen in pixels or in any other unit used consistently by the layout
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub width: f32,
+    pub height: f32,
+}
+
+/// Space between the edges of the screen and the text
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Margins {
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32,
+}
+
+/// Sizes of characters of a font
+pub trait FontMetrics {
+    /// Horizontal advance of the character when it's styled as `text`
+    fn advance(&self, c: char, text: &Text) -> f32;
+
+    /// Distance between the baselines of two lines of text
+    fn line_height(&self) -> f32;
+}
+
+/// Metrics of a monospaced font, where every visible character has the same advance
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FixedAdvance {
+    pub advance: f32,
+    pub line_height: f32,
+}
+
+impl FontMetrics for FixedAdvance {
+    fn advance(&self, c: char, _text: &Text) -> f32 {
+        if is_invisible(c) {
+            0.0
+        } else {
+            self.advance
+        }
+    }
+
+    fn line_height(&self) -> f32 {
+        self.line_height
+    }
+}
+
+/// Breaks chapters into pages of a fixed size, so that every reader gets the same pages
+#[derive(Clone, Debug)]
+pub struct Layout<M> {
+    pub viewport: Viewport,
+    pub margins: Margins,
+    pub metrics: M,
+    /// Vertical space between paragraphs and other blocks
+    pub block_spacing: f32,
+    /// Height of every image, as the layout doesn't know the real size of images
+    pub image_height: f32,
+}
+
+/// A page of a chapter
+#[derive(Clone, Debug, PartialEq)]
+pub struct Page {
+    /// Start of the first line of the page
+    pub start: Locator,
+    /// End of the last line of the page
+    pub end: Locator,
+}
+
+impl<M: FontMetrics> Layout<M> {
+    /// Creates a layout without spacing between blocks, where images take half of the page
+    pub fn new(viewport: Viewport, margins: Margins, metrics: M) -> Layout<M> {
+        let image_height = (viewport.height - margins.top - margins.bottom) / 2.0;
+        Layout {
+            viewport,
+            margins,
+            metrics,
+            block_spacing: 0.0,
+            image_height,
+        }
+    }
+
+    /// Breaks the chapter, along with its sub-chapters, into pages.
+    ///
+    /// `path` locates the chapter in the book as in [`Locator::chapters`]. Lines are broken
+    /// according to the Unicode line breaking algorithm (UAX #14). Lines of a stanza and lines of
+    /// a table row are kept on the same page, unless they don't fit even on an empty page.
+    pub fn paginate(&self, chapter: &Chapter, path: &[u32]) -> Vec<Page> {
+        let mut paginator = Paginator::new(self, Position::before_chapter(path));
+        paginator.visit_chapter(chapter);
+        self.pages(paginator.lines)
+    }
+
+    /// Estimates the number of pages of the book: its title, annotation and epigraphs followed by
+    /// chapters, each starting on a new page. Footnotes aren't counted
+    pub fn estimate_pages(&self, book: &Book) -> usize {
+        let mut front_matter = Paginator::new(self, Position::new());
+        front_matter.front_matter_only = true;
+        front_matter.visit_book(book);
+        let front_matter = self.pages(front_matter.lines).len();
+        let chapters = book
+            .chapters
+            .iter()
+            .enumerate()
+            .map(|(i, chapter)| self.paginate(chapter, &[i as u32]).len())
+            .sum::<usize>();
+        front_matter + chapters
+    }
+
+    fn content_width(&self) -> f32 {
+        self.viewport.width - self.margins.left - self.margins.right
+    }
+
+    fn content_height(&self) -> f32 {
+        self.viewport.height - self.margins.top - self.margins.bottom
+    }
+
+    fn pages(&self, lines: Vec<Line>) -> Vec<Page> {
+        let height = self.content_height();
+        let mut pages = vec![];
+        let mut page: Option<Page> = None;
+        let mut used = 0.0;
+        for (i, line) in lines.iter().enumerate() {
+            let spacing = if line.first_in_block && used > 0.0 {
+                self.block_spacing
+            } else {
+                0.0
+            };
+            let group_starts = line.group.is_some() && (i == 0 || lines[i - 1].group != line.group);
+            let needed = if group_starts {
+                let group = lines[i..]
+                    .iter()
+                    .take_while(|l| l.group == line.group)
+                    .collect::<Vec<_>>();
+                let group_height = group.iter().map(|l| l.height).sum::<f32>()
+                    + group.iter().skip(1).filter(|l| l.first_in_block).count() as f32
+                        * self.block_spacing;
+                // a group taller than a page is broken as usual
+                if group_height <= height {
+                    group_height
+                } else {
+                    line.height
+                }
+            } else {
+                line.height
+            };
+            if used > 0.0 && used + spacing + needed > height {
+                pages.extend(page.take());
+                used = 0.0;
+            }
+            let spacing = if used > 0.0 { spacing } else { 0.0 };
+            used += spacing + line.height;
+            match &mut page {
+                Some(page) => page.end = line.end.clone(),
+                None => {
+                    page = Some(Page {
+                        start: line.start.clone(),
+                        end: line.end.clone(),
+                    })
+                }
+            }
+        }
+        pages.extend(page);
+        pages
+    }
+
+    /// Breaks the content of a paragraph into lines fitting `width`, returns the ranges of
+    /// characters of each line. A line has at least one character, even if nothing fits, such as
+    /// when the margins are wider than the viewport
+    fn break_lines(&self, spans: &[Span], width: f32) -> Vec<(usize, usize)> {
+        let text = paragraph_text(spans);
+        let widths = self.char_widths(spans);
+        // prefix[i] is the width of the first i characters
+        let mut prefix = Vec::with_capacity(widths.len() + 1);
+        prefix.push(0.0);
+        for w in &widths {
+            prefix.push(prefix.last().unwrap() + w);
+        }
+        let chars = text.chars().collect::<Vec<_>>();
+        let line_width = |start: usize, end: usize| {
+            let mut end = end;
+            while end > start && chars[end - 1].is_whitespace() {
+                end -= 1;
+            }
+            prefix[end] - prefix[start]
+        };
+        let mut byte_to_char = vec![0; text.len() + 1];
+        for (i, (b, _)) in text.char_indices().enumerate() {
+            byte_to_char[b] = i;
+        }
+        byte_to_char[text.len()] = chars.len();
+
+        let mut lines = vec![];
+        let mut start = 0;
+        let mut last_break = None;
+        for (b, opportunity) in linebreaks(&text) {
+            let position = byte_to_char[b];
+            if line_width(start, position) > width {
+                if let Some(last_break) = last_break.filter(|b| *b > start) {
+                    lines.push((start, last_break));
+                    start = last_break;
+                }
+                // a word wider than a line is broken anywhere
+                while start < position && line_width(start, position) > width {
+                    let mut end = start + 1;
+                    while end < position && line_width(start, end + 1) <= width {
+                        end += 1;
+                    }
+                    lines.push((start, end));
+                    start = end;
+                }
+            }
+            last_break = Some(position);
+            if opportunity == BreakOpportunity::Mandatory && position > start {
+                lines.push((start, position));
+                start = position;
+                last_break = None;
+            }
+        }
+        if start < chars.len() || lines.is_empty() {
+            lines.push((start, chars.len()));
+        }
+        lines
+    }
+
+    fn char_widths(&self, spans: &[Span]) -> Vec<f32> {
+        let mut widths = vec![];
+        for span in spans {
+            let texts = match &span.span {
+                Some(span::Span::Text(t)) => std::slice::from_ref(t),
+                Some(span::Span::Link(l)) => l.content.as_slice(),
+                Some(span::Span::Footnote(f)) => f.content.as_slice(),
+                // an inline image is about the size of a character
+                Some(span::Span::Image(_)) => {
+                    widths.push(self.metrics.line_height());
+                    continue;
+                }
+                None => continue,
+            };
+            for text in texts {
+                widths.extend(text.value.chars().map(|c| self.metrics.advance(c, text)));
+            }
+        }
+        widths
+    }
+}
+
+struct Line {
+    start: Locator,
+    end: Locator,
+    height: f32,
+    /// Lines of a group are kept on the same page
+    group: Option<usize>,
+    /// Whether it's the first line of a paragraph or another block
+    first_in_block: bool,
+}
+
+struct Paginator<'l, M> {
+    layout: &'l Layout<M>,
+    position: Position,
+    lines: Vec<Line>,
+    group: Option<usize>,
+    groups: usize,
+    /// Whether to skip chapters and footnotes of a book
+    front_matter_only: bool,
+}
+
+impl<'l, M: FontMetrics> Paginator<'l, M> {
+    fn new(layout: &'l Layout<M>, position: Position) -> Paginator<'l, M> {
+        Paginator {
+            layout,
+            position,
+            lines: vec![],
+            group: None,
+            groups: 0,
+            front_matter_only: false,
+        }
+    }
+
+    fn start_group(&mut self) -> usize {
+        self.groups += 1;
+        self.groups
+    }
+
+    fn push_block(&mut self, height: f32) {
+        let locator = self.position.peek_paragraph();
+        self.lines.push(Line {
+            start: locator.clone(),
+            end: locator,
+            height,
+            group: self.group,
+            first_in_block: true,
+        });
+    }
+}
+
+impl<'a, M: FontMetrics> Visit<'a> for Paginator<'_, M> {
+    fn visit_chapter(&mut self, chapter: &'a Chapter) {
+        if self.front_matter_only {
+            return;
+        }
+        self.position.enter_chapter();
+        visit_chapter(self, chapter);
+        self.position.leave_chapter();
+    }
+
+    fn visit_footnotes(&mut self, _type: FootnoteType, _footnotes: &'a Footnotes) {}
+
+    fn visit_footnote(&mut self, _type: FootnoteType, _footnote: &'a Footnote) {}
+
+    fn visit_stanza(&mut self, stanza: &'a Stanza) {
+        self.group = Some(self.start_group());
+        visit_stanza(self, stanza);
+        self.group = None;
+    }
+
+    fn visit_table(&mut self, table: &'a Table) {
+        let width = self.layout.content_width();
+        for (r, row) in table.rows.iter().enumerate() {
+            let group = Some(self.start_group());
+            let cell_width = width / row.cells.len().max(1) as f32;
+            let mut start = None;
+            let mut end = self.position.peek_paragraph();
+            let mut height = 0;
+            for cell in &row.cells {
+                let locator = self.position.next_paragraph();
+                let lines = self.layout.break_lines(&cell.content, cell_width);
+                height = height.max(lines.len());
+                end = Locator {
+                    offset: lines.last().map(|l| l.1 as u32).unwrap_or_default(),
+                    ..locator.clone()
+                };
+                start.get_or_insert(locator);
+            }
+            let start = start.unwrap_or_else(|| end.clone());
+            for i in 0..height.max(1) {
+                self.lines.push(Line {
+                    start: start.clone(),
+                    end: end.clone(),
+                    height: self.layout.metrics.line_height(),
+                    group,
+                    first_in_block: r == 0 && i == 0,
+                });
+            }
+        }
+    }
+
+    fn visit_image(&mut self, _image: &'a Image) {
+        self.push_block(self.layout.image_height);
+    }
+
+    fn visit_empty_line(&mut self) {
+        self.push_block(self.layout.metrics.line_height());
+    }
+
+    fn visit_spans(&mut self, spans: &'a [Span]) {
+        let locator = self.position.next_paragraph();
+        let lines = self.layout.break_lines(spans, self.layout.content_width());
+        for (i, (start, end)) in lines.into_iter().enumerate() {
+            self.lines.push(Line {
+                start: Locator {
+                    offset: start as u32,
+                    ..locator.clone()
+                },
+                end: Locator {
+                    offset: end as u32,
+                    ..locator.clone()
+                },
+                height: self.layout.metrics.line_height(),
+                group: self.group,
+                first_in_block: i == 0,
+            });
+        }
+    }
+}
+
+/// Characters that don't take any space unless a line is broken at them
+fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00AD}' | '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' | '\u{FEFF}'
+    ) || is_bidi_control(c)
+}
+
+#[cfg(feature = "ttf")]
+pub use self::ttf::{FontError, TtfMetrics};
+
+#[cfg(feature = "ttf")]
+mod ttf {
+    use std::collections::HashMap;
+    use std::fmt::{self, Display, Formatter};
+    use std::path::Path;
+    use std::{fs, io};
+    use ttf_parser::Face;
+
+    use super::{is_invisible, FontMetrics};
+    use crate::Text;
+
+    /// Metrics of a TrueType or OpenType font of a given size
+    #[derive(Clone, Debug)]
+    pub struct TtfMetrics {
+        advances: HashMap<char, f32>,
+        // advance of characters missing in the font
+        missing_advance: f32,
+        line_height: f32,
+    }
+
+    #[derive(Debug)]
+    pub enum FontError {
+        Io(io::Error),
+        Parse(ttf_parser::FaceParsingError),
+    }
+
+    impl Display for FontError {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            match self {
+                FontError::Io(e) => write!(f, "failed to read the font: {e}"),
+                FontError::Parse(e) => write!(f, "failed to parse the font: {e}"),
+            }
+        }
+    }
+
+    impl std::error::Error for FontError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            match self {
+                FontError::Io(e) => Some(e),
+                FontError::Parse(e) => Some(e),
+            }
+        }
+    }
+
+    impl TtfMetrics {
+        /// Loads metrics of the font from a TTF or OTF file, `font_size` is the size of an em
+        pub fn from_file(path: impl AsRef<Path>, font_size: f32) -> Result<TtfMetrics, FontError> {
+            let data = fs::read(path).map_err(FontError::Io)?;
+            TtfMetrics::from_bytes(&data, font_size)
+        }
+
+        pub fn from_bytes(data: &[u8], font_size: f32) -> Result<TtfMetrics, FontError> {
+            let face = Face::parse(data, 0).map_err(FontError::Parse)?;
+            let scale = font_size / f32::from(face.units_per_em());
+            let mut advances = HashMap::new();
+            if let Some(cmap) = face.tables().cmap {
+                for subtable in cmap.subtables.into_iter().filter(|s| s.is_unicode()) {
+                    subtable.codepoints(|codepoint| {
+                        let advance = char::from_u32(codepoint)
+                            .and_then(|c| face.glyph_index(c).map(|g| (c, g)))
+                            .and_then(|(c, g)| face.glyph_hor_advance(g).map(|a| (c, a)));
+                        if let Some((c, advance)) = advance {
+                            advances.insert(c, f32::from(advance) * scale);
+                        }
+                    });
+                }
+            }
+            let missing_advance = face
+                .glyph_hor_advance(ttf_parser::GlyphId(0))
+                .map(|a| f32::from(a) * scale)
+                .unwrap_or(font_size / 2.0);
+            // the sum of the metrics can overflow i16
+            let line_height = (f32::from(face.ascender()) - f32::from(face.descender())
+                + f32::from(face.line_gap()))
+                * scale;
+            Ok(TtfMetrics {
+                advances,
+                missing_advance,
+                line_height,
+            })
+        }
+    }
+
+    impl FontMetrics for TtfMetrics {
+        fn advance(&self, c: char, _text: &Text) -> f32 {
+            if is_invisible(c) {
+                return 0.0;
+            }
+            self.advances
+                .get(&c)
+                .copied()
+                .unwrap_or(self.missing_advance)
+        }
+
+        fn line_height(&self) -> f32 {
+            self.line_height
+        }
+    }
+}
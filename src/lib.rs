@@ -1,10 +1,62 @@
 mod proto {
     include!(concat!(env!("OUT_DIR"), "/protobook.rs"));
+    #[cfg(feature = "serde")]
+    include!(concat!(env!("OUT_DIR"), "/protobook.serde.rs"));
 }
+#[cfg(feature = "batch")]
+mod batch;
+#[cfg(any(feature = "fb2", feature = "html"))]
+mod convert;
+mod date;
+mod diff;
+mod direction;
 #[cfg(feature = "fb2")]
 mod fb2;
+#[cfg(feature = "fb2-stream")]
+mod fb2_stream;
+mod fingerprint;
+mod footnotes;
+#[cfg(feature = "html")]
+mod html;
+#[cfg(feature = "hyphenation")]
+mod hyphenation;
+mod language;
+mod layout;
+mod limits;
+mod normalize;
+mod overlay;
+mod patch;
+mod search;
+mod speech;
+mod stats;
+mod typography;
+mod visit;
 
+#[cfg(feature = "batch")]
+pub use batch::{
+    convert_library, BatchEntry, BatchOptions, BatchStatus, BatchSummary, SUMMARY_FILE,
+};
+#[cfg(any(feature = "fb2", feature = "html"))]
+pub use convert::{ConvertOptions, ConvertReport, LinkRejection, RejectedLink, TextStyle};
+pub use date::PartialDate;
+#[cfg(feature = "fb2-stream")]
+pub use fb2_stream::{ImportError, ResourceSink};
+pub use footnotes::FootnoteReferences;
+#[cfg(feature = "hyphenation")]
+pub use hyphenation::HyphenationOptions;
+pub use layout::{FixedAdvance, FontMetrics, Layout, Margins, Page, Viewport};
+#[cfg(feature = "ttf")]
+pub use layout::{FontError, TtfMetrics};
+pub use limits::{LimitError, Limits};
+pub use overlay::OverlayError;
+pub use patch::PatchError;
 pub use proto::*;
+pub use search::SearchHit;
+pub use speech::{Sentence, SpeechOptions};
+pub use stats::ReadingSpeed;
+
+/// Font weight of bold text, as converted from FB2 and HTML
+pub(crate) const BOLD_WEIGHT: u32 = 600;
 
 impl AsRef<str> for link::Href {
     fn as_ref(&self) -> &str {
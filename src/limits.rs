@@ -0,0 +1,185 @@
+use prost::Message;
+use std::fmt::{self, Display, Formatter};
+
+use crate::visit::{visit_chapter, visit_footnote, Visit};
+use crate::{span, Book, Chapter, Footnote, FootnoteType, Span};
+
+/// Bounds on books from untrusted sources, so that a crafted book can't exhaust the stack or the
+/// memory of a reader. Enforced by [`Book::decode_checked`] and by the conversions from other
+/// formats
+#[derive(Clone, Debug)]
+pub struct Limits {
+    /// Maximum nesting of chapters, as well as of cites, poems and styles in other formats. 64 by
+    /// default
+    pub max_depth: usize,
+    /// Maximum length of a text in bytes. 1 MiB by default
+    pub max_text_length: usize,
+    /// Maximum number of chapters along with their sub-chapters. 10 000 by default
+    pub max_chapters: usize,
+    /// Maximum number of spans of all paragraphs. 1 000 000 by default
+    pub max_spans: usize,
+    /// Maximum number of notes along with comments. 100 000 by default
+    pub max_footnotes: usize,
+    /// Maximum size in bytes of an encoded book or of a resource of another format, such as an
+    /// FB2 binary. 64 MiB by default
+    pub max_resource_size: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_depth: 64,
+            max_text_length: 1 << 20,
+            max_chapters: 10_000,
+            max_spans: 1_000_000,
+            max_footnotes: 100_000,
+            max_resource_size: 64 << 20,
+        }
+    }
+}
+
+/// A book that exceeds [`Limits`], by the exceeded limit, or that can't be decoded
+#[derive(Clone, Debug)]
+pub enum LimitError {
+    Depth(usize),
+    TextLength(usize),
+    Chapters(usize),
+    Spans(usize),
+    Footnotes(usize),
+    /// A resource, by its id, or the whole source, such as an encoded book, by an empty id,
+    /// larger than the limit
+    ResourceSize(String, usize),
+    Decode(prost::DecodeError),
+}
+
+impl Display for LimitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitError::Depth(limit) => write!(f, "nested deeper than {limit} levels"),
+            LimitError::TextLength(limit) => write!(f, "text longer than {limit} bytes"),
+            LimitError::Chapters(limit) => write!(f, "more than {limit} chapters"),
+            LimitError::Spans(limit) => write!(f, "more than {limit} spans"),
+            LimitError::Footnotes(limit) => write!(f, "more than {limit} footnotes"),
+            LimitError::ResourceSize(id, limit) if id.is_empty() => {
+                write!(f, "source larger than {limit} bytes")
+            }
+            LimitError::ResourceSize(id, limit) => {
+                write!(f, "resource {id} larger than {limit} bytes")
+            }
+            LimitError::Decode(e) => write!(f, "failed to decode the book: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for LimitError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LimitError::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Limits {
+    /// Checks every limit but the resource size against the book
+    pub fn check(&self, book: &Book) -> Result<(), LimitError> {
+        let mut checker = Checker {
+            limits: self,
+            depth: 0,
+            chapters: 0,
+            spans: 0,
+            footnotes: 0,
+            error: None,
+        };
+        checker.visit_book(book);
+        match checker.error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Book {
+    /// Decodes a book from an untrusted source, failing if it exceeds the limits.
+    ///
+    /// Only the size of the source is checked before decoding. The other limits apply to the
+    /// decoded book, so a source within [`Limits::max_resource_size`] is decoded in full before
+    /// it's rejected, and while decoding its nesting is bounded only by the fixed recursion limit
+    /// of prost, 100 levels
+    pub fn decode_checked(buf: &[u8], limits: &Limits) -> Result<Book, LimitError> {
+        if buf.len() > limits.max_resource_size {
+            return Err(LimitError::ResourceSize(
+                String::new(),
+                limits.max_resource_size,
+            ));
+        }
+        let book = Book::decode(buf).map_err(LimitError::Decode)?;
+        limits.check(&book)?;
+        Ok(book)
+    }
+}
+
+struct Checker<'l> {
+    limits: &'l Limits,
+    depth: usize,
+    chapters: usize,
+    spans: usize,
+    footnotes: usize,
+    // the first exceeded limit
+    error: Option<LimitError>,
+}
+
+impl Checker<'_> {
+    fn fail(&mut self, error: LimitError) {
+        self.error.get_or_insert(error);
+    }
+
+    fn check_text(&mut self, value: &str) {
+        if value.len() > self.limits.max_text_length {
+            self.fail(LimitError::TextLength(self.limits.max_text_length));
+        }
+    }
+}
+
+impl<'a> Visit<'a> for Checker<'_> {
+    fn visit_chapter(&mut self, chapter: &'a Chapter) {
+        self.chapters += 1;
+        if self.chapters > self.limits.max_chapters {
+            self.fail(LimitError::Chapters(self.limits.max_chapters));
+        }
+        if self.depth >= self.limits.max_depth {
+            self.fail(LimitError::Depth(self.limits.max_depth));
+            return;
+        }
+        self.depth += 1;
+        visit_chapter(self, chapter);
+        self.depth -= 1;
+    }
+
+    fn visit_footnote(&mut self, _type: FootnoteType, footnote: &'a Footnote) {
+        self.footnotes += 1;
+        if self.footnotes > self.limits.max_footnotes {
+            self.fail(LimitError::Footnotes(self.limits.max_footnotes));
+        }
+        visit_footnote(self, footnote);
+    }
+
+    fn visit_spans(&mut self, spans: &'a [Span]) {
+        self.spans += spans.len();
+        if self.spans > self.limits.max_spans {
+            self.fail(LimitError::Spans(self.limits.max_spans));
+        }
+        for span in spans {
+            match &span.span {
+                Some(span::Span::Text(t)) => self.check_text(&t.value),
+                Some(span::Span::Link(l)) => {
+                    l.content.iter().for_each(|t| self.check_text(&t.value))
+                }
+                Some(span::Span::Footnote(f)) => {
+                    f.content.iter().for_each(|t| self.check_text(&t.value))
+                }
+                Some(span::Span::Image(_)) | None => {}
+            }
+        }
+    }
+}
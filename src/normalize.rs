@@ -0,0 +1,139 @@
+use crate::visit::VisitMut;
+use crate::{span, Book, FontStyle, Span, Text};
+
+impl Book {
+    /// Merges adjacent text with identical styling, language and classes into a single `Text`,
+    /// including the text of links and footnote links.
+    ///
+    /// Converters produce a `Text` per text node of the source, so "a <style>b</style> c" turns
+    /// into three `Text`s that look the same. It's run by converters after the conversion.
+    pub fn merge_text_spans(&mut self) {
+        MergeTextSpans.visit_book_mut(self);
+    }
+}
+
+struct MergeTextSpans;
+
+impl VisitMut for MergeTextSpans {
+    fn visit_spans_mut(&mut self, spans: &mut Vec<Span>) {
+        for span in spans.iter_mut() {
+            match &mut span.span {
+                Some(span::Span::Link(l)) => merge_text(&mut l.content),
+                Some(span::Span::Footnote(f)) => merge_text(&mut f.content),
+                _ => {}
+            }
+        }
+        spans.dedup_by(|next, previous| match (&next.span, &mut previous.span) {
+            (Some(span::Span::Text(next)), Some(span::Span::Text(previous))) => {
+                merge(next, previous)
+            }
+            _ => false,
+        });
+    }
+}
+
+fn merge_text(text: &mut Vec<Text>) {
+    text.dedup_by(|next, previous| merge(next, previous));
+}
+
+/// Appends `next` to `previous` if both look the same, returns whether it did so
+fn merge(next: &Text, previous: &mut Text) -> bool {
+    if next.font_weight != previous.font_weight
+        || next.font_style != previous.font_style
+        || next.baseline_shift != previous.baseline_shift
+        || next.decorations != previous.decorations
+        || next.language != previous.language
+        || next.classes != previous.classes
+    {
+        return false;
+    }
+    previous.value.push_str(&next.value);
+    true
+}
+
+impl Book {
+    /// Collapses whitespace of every paragraph as XML and HTML renderers do: a run of spaces, tabs
+    /// and line breaks becomes a single space, even if it spans several `Text`s, whitespace at the
+    /// edges of the paragraph is removed along with `Text`s left without any content.
+    ///
+    /// Text styled as [`FontStyle::Code`] is kept as is.
+    pub fn collapse_whitespace(&mut self) {
+        CollapseWhitespace.visit_book_mut(self);
+    }
+}
+
+struct CollapseWhitespace;
+
+impl VisitMut for CollapseWhitespace {
+    fn visit_spans_mut(&mut self, spans: &mut Vec<Span>) {
+        // whether the paragraph has no visible content so far
+        let mut start = true;
+        // the text ending with a collapsed space, if it's the last content so far
+        let mut trailing_space: Option<&mut Text> = None;
+        for span in spans.iter_mut() {
+            let texts = match &mut span.span {
+                Some(span::Span::Text(t)) => std::slice::from_mut(t),
+                Some(span::Span::Link(l)) => l.content.as_mut_slice(),
+                Some(span::Span::Footnote(f)) => f.content.as_mut_slice(),
+                Some(span::Span::Image(_)) => {
+                    start = false;
+                    trailing_space = None;
+                    continue;
+                }
+                None => continue,
+            };
+            for text in texts {
+                if text.font_style() == FontStyle::Code {
+                    if !text.value.is_empty() {
+                        start = false;
+                        trailing_space = None;
+                    }
+                    continue;
+                }
+                let mut value = String::with_capacity(text.value.len());
+                let mut space = trailing_space.is_some() || start;
+                for c in text.value.chars() {
+                    if is_collapsible(c) {
+                        if !space {
+                            value.push(' ');
+                            space = true;
+                        }
+                    } else {
+                        value.push(c);
+                        space = false;
+                        start = false;
+                    }
+                }
+                text.value = value;
+                if space && text.value.ends_with(' ') {
+                    trailing_space = Some(text);
+                } else if !text.value.is_empty() {
+                    trailing_space = None;
+                }
+            }
+        }
+        if let Some(text) = trailing_space {
+            text.value.pop();
+        }
+
+        for span in spans.iter_mut() {
+            match &mut span.span {
+                Some(span::Span::Link(l)) => l.content.retain(|t| !t.value.is_empty()),
+                Some(span::Span::Footnote(f)) => f.content.retain(|t| !t.value.is_empty()),
+                _ => {}
+            }
+        }
+        spans.retain(|s| match &s.span {
+            Some(span::Span::Text(t)) => !t.value.is_empty(),
+            Some(span::Span::Link(l)) => !l.content.is_empty(),
+            Some(span::Span::Footnote(f)) => !f.content.is_empty(),
+            Some(span::Span::Image(_)) => true,
+            None => false,
+        });
+    }
+}
+
+/// Whitespace as defined by XML, the no-break space and others aren't collapsed
+fn is_collapsible(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\r')
+}
@@ -0,0 +1,216 @@
+use std::fmt::{self, Display, Formatter};
+
+use crate::{AudioClip, MediaOverlay};
+
+#[derive(Debug)]
+pub enum OverlayError {
+    #[cfg(feature = "media-overlay")]
+    Xml(quick_xml::Error),
+    #[cfg(feature = "media-overlay")]
+    Json(serde_json::Error),
+    /// A time that is neither a SMIL clock value nor a number of seconds
+    Time(String),
+    /// A fragment without an id, a start or an end, by its number starting from 1
+    Fragment(usize),
+}
+
+impl Display for OverlayError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(feature = "media-overlay")]
+            OverlayError::Xml(e) => write!(f, "failed to parse the SMIL document: {e}"),
+            #[cfg(feature = "media-overlay")]
+            OverlayError::Json(e) => write!(f, "failed to parse the JSON document: {e}"),
+            OverlayError::Time(time) => write!(f, "invalid time: {time}"),
+            OverlayError::Fragment(i) => write!(f, "fragment {i} lacks an id, a start or an end"),
+        }
+    }
+}
+
+impl std::error::Error for OverlayError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "media-overlay")]
+            OverlayError::Xml(e) => Some(e),
+            #[cfg(feature = "media-overlay")]
+            OverlayError::Json(e) => Some(e),
+            OverlayError::Time(_) | OverlayError::Fragment(_) => None,
+        }
+    }
+}
+
+impl MediaOverlay {
+    /// The clip of the audio file being played at `time`, in milliseconds, whose anchor locates
+    /// the text being narrated
+    pub fn clip_at(&self, audio: &str, time: u64) -> Option<&AudioClip> {
+        self.clips
+            .iter()
+            .find(|c| c.audio == audio && (c.start..c.end).contains(&time))
+    }
+
+    /// The first clip narrating the paragraph or sentence with the anchor, whose start is where
+    /// the narration of the text starts in its audio file
+    pub fn clip_of(&self, anchor: &str) -> Option<&AudioClip> {
+        self.clips.iter().find(|c| c.anchor == anchor)
+    }
+
+    /// Reads a forced alignment of the `audio` file in CSV, as produced by aeneas: a line per
+    /// fragment of the id of the text, its start and its end in seconds, optionally followed by
+    /// other columns, such as the text itself. The first line is skipped as a header if its start
+    /// isn't a number, while other lines with invalid times are errors
+    pub fn from_csv(csv: &str, audio: &str) -> Result<MediaOverlay, OverlayError> {
+        let mut clips = vec![];
+        let mut first = true;
+        for (i, line) in csv.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut columns = line.split(',').map(|c| c.trim().trim_matches('"'));
+            let (Some(anchor), Some(start), Some(end)) =
+                (columns.next(), columns.next(), columns.next())
+            else {
+                return Err(OverlayError::Fragment(i + 1));
+            };
+            if std::mem::take(&mut first) && start.parse::<f64>().is_err() {
+                continue;
+            }
+            clips.push(AudioClip {
+                anchor: anchor.to_string(),
+                audio: audio.to_string(),
+                start: parse_time(start)?,
+                end: parse_time(end)?,
+            });
+        }
+        Ok(MediaOverlay { clips })
+    }
+
+    /// Reads a forced alignment of the `audio` file in JSON, as produced by aeneas: an object
+    /// with `fragments`, each with the `id` of the text, its `begin` and `end` in seconds, either
+    /// as numbers or as strings. Negative times are errors
+    #[cfg(feature = "media-overlay")]
+    pub fn from_json(json: &str, audio: &str) -> Result<MediaOverlay, OverlayError> {
+        use serde_json::Value;
+
+        let value: Value = serde_json::from_str(json).map_err(OverlayError::Json)?;
+        let fragments = value["fragments"]
+            .as_array()
+            .map_or(&[][..], |f| f.as_slice());
+        let mut clips = vec![];
+        for (i, fragment) in fragments.iter().enumerate() {
+            let time = |key: &str| match &fragment[key] {
+                Value::Number(n) => Some(parse_time(&n.to_string())),
+                Value::String(s) => Some(parse_time(s)),
+                _ => None,
+            };
+            let (Some(anchor), Some(start), Some(end)) =
+                (fragment["id"].as_str(), time("begin"), time("end"))
+            else {
+                return Err(OverlayError::Fragment(i + 1));
+            };
+            clips.push(AudioClip {
+                anchor: anchor.to_string(),
+                audio: audio.to_string(),
+                start: start?,
+                end: end?,
+            });
+        }
+        Ok(MediaOverlay { clips })
+    }
+
+    /// Reads an EPUB 3 media overlay document: each `<par>` of a `<text>` and an `<audio>` clip
+    /// becomes a clip of the anchor in the fragment of the text's `src`, such as `para1` of
+    /// `chapter1.xhtml#para1`, and of the audio file identified by the audio's `src`. A clip
+    /// without `clipEnd` lasts until the end of its audio file, and gets `u64::MAX` as its end
+    #[cfg(feature = "media-overlay")]
+    pub fn from_smil(smil: &str) -> Result<MediaOverlay, OverlayError> {
+        use quick_xml::events::{BytesStart, Event};
+        use quick_xml::Reader;
+
+        fn attribute(e: &BytesStart, name: &str) -> Result<Option<String>, OverlayError> {
+            let Some(attribute) = e.try_get_attribute(name).map_err(OverlayError::Xml)? else {
+                return Ok(None);
+            };
+            let value = attribute.unescape_value().map_err(OverlayError::Xml)?;
+            Ok(Some(value.into_owned()))
+        }
+
+        let mut reader = Reader::from_str(smil);
+        let mut clips = vec![];
+        let mut anchor = None;
+        let mut clip = None;
+        loop {
+            match reader.read_event().map_err(OverlayError::Xml)? {
+                Event::Start(e) | Event::Empty(e) => match e.local_name().as_ref() {
+                    b"par" => {
+                        anchor = None;
+                        clip = None;
+                    }
+                    b"text" => {
+                        anchor = attribute(&e, "src")?.map(|src| match src.split_once('#') {
+                            Some((_, fragment)) => fragment.to_string(),
+                            None => src,
+                        });
+                    }
+                    b"audio" => {
+                        let time = |name| match attribute(&e, name)? {
+                            Some(time) => parse_time(&time).map(Some),
+                            None => Ok(None),
+                        };
+                        let (start, end) = (time("clipBegin")?, time("clipEnd")?);
+                        clip = Some((attribute(&e, "src")?.unwrap_or_default(), start, end));
+                    }
+                    _ => {}
+                },
+                Event::End(e) if e.local_name().as_ref() == b"par" => {
+                    if let (Some(anchor), Some((audio, start, end))) = (anchor.take(), clip.take())
+                    {
+                        clips.push(AudioClip {
+                            anchor,
+                            audio,
+                            start: start.unwrap_or_default(),
+                            end: end.unwrap_or(u64::MAX),
+                        });
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        Ok(MediaOverlay { clips })
+    }
+}
+
+/// Parses a SMIL clock value, such as "0:01:02.5", "01:02.5", "62.5s", "62500ms", "1.5min" or
+/// "62.5", into milliseconds
+fn parse_time(time: &str) -> Result<u64, OverlayError> {
+    let invalid = || OverlayError::Time(time.to_string());
+    let number = |s: &str| {
+        s.parse::<f64>()
+            .ok()
+            .filter(|n| n.is_finite() && *n >= 0.0)
+            .ok_or_else(invalid)
+    };
+    let trimmed = time.trim();
+    let seconds = if trimmed.contains(':') {
+        let parts = trimmed.split(':').collect::<Vec<_>>();
+        if parts.len() > 3 {
+            return Err(invalid());
+        }
+        let mut seconds = 0.0;
+        for part in parts {
+            seconds = seconds * 60.0 + number(part)?;
+        }
+        seconds
+    } else if let Some(ms) = trimmed.strip_suffix("ms") {
+        number(ms)? / 1000.0
+    } else if let Some(min) = trimmed.strip_suffix("min") {
+        number(min)? * 60.0
+    } else if let Some(h) = trimmed.strip_suffix('h') {
+        number(h)? * 3600.0
+    } else if let Some(s) = trimmed.strip_suffix('s') {
+        number(s)?
+    } else {
+        number(trimmed)?
+    };
+    Ok((seconds * 1000.0).round() as u64)
+}
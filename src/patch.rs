@@ -0,0 +1,363 @@
+use std::fmt::{self, Display, Formatter};
+
+use crate::diff::{metadata, PRECISIONS};
+use crate::visit::{
+    paragraph_text, visit_chapter_mut, visit_footnote_mut, visit_front_matter_mut, visit_title_mut,
+    VisitMut,
+};
+use crate::{
+    patch_operation, span, Book, Chapter, Content, FootnoteType, InsertContent, Locator, Patch,
+    PatchOperation, RemoveContent, ReplaceText, SetMetadata, Span, Text,
+};
+
+/// Why a patch can't be applied to a book
+#[derive(Clone, Debug, PartialEq)]
+pub enum PatchError {
+    /// The book isn't the version of the book that the patch is made for or results in
+    Fingerprint,
+    /// An operation, by its index, targets a part of the book that doesn't exist
+    Target(usize),
+    /// An operation, by its index, expects another text, content or value than the book has, or
+    /// sets a value the field can't have
+    Mismatch(usize),
+    /// Undoing the patch doesn't restore the book, such as when replaced text spanned texts of
+    /// several styles
+    Irreversible,
+}
+
+impl Display for PatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::Fingerprint => {
+                write!(f, "the patch is made for another version of the book")
+            }
+            PatchError::Target(i) => write!(f, "operation {i} targets a missing part of the book"),
+            PatchError::Mismatch(i) => write!(f, "operation {i} doesn't match the book"),
+            PatchError::Irreversible => write!(f, "the patch can't be undone exactly"),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+impl Patch {
+    /// Makes a patch of the operations for the book, failing if they can't be applied to it
+    pub fn new(book: &Book, operations: Vec<PatchOperation>) -> Result<Patch, PatchError> {
+        let mut patched = book.clone();
+        for (i, operation) in operations.iter().enumerate() {
+            apply(&mut patched, operation, i)?;
+        }
+        Ok(Patch {
+            base_fingerprint: book.fingerprint().to_vec(),
+            result_fingerprint: patched.fingerprint().to_vec(),
+            operations,
+        })
+    }
+
+    /// Fails if the patch isn't made for the book
+    pub fn check(&self, book: &Book) -> Result<(), PatchError> {
+        if book.fingerprint() == self.base_fingerprint.as_slice() {
+            Ok(())
+        } else {
+            Err(PatchError::Fingerprint)
+        }
+    }
+
+    /// The book with the operations applied, if the patch is made for the book and its operations
+    /// match it
+    pub fn apply(&self, book: &Book) -> Result<Book, PatchError> {
+        self.check(book)?;
+        let mut patched = book.clone();
+        for (i, operation) in self.operations.iter().enumerate() {
+            apply(&mut patched, operation, i)?;
+        }
+        if patched.fingerprint() == self.result_fingerprint.as_slice() {
+            Ok(patched)
+        } else {
+            Err(PatchError::Fingerprint)
+        }
+    }
+
+    /// The patch that undoes this patch of the book. Text is restored with the style of the text
+    /// where it starts, so a replacement of text of several styles can't be undone and fails
+    /// with [`PatchError::Irreversible`]
+    pub fn invert(&self, book: &Book) -> Result<Patch, PatchError> {
+        let patched = self.apply(book)?;
+        let operations = self.operations.iter().rev().map(invert).collect();
+        let inverse = Patch::new(&patched, operations)?;
+        if inverse.result_fingerprint == self.base_fingerprint {
+            Ok(inverse)
+        } else {
+            Err(PatchError::Irreversible)
+        }
+    }
+}
+
+fn invert(operation: &PatchOperation) -> PatchOperation {
+    let operation = match &operation.operation {
+        Some(patch_operation::Operation::ReplaceText(r)) => {
+            patch_operation::Operation::ReplaceText(ReplaceText {
+                start: r.start.clone(),
+                length: r.new_text.chars().count() as u32,
+                old_text: r.new_text.clone(),
+                new_text: r.old_text.clone(),
+            })
+        }
+        Some(patch_operation::Operation::InsertContent(i)) => {
+            patch_operation::Operation::RemoveContent(RemoveContent {
+                target: i.target.clone(),
+                index: i.index,
+                content: i.content.clone(),
+            })
+        }
+        Some(patch_operation::Operation::RemoveContent(r)) => {
+            patch_operation::Operation::InsertContent(InsertContent {
+                target: r.target.clone(),
+                index: r.index,
+                content: r.content.clone(),
+            })
+        }
+        Some(patch_operation::Operation::SetMetadata(s)) => {
+            patch_operation::Operation::SetMetadata(SetMetadata {
+                field: s.field.clone(),
+                old_value: s.new_value.clone(),
+                new_value: s.old_value.clone(),
+            })
+        }
+        None => return PatchOperation { operation: None },
+    };
+    PatchOperation {
+        operation: Some(operation),
+    }
+}
+
+/// Applies the operation, by its index, to the book
+fn apply(book: &mut Book, operation: &PatchOperation, i: usize) -> Result<(), PatchError> {
+    match &operation.operation {
+        Some(patch_operation::Operation::ReplaceText(r)) => {
+            let start = r.start.as_ref().ok_or(PatchError::Target(i))?;
+            let mut replace = Replace {
+                operation: r,
+                paragraph: start.paragraph,
+                matches: None,
+            };
+            if !visit_located(book, start, &mut replace) {
+                return Err(PatchError::Target(i));
+            }
+            match replace.matches {
+                Some(true) => Ok(()),
+                Some(false) => Err(PatchError::Mismatch(i)),
+                None => Err(PatchError::Target(i)),
+            }
+        }
+        Some(patch_operation::Operation::InsertContent(insert)) => {
+            let content = insert
+                .target
+                .as_ref()
+                .and_then(|target| contents_mut(book, target))
+                .filter(|content| insert.index as usize <= content.len())
+                .ok_or(PatchError::Target(i))?;
+            let inserted = insert.content.clone().unwrap_or_default();
+            content.insert(insert.index as usize, inserted);
+            Ok(())
+        }
+        Some(patch_operation::Operation::RemoveContent(remove)) => {
+            let content = remove
+                .target
+                .as_ref()
+                .and_then(|target| contents_mut(book, target))
+                .filter(|content| (remove.index as usize) < content.len())
+                .ok_or(PatchError::Target(i))?;
+            let expected = remove.content.clone().unwrap_or_default();
+            if content[remove.index as usize] != expected {
+                return Err(PatchError::Mismatch(i));
+            }
+            content.remove(remove.index as usize);
+            Ok(())
+        }
+        Some(patch_operation::Operation::SetMetadata(set)) => {
+            let (_, value) = metadata(book)
+                .into_iter()
+                .find(|(name, _)| *name == set.field)
+                .ok_or(PatchError::Target(i))?;
+            if value != set.old_value {
+                return Err(PatchError::Mismatch(i));
+            }
+            let value = set.new_value.clone();
+            let date = book.date.get_or_insert_with(Default::default);
+            match set.field.as_str() {
+                "short_title" => book.short_title = value,
+                "date.iso_date" => date.iso_date = value,
+                "date.iso_end_date" => date.iso_end_date = value,
+                "date.precision" => {
+                    let (precision, _) = PRECISIONS
+                        .iter()
+                        .find(|(_, name)| *name == value)
+                        .ok_or(PatchError::Mismatch(i))?;
+                    date.set_precision(*precision);
+                }
+                "date.approximate" => {
+                    date.approximate = match value.as_str() {
+                        "true" => true,
+                        "" => false,
+                        _ => return Err(PatchError::Mismatch(i)),
+                    }
+                }
+                "date.display_date" => date.display_date = value,
+                "language" => book.language = value,
+                _ => return Err(PatchError::Target(i)),
+            }
+            if book.date.as_ref().is_some_and(|d| *d == Default::default()) {
+                book.date = None;
+            }
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+/// Walks the paragraphs of the chapter, without its sub-chapters, of the footnote, or outside
+/// of chapters located by the locator. Fails if there's no such chapter or footnote
+fn visit_located(book: &mut Book, locator: &Locator, v: &mut impl VisitMut) -> bool {
+    let footnotes = match locator.footnote_type() {
+        FootnoteType::Unknown if locator.chapters.is_empty() => {
+            visit_front_matter_mut(v, book);
+            return true;
+        }
+        FootnoteType::Unknown => match chapter_mut(&mut book.chapters, &locator.chapters) {
+            Some(chapter) => {
+                visit_chapter_mut(v, chapter);
+                return true;
+            }
+            None => return false,
+        },
+        FootnoteType::Note => book.notes.as_mut(),
+        FootnoteType::Comment => book.comments.as_mut(),
+    };
+    let Some(footnotes) = footnotes else {
+        return false;
+    };
+    if locator.footnote.is_empty() {
+        if let Some(title) = &mut footnotes.title {
+            visit_title_mut(v, title);
+        }
+        return true;
+    }
+    match footnotes
+        .content
+        .iter_mut()
+        .find(|f| f.id == locator.footnote)
+    {
+        Some(footnote) => {
+            visit_footnote_mut(v, footnote);
+            true
+        }
+        None => false,
+    }
+}
+
+fn chapter_mut<'b>(chapters: &'b mut [Chapter], path: &[u32]) -> Option<&'b mut Chapter> {
+    let (first, rest) = path.split_first()?;
+    let chapter = chapters.get_mut(*first as usize)?;
+    match rest {
+        [] => Some(chapter),
+        _ => chapter_mut(&mut chapter.sub_chapters, rest),
+    }
+}
+
+/// The content of the chapter or the footnote located by the locator
+fn contents_mut<'b>(book: &'b mut Book, locator: &Locator) -> Option<&'b mut Vec<Content>> {
+    let footnotes = match locator.footnote_type() {
+        FootnoteType::Unknown => {
+            let chapter = chapter_mut(&mut book.chapters, &locator.chapters)?;
+            return Some(&mut chapter.content);
+        }
+        FootnoteType::Note => book.notes.as_mut()?,
+        FootnoteType::Comment => book.comments.as_mut()?,
+    };
+    let footnote = footnotes
+        .content
+        .iter_mut()
+        .find(|f| f.id == locator.footnote)?;
+    Some(&mut footnote.content)
+}
+
+/// Replaces the text of the paragraph with the index `paragraph`
+struct Replace<'o> {
+    operation: &'o ReplaceText,
+    paragraph: u32,
+    /// Whether the replaced text was found, once the paragraph is
+    matches: Option<bool>,
+}
+
+impl VisitMut for Replace<'_> {
+    // sub-chapters aren't walked, as their paragraphs are counted on their own
+    fn visit_chapter_mut(&mut self, _chapter: &mut Chapter) {}
+
+    fn visit_spans_mut(&mut self, spans: &mut Vec<Span>) {
+        if self.matches.is_some() {
+            return;
+        }
+        if self.paragraph > 0 {
+            self.paragraph -= 1;
+            return;
+        }
+        self.matches = Some(replace_text(spans, self.operation));
+    }
+}
+
+/// Replaces the characters of the spans, failing if they're not the expected ones or include an
+/// image. The new text goes to the text where the replaced one starts, or, if nothing's replaced,
+/// to the text that ends where the new one is inserted
+fn replace_text(spans: &mut Vec<Span>, operation: &ReplaceText) -> bool {
+    let start = operation.start.as_ref().map_or(0, |s| s.offset as usize);
+    let end = start + operation.length as usize;
+    let text = paragraph_text(spans);
+    let old: String = text.chars().skip(start).take(end - start).collect();
+    if text.chars().count() < end || old != operation.old_text {
+        return false;
+    }
+
+    let mut position = 0;
+    let mut new_text = Some(operation.new_text.as_str());
+    for span in spans.iter_mut() {
+        let texts: Vec<&mut Text> = match &mut span.span {
+            Some(span::Span::Text(t)) => vec![t],
+            Some(span::Span::Link(l)) => l.content.iter_mut().collect(),
+            Some(span::Span::Footnote(f)) => f.content.iter_mut().collect(),
+            Some(span::Span::Image(_)) => {
+                if (start..end).contains(&position) {
+                    return false;
+                }
+                position += 1;
+                continue;
+            }
+            None => continue,
+        };
+        for text in texts {
+            let length = text.value.chars().count();
+            let has_start = if start == end {
+                (position..=position + length).contains(&start)
+            } else {
+                (position..position + length).contains(&start)
+            };
+            let from = start.clamp(position, position + length) - position;
+            let to = end.clamp(position, position + length) - position;
+            if from < to || (has_start && new_text.is_some()) {
+                let chars: Vec<char> = text.value.chars().collect();
+                let mut value: String = chars[..from].iter().collect();
+                if has_start {
+                    value.push_str(new_text.take().unwrap_or_default());
+                }
+                value.extend(&chars[to..]);
+                text.value = value;
+            }
+            position += length;
+        }
+    }
+    if new_text.is_some_and(|t| !t.is_empty()) {
+        return false;
+    }
+    spans.retain(|s| !matches!(&s.span, Some(span::Span::Text(t)) if t.value.is_empty()));
+    true
+}
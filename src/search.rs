@@ -0,0 +1,260 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::direction::is_bidi_control;
+use crate::visit::{for_each_paragraph, paragraph_text};
+use crate::{Book, Locator, SearchIndex, SearchTerm};
+
+/// Number of characters of a snippet on each side of the found text
+const SNIPPET_CONTEXT: usize = 40;
+const ELLIPSIS: char = '…';
+const SOFT_HYPHEN: char = '\u{00AD}';
+
+/// Found text of a book
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchHit {
+    /// Start of the found text
+    pub start: Locator,
+    /// End of the found text, always in the same paragraph as the start
+    pub end: Locator,
+    /// The found text along with some text around it
+    pub snippet: String,
+    /// Characters of the found text in the snippet
+    pub snippet_range: Range<usize>,
+}
+
+impl SearchIndex {
+    /// Indexes every word of the book: its titles, epigraphs, chapters and footnotes.
+    ///
+    /// Words are split according to the Unicode word boundaries (UAX #29), folded to lower case
+    /// and with "ё" folded to "е", so that a query matches regardless of them.
+    pub fn new(book: &Book) -> SearchIndex {
+        index(book, String::new())
+    }
+
+    /// Like [`SearchIndex::new`], but also reduces words to their stems according to the
+    /// language of the book, so that "books" are found by "book". Words aren't stemmed if the
+    /// language isn't supported.
+    ///
+    /// Queries are stemmed the same way, as long as the index is searched with the `stemming`
+    /// feature enabled.
+    #[cfg(feature = "stemming")]
+    pub fn with_stemming(book: &Book) -> SearchIndex {
+        match algorithm(&book.language) {
+            Some(_) => index(book, book.language.clone()),
+            None => index(book, String::new()),
+        }
+    }
+
+    /// Finds every occurrence of the words of the query, in the same order, in the indexed book.
+    /// Hits are ordered as they appear in the book.
+    ///
+    /// The book is only used to make snippets, so it must be the same book that was indexed.
+    /// Occurrences that don't match it anymore are skipped.
+    pub fn search(&self, book: &Book, query: &str) -> Vec<SearchHit> {
+        let normalizer = Normalizer::new(&self.stemming_language);
+        let query = words(query, &normalizer);
+        let Some(postings) = query
+            .iter()
+            .map(|(term, _)| self.occurrences(term))
+            .collect::<Option<Vec<_>>>()
+        else {
+            return vec![];
+        };
+        let Some((first, rest)) = postings.split_first() else {
+            return vec![];
+        };
+        // occurrences of the first word followed by the rest of the words
+        let phrases = first
+            .iter()
+            .filter(|(paragraph, word)| {
+                rest.iter().zip(1..).all(|(occurrences, i)| {
+                    occurrences.binary_search(&(*paragraph, word + i)).is_ok()
+                })
+            })
+            .filter_map(|(paragraph, word)| {
+                let locator = self.paragraphs.get(*paragraph as usize)?;
+                Some((locator, *word as usize))
+            })
+            .collect::<Vec<_>>();
+
+        let mut texts: HashMap<_, Option<String>> =
+            phrases.iter().map(|(l, _)| (key(l), None)).collect();
+        for_each_paragraph(book, |locator, spans| {
+            if let Some(text) = texts.get_mut(&key(&locator)) {
+                *text = Some(paragraph_text(spans));
+            }
+        });
+
+        let mut hits = vec![];
+        // words of the last paragraph with a hit, as a paragraph may have several hits
+        let mut paragraph_words: Option<(&Locator, Words)> = None;
+        for (locator, word) in phrases {
+            let Some(Some(text)) = texts.get(&key(locator)) else {
+                continue;
+            };
+            if paragraph_words.as_ref().is_none_or(|(l, _)| *l != locator) {
+                paragraph_words = Some((locator, words(text, &normalizer)));
+            }
+            let words = &paragraph_words.as_ref().unwrap().1;
+            let Some(found) = words.get(word..word + query.len()) else {
+                continue;
+            };
+            if found.iter().zip(&query).any(|(w, q)| w.0 != q.0) {
+                continue;
+            }
+            let range = found[0].1.start..found[found.len() - 1].1.end;
+            hits.push(hit(locator, text, range));
+        }
+        hits
+    }
+
+    /// Occurrences of the term as pairs of paragraph and word numbers
+    fn occurrences(&self, term: &str) -> Option<Vec<(u32, u32)>> {
+        let i = self
+            .terms
+            .binary_search_by(|t| t.term.as_str().cmp(term))
+            .ok()?;
+        let occurrences = &self.terms[i].occurrences;
+        Some(occurrences.chunks_exact(2).map(|o| (o[0], o[1])).collect())
+    }
+}
+
+fn index(book: &Book, stemming_language: String) -> SearchIndex {
+    let normalizer = Normalizer::new(&stemming_language);
+    let mut paragraphs = vec![];
+    let mut terms: BTreeMap<String, Vec<u32>> = BTreeMap::new();
+    for_each_paragraph(book, |locator, spans| {
+        let words = words(&paragraph_text(spans), &normalizer);
+        if words.is_empty() {
+            return;
+        }
+        let paragraph = paragraphs.len() as u32;
+        for ((term, _), i) in words.into_iter().zip(0..) {
+            terms.entry(term).or_default().extend([paragraph, i]);
+        }
+        paragraphs.push(locator);
+    });
+    SearchIndex {
+        paragraphs,
+        terms: terms
+            .into_iter()
+            .map(|(term, occurrences)| SearchTerm { term, occurrences })
+            .collect(),
+        stemming_language,
+    }
+}
+
+fn hit(locator: &Locator, text: &str, range: Range<usize>) -> SearchHit {
+    let chars = text.chars().collect::<Vec<_>>();
+    let start = range.start.saturating_sub(SNIPPET_CONTEXT);
+    let end = (range.end + SNIPPET_CONTEXT).min(chars.len());
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push(ELLIPSIS);
+    }
+    let offset = snippet.chars().count();
+    snippet.extend(&chars[start..end]);
+    if end < chars.len() {
+        snippet.push(ELLIPSIS);
+    }
+    SearchHit {
+        start: Locator {
+            offset: range.start as u32,
+            ..locator.clone()
+        },
+        end: Locator {
+            offset: range.end as u32,
+            ..locator.clone()
+        },
+        snippet,
+        snippet_range: range.start - start + offset..range.end - start + offset,
+    }
+}
+
+/// Identifies the paragraph of a locator
+fn key(locator: &Locator) -> (Vec<u32>, String, i32, u32) {
+    (
+        locator.chapters.clone(),
+        locator.footnote.clone(),
+        locator.footnote_type,
+        locator.paragraph,
+    )
+}
+
+/// Normalized words along with their ranges of characters
+type Words = Vec<(String, Range<usize>)>;
+
+fn words(text: &str, normalizer: &Normalizer) -> Words {
+    let mut words = vec![];
+    let mut chars = 0;
+    // the byte offset of the text counted in `chars`
+    let mut counted = 0;
+    for (start, word) in text.unicode_word_indices() {
+        chars += text[counted..start].chars().count();
+        let length = word.chars().count();
+        words.push((normalizer.normalize(word), chars..chars + length));
+        chars += length;
+        counted = start + word.len();
+    }
+    words
+}
+
+struct Normalizer {
+    #[cfg(feature = "stemming")]
+    stemmer: Option<rust_stemmers::Stemmer>,
+}
+
+impl Normalizer {
+    #[cfg_attr(not(feature = "stemming"), allow(unused_variables))]
+    fn new(stemming_language: &str) -> Normalizer {
+        Normalizer {
+            #[cfg(feature = "stemming")]
+            stemmer: algorithm(stemming_language).map(rust_stemmers::Stemmer::create),
+        }
+    }
+
+    fn normalize(&self, word: &str) -> String {
+        let word = word
+            .chars()
+            .filter(|c| *c != SOFT_HYPHEN && !is_bidi_control(*c))
+            .flat_map(char::to_lowercase)
+            .map(|c| if c == 'ё' { 'е' } else { c })
+            .collect::<String>();
+        #[cfg(feature = "stemming")]
+        if let Some(stemmer) = &self.stemmer {
+            return stemmer.stem(&word).into_owned();
+        }
+        word
+    }
+}
+
+#[cfg(feature = "stemming")]
+fn algorithm(language: &str) -> Option<rust_stemmers::Algorithm> {
+    use rust_stemmers::Algorithm;
+
+    let primary = language.split('-').next().unwrap_or_default();
+    let algorithm = match primary.to_ascii_lowercase().as_str() {
+        "ar" => Algorithm::Arabic,
+        "da" => Algorithm::Danish,
+        "nl" => Algorithm::Dutch,
+        "en" => Algorithm::English,
+        "fi" => Algorithm::Finnish,
+        "fr" => Algorithm::French,
+        "de" => Algorithm::German,
+        "el" => Algorithm::Greek,
+        "hu" => Algorithm::Hungarian,
+        "it" => Algorithm::Italian,
+        "no" | "nb" | "nn" => Algorithm::Norwegian,
+        "pt" => Algorithm::Portuguese,
+        "ro" => Algorithm::Romanian,
+        "ru" => Algorithm::Russian,
+        "es" => Algorithm::Spanish,
+        "sv" => Algorithm::Swedish,
+        "ta" => Algorithm::Tamil,
+        "tr" => Algorithm::Turkish,
+        _ => return None,
+    };
+    Some(algorithm)
+}
@@ -0,0 +1,395 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::direction::is_bidi_control;
+use crate::visit::{
+    visit_chapter, visit_cite, visit_epigraph, visit_poem, Position, Visit, OBJECT_REPLACEMENT,
+};
+use crate::{
+    span, Book, Chapter, Cite, Epigraph, FontStyle, FootnoteType, Footnotes, Locator, Paragraph,
+    Poem, Span, Text, BOLD_WEIGHT,
+};
+
+const SOFT_HYPHEN: char = '\u{00AD}';
+
+/// Settings of [`Book::sentences`] and [`Book::to_ssml`]
+#[derive(Clone, Debug, Default)]
+pub struct SpeechOptions {
+    /// Languages to read the chapters with these anchors, and their sub-chapters, in instead of
+    /// the ones from [`Book::language_at`]
+    pub chapter_languages: HashMap<String, String>,
+}
+
+/// A sentence of a paragraph to be read aloud
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sentence {
+    /// Identifier of the sentence, the same every time the same book is segmented
+    pub id: String,
+    /// Start of the sentence
+    pub start: Locator,
+    /// End of the sentence, always in the same paragraph as the start
+    pub end: Locator,
+    /// Text to be spoken, without images, soft hyphens and links to footnotes
+    pub text: String,
+}
+
+impl Book {
+    /// Splits every paragraph of the book, except footnotes, into sentences to be read aloud.
+    ///
+    /// Sentences are split according to the Unicode sentence boundaries (UAX #29), even if they
+    /// span several `Text`s, except after initials and common abbreviations of the language of the
+    /// paragraph, such as "Mr." in English or "т. е." in Russian. The language of a paragraph is
+    /// its own or of the innermost element around it, as in [`Book::language_at`].
+    pub fn sentences(&self, options: &SpeechOptions) -> Vec<Sentence> {
+        let mut speech = Speech::new(self, options);
+        speech.visit_book(self);
+        speech.sentences
+    }
+
+    /// Exports the book, except footnotes, as an SSML document for text-to-speech engines.
+    ///
+    /// Every paragraph becomes a `<p>` of sentences as returned by [`Book::sentences`]. Each `<s>`
+    /// starts with a `<mark>` named after the id of the sentence, so that an engine reports when
+    /// it's being spoken. Italic text is emphasized moderately, bold text is emphasized strongly,
+    /// and empty lines become breaks. Paragraphs in other languages than the book have their
+    /// `xml:lang`, and text in other languages than its paragraph is put in `<lang>`.
+    pub fn to_ssml(&self, options: &SpeechOptions) -> String {
+        let mut speech = Speech::new(self, options);
+        speech
+            .ssml
+            .push_str(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        speech.ssml.push('\n');
+        speech
+            .ssml
+            .push_str(r#"<speak version="1.1" xmlns="http://www.w3.org/2001/10/synthesis""#);
+        if !self.language.is_empty() {
+            speech.ssml.push_str(" xml:lang=\"");
+            escape(&mut speech.ssml, &self.language);
+            speech.ssml.push('"');
+        }
+        speech.ssml.push_str(">\n");
+        speech.visit_book(self);
+        speech.ssml.push_str("</speak>\n");
+        speech.ssml
+    }
+}
+
+struct Speech<'a> {
+    options: &'a SpeechOptions,
+    position: Position,
+    // languages of the elements being visited, starting with the language of the book
+    languages: Vec<&'a str>,
+    sentences: Vec<Sentence>,
+    ssml: String,
+}
+
+impl<'a> Speech<'a> {
+    fn new(book: &'a Book, options: &'a SpeechOptions) -> Speech<'a> {
+        Speech {
+            options,
+            position: Position::new(),
+            languages: vec![&book.language],
+            sentences: vec![],
+            ssml: String::new(),
+        }
+    }
+}
+
+impl<'a> Visit<'a> for Speech<'a> {
+    fn visit_chapter(&mut self, chapter: &'a Chapter) {
+        let language = match self.options.chapter_languages.get(&chapter.anchor) {
+            Some(language) => language.as_str(),
+            None => &chapter.language,
+        };
+        self.within(language, |s| {
+            s.position.enter_chapter();
+            visit_chapter(s, chapter);
+            s.position.leave_chapter();
+        });
+    }
+
+    fn visit_epigraph(&mut self, epigraph: &'a Epigraph) {
+        self.within(&epigraph.language, |s| visit_epigraph(s, epigraph));
+    }
+
+    fn visit_poem(&mut self, poem: &'a Poem) {
+        self.within(&poem.language, |s| visit_poem(s, poem));
+    }
+
+    fn visit_cite(&mut self, cite: &'a Cite) {
+        self.within(&cite.language, |s| visit_cite(s, cite));
+    }
+
+    fn visit_paragraph(&mut self, paragraph: &'a Paragraph) {
+        self.within(&paragraph.language, |s| s.visit_spans(&paragraph.content));
+    }
+
+    fn visit_footnotes(&mut self, _type: FootnoteType, _footnotes: &'a Footnotes) {}
+
+    fn visit_empty_line(&mut self) {
+        self.ssml.push_str("<break strength=\"strong\"/>\n");
+    }
+
+    fn visit_spans(&mut self, spans: &'a [Span]) {
+        let locator = self.position.next_paragraph();
+        let language = *self.languages.last().unwrap();
+        let (text, voices, languages) = voiced_text(spans);
+        let mut sentences = sentences(&text, language)
+            .into_iter()
+            .map(|range| {
+                let spoken = text[range.clone()]
+                    .iter()
+                    .zip(&voices[range.clone()])
+                    .filter(|(_, voice)| **voice != Voice::Silent)
+                    .map(|(c, _)| *c)
+                    .collect::<String>();
+                (range, spoken.trim().to_string())
+            })
+            .filter(|(_, spoken)| !spoken.is_empty())
+            .peekable();
+        if sentences.peek().is_none() {
+            return;
+        }
+
+        self.ssml.push_str("<p");
+        if language != self.languages[0] {
+            self.ssml.push_str(" xml:lang=\"");
+            escape(&mut self.ssml, language);
+            self.ssml.push('"');
+        }
+        self.ssml.push('>');
+        for (i, (range, spoken)) in sentences.enumerate() {
+            let id = sentence_id(&locator, i);
+            self.ssml.push_str("<s><mark name=\"");
+            escape(&mut self.ssml, &id);
+            self.ssml.push_str("\"/>");
+            self.push_voiced(
+                &text[range.clone()],
+                &voices[range.clone()],
+                &languages[range.clone()],
+            );
+            self.ssml.push_str("</s>");
+            self.sentences.push(Sentence {
+                id,
+                start: Locator {
+                    offset: range.start as u32,
+                    ..locator.clone()
+                },
+                end: Locator {
+                    offset: range.end as u32,
+                    ..locator.clone()
+                },
+                text: spoken,
+            });
+        }
+        self.ssml.push_str("</p>\n");
+    }
+}
+
+impl<'a> Speech<'a> {
+    /// Visits an element in its own language, if it has one
+    fn within(&mut self, language: &'a str, visit: impl FnOnce(&mut Self)) {
+        let language = if language.is_empty() {
+            self.languages.last().unwrap()
+        } else {
+            language
+        };
+        self.languages.push(language);
+        visit(self);
+        self.languages.pop();
+    }
+
+    fn push_voiced(&mut self, text: &[char], voices: &[Voice], languages: &[&str]) {
+        let paragraph_language = *self.languages.last().unwrap();
+        let mut start = 0;
+        while start < text.len() {
+            let (voice, language) = (voices[start], languages[start]);
+            let end = (start..text.len())
+                .find(|i| voices[*i] != voice || languages[*i] != language)
+                .unwrap_or(text.len());
+            let value = text[start..end].iter().collect::<String>();
+            let foreign = voice != Voice::Silent
+                && !language.is_empty()
+                && !language.eq_ignore_ascii_case(paragraph_language);
+            if foreign {
+                self.ssml.push_str("<lang xml:lang=\"");
+                escape(&mut self.ssml, language);
+                self.ssml.push_str("\">");
+            }
+            match voice {
+                Voice::Silent => {}
+                Voice::Plain => escape(&mut self.ssml, &value),
+                Voice::Moderate | Voice::Strong => {
+                    let level = if voice == Voice::Strong {
+                        "strong"
+                    } else {
+                        "moderate"
+                    };
+                    self.ssml.push_str("<emphasis level=\"");
+                    self.ssml.push_str(level);
+                    self.ssml.push_str("\">");
+                    escape(&mut self.ssml, &value);
+                    self.ssml.push_str("</emphasis>");
+                }
+            }
+            if foreign {
+                self.ssml.push_str("</lang>");
+            }
+            start = end;
+        }
+    }
+}
+
+/// How a character of a paragraph is spoken
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Voice {
+    Silent,
+    Plain,
+    Moderate,
+    Strong,
+}
+
+/// Characters of a paragraph as counted by [`Locator::offset`] along with their voices and the
+/// languages of their texts, empty for the language of the paragraph
+fn voiced_text(spans: &[Span]) -> (Vec<char>, Vec<Voice>, Vec<&str>) {
+    let mut text = (vec![], vec![], vec![]);
+    for span in spans {
+        match &span.span {
+            Some(span::Span::Text(t)) => push_voiced(&mut text, t, false),
+            Some(span::Span::Link(l)) => l
+                .content
+                .iter()
+                .for_each(|t| push_voiced(&mut text, t, false)),
+            Some(span::Span::Footnote(f)) => f
+                .content
+                .iter()
+                .for_each(|t| push_voiced(&mut text, t, true)),
+            Some(span::Span::Image(_)) => {
+                text.0.push(OBJECT_REPLACEMENT);
+                text.1.push(Voice::Silent);
+                text.2.push("");
+            }
+            None => {}
+        }
+    }
+    text
+}
+
+fn push_voiced<'a>(
+    (chars, voices, languages): &mut (Vec<char>, Vec<Voice>, Vec<&'a str>),
+    text: &'a Text,
+    silent: bool,
+) {
+    for c in text.value.chars() {
+        chars.push(c);
+        languages.push(&text.language);
+        voices.push(if silent || c == SOFT_HYPHEN || is_bidi_control(c) {
+            Voice::Silent
+        } else if text.font_weight() >= BOLD_WEIGHT {
+            Voice::Strong
+        } else if text.font_style() == FontStyle::Italic {
+            Voice::Moderate
+        } else {
+            Voice::Plain
+        });
+    }
+}
+
+/// Character ranges of the sentences of the text without surrounding whitespace
+fn sentences(text: &[char], language: &str) -> Vec<Range<usize>> {
+    let string = text.iter().collect::<String>();
+    let abbreviations = abbreviations(language);
+    let mut sentences: Vec<Range<usize>> = vec![];
+    let mut start = 0;
+    // whether the previous sentence ends with an abbreviation and continues
+    let mut continued = false;
+    for sentence in string.split_sentence_bound_indices().map(|(_, s)| s) {
+        let end = start + sentence.chars().count();
+        match sentences.last_mut() {
+            Some(previous) if continued => previous.end = end,
+            _ => sentences.push(start..end),
+        }
+        let trimmed = sentence.trim_end();
+        continued =
+            abbreviations.iter().any(|a| ends_with_word(trimmed, a)) || ends_with_initial(trimmed);
+        start = end;
+    }
+    for range in &mut sentences {
+        while range.start < range.end && text[range.start].is_whitespace() {
+            range.start += 1;
+        }
+        while range.end > range.start && text[range.end - 1].is_whitespace() {
+            range.end -= 1;
+        }
+    }
+    sentences.retain(|r| !r.is_empty());
+    sentences
+}
+
+/// Abbreviations after which a sentence usually continues
+fn abbreviations(language: &str) -> &'static [&'static str] {
+    let primary = language.split('-').next().unwrap_or_default();
+    match primary.to_ascii_lowercase().as_str() {
+        "en" => &[
+            "Mr.", "Mrs.", "Ms.", "Dr.", "Prof.", "St.", "Jr.", "Sr.", "vs.", "e.g.", "i.e.",
+        ],
+        "ru" => &[
+            "т. е.",
+            "т.е.",
+            "т. к.",
+            "т.к.",
+            "т. н.",
+            "т.н.",
+            "г.",
+            "гг.",
+            "им.",
+            "см.",
+            "ср.",
+            "напр.",
+            "стр.",
+            "ул.",
+        ],
+        "de" => &[
+            "z. B.", "z.B.", "d. h.", "d.h.", "bzw.", "Nr.", "Dr.", "Prof.", "Hr.", "Fr.",
+        ],
+        _ => &[],
+    }
+}
+
+/// Whether the text ends with the word, which isn't the end of another word
+fn ends_with_word(text: &str, word: &str) -> bool {
+    text.strip_suffix(word)
+        .is_some_and(|rest| rest.chars().last().is_none_or(|c| !c.is_alphanumeric()))
+}
+
+/// Whether the text ends with an initial, such as "A." in "A. S. Pushkin"
+fn ends_with_initial(text: &str) -> bool {
+    let mut chars = text.chars().rev();
+    chars.next() == Some('.')
+        && chars.next().is_some_and(char::is_uppercase)
+        && chars.next().is_none_or(|c| !c.is_alphanumeric())
+}
+
+/// Identifier of the sentence made of the numbers of its chapters, paragraph and the sentence
+/// itself
+fn sentence_id(locator: &Locator, sentence: usize) -> String {
+    let mut id = String::from("s");
+    for chapter in &locator.chapters {
+        id.push_str(&format!("-{chapter}"));
+    }
+    id.push_str(&format!("-{}-{sentence}", locator.paragraph));
+    id
+}
+
+fn escape(xml: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => xml.push_str("&amp;"),
+            '<' => xml.push_str("&lt;"),
+            '>' => xml.push_str("&gt;"),
+            '"' => xml.push_str("&quot;"),
+            _ => xml.push(c),
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::visit::{paragraph_text, visit_table, Visit, OBJECT_REPLACEMENT};
+use crate::{
+    span, Book, Chapter, FootnoteType, Footnotes, Image, Paragraph, Span, Statistics, Table,
+};
+
+const SOFT_HYPHEN: &str = "\u{00AD}";
+
+/// Reading rates used to estimate [`Statistics::reading_time`]
+#[derive(Clone, Debug)]
+pub struct ReadingSpeed {
+    /// Words per minute in languages without a rate of their own. 200 by default
+    pub words_per_minute: u32,
+    /// Words per minute by language, either by the full tag, such as "pt-BR", or by its primary
+    /// subtag, such as "pt"
+    pub languages: HashMap<String, u32>,
+}
+
+impl Default for ReadingSpeed {
+    fn default() -> Self {
+        ReadingSpeed {
+            words_per_minute: 200,
+            languages: HashMap::new(),
+        }
+    }
+}
+
+impl ReadingSpeed {
+    /// Words per minute in the language
+    pub fn words_per_minute(&self, language: &str) -> u32 {
+        let primary = language.split('-').next().unwrap_or_default();
+        self.languages
+            .get(language)
+            .or_else(|| self.languages.get(primary))
+            .copied()
+            .unwrap_or(self.words_per_minute)
+    }
+}
+
+impl Book {
+    /// Counts words, characters and other elements of the book: its title, annotation, epigraphs
+    /// and chapters. Footnotes are only counted by the links to them, just like
+    /// [`Layout::estimate_pages`] doesn't count their pages.
+    ///
+    /// [`Layout::estimate_pages`]: crate::Layout::estimate_pages
+    pub fn compute_statistics(&self, speed: &ReadingSpeed) -> Statistics {
+        let mut counter = Counter::default();
+        counter.visit_book(self);
+        counter.finish(speed.words_per_minute(&self.language))
+    }
+
+    /// Counts statistics of the book and of each of its chapters, see
+    /// [`Book::compute_statistics`], and stores them in [`Book::statistics`] and
+    /// [`Chapter::statistics`], so that readers don't have to count them again
+    pub fn store_statistics(&mut self, speed: &ReadingSpeed) {
+        for chapter in &mut self.chapters {
+            store_statistics(chapter, &self.language, speed);
+        }
+        self.statistics = Some(self.compute_statistics(speed));
+    }
+}
+
+impl Chapter {
+    /// Counts words, characters and other elements of the chapter along with its sub-chapters.
+    /// The reading time is estimated for the `language` of the book
+    pub fn compute_statistics(&self, language: &str, speed: &ReadingSpeed) -> Statistics {
+        let mut counter = Counter::default();
+        counter.visit_chapter(self);
+        counter.finish(speed.words_per_minute(language))
+    }
+}
+
+fn store_statistics(chapter: &mut Chapter, language: &str, speed: &ReadingSpeed) {
+    for sub_chapter in &mut chapter.sub_chapters {
+        store_statistics(sub_chapter, language, speed);
+    }
+    chapter.statistics = Some(chapter.compute_statistics(language, speed));
+}
+
+#[derive(Default)]
+struct Counter {
+    statistics: Statistics,
+}
+
+impl Counter {
+    fn finish(mut self, words_per_minute: u32) -> Statistics {
+        let seconds = (self.statistics.words * 60).div_ceil(words_per_minute.max(1) as u64);
+        self.statistics.reading_time = seconds.try_into().unwrap_or(u32::MAX);
+        self.statistics
+    }
+}
+
+impl<'a> Visit<'a> for Counter {
+    fn visit_footnotes(&mut self, _type: FootnoteType, _footnotes: &'a Footnotes) {}
+
+    fn visit_table(&mut self, table: &'a Table) {
+        self.statistics.tables += 1;
+        visit_table(self, table);
+    }
+
+    fn visit_paragraph(&mut self, paragraph: &'a Paragraph) {
+        self.statistics.paragraphs += 1;
+        self.visit_spans(&paragraph.content);
+    }
+
+    fn visit_image(&mut self, _image: &'a Image) {
+        self.statistics.images += 1;
+    }
+
+    fn visit_spans(&mut self, spans: &'a [Span]) {
+        for span in spans {
+            match &span.span {
+                Some(span::Span::Image(_)) => self.statistics.images += 1,
+                Some(span::Span::Footnote(_)) => self.statistics.footnotes += 1,
+                _ => {}
+            }
+        }
+        // words are counted in the whole text, as styling may change in the middle of a word
+        let text = paragraph_text(spans);
+        self.statistics.words += text.unicode_words().count() as u64;
+        self.statistics.characters += text
+            .graphemes(true)
+            .filter(|g| *g != SOFT_HYPHEN && !g.starts_with(OBJECT_REPLACEMENT))
+            .count() as u64;
+    }
+}
@@ -0,0 +1,205 @@
+use crate::visit::VisitMut;
+use crate::{span, Book, FontStyle, Span, Text};
+
+const NBSP: char = '\u{00A0}';
+const EM_DASH: char = '—';
+const ELLIPSIS: char = '…';
+const APOSTROPHE: char = '’';
+/// Stands in for content without text, such as images and code, while looking at the neighbours
+/// of a character
+const OBJECT: char = '\u{FFFC}';
+/// Marks a character removed from a paragraph, such as the dots after the first one of an
+/// ellipsis, a noncharacter never found in texts
+const REMOVED: char = '\u{FFFF}';
+
+impl Book {
+    /// Replaces typewriter punctuation with typographic one according to the language of the book:
+    /// straight quotes become «ёлочки» in Russian and “curly quotes” in English, a hyphen
+    /// surrounded by spaces becomes an em dash and so on. Languages without known rules are left
+    /// as is.
+    ///
+    /// Quotes and ellipses are matched within a paragraph, even if they are in different `Text`s.
+    /// Text styled as [`FontStyle::Code`] is kept as is.
+    pub fn apply_typography(&mut self) {
+        if let Some(rules) = Rules::for_language(&self.language) {
+            Typography { rules }.visit_book_mut(self);
+        }
+    }
+}
+
+struct Rules {
+    /// Quotes of the outer and the inner levels
+    quotes: [(char, char); 2],
+    /// Whether single quotes are used as inner quotes, otherwise they can only be apostrophes
+    single_quotes: bool,
+    /// Whether a dash is bound to the preceding word with a no-break space
+    bound_dash: bool,
+    /// Whether one- and two-letter words are bound to the following word with a no-break space
+    bound_short_words: bool,
+}
+
+impl Rules {
+    fn for_language(language: &str) -> Option<Rules> {
+        let primary = language.split('-').next().unwrap_or_default();
+        match primary.to_ascii_lowercase().as_str() {
+            "ru" | "uk" | "be" => Some(Rules {
+                quotes: [('«', '»'), ('„', '“')],
+                single_quotes: false,
+                bound_dash: true,
+                bound_short_words: true,
+            }),
+            "en" => Some(Rules {
+                quotes: [('“', '”'), ('‘', '’')],
+                single_quotes: true,
+                bound_dash: false,
+                bound_short_words: false,
+            }),
+            "de" => Some(Rules {
+                quotes: [('„', '“'), ('‚', '‘')],
+                single_quotes: false,
+                bound_dash: true,
+                bound_short_words: false,
+            }),
+            _ => None,
+        }
+    }
+}
+
+struct Typography {
+    rules: Rules,
+}
+
+impl VisitMut for Typography {
+    fn visit_spans_mut(&mut self, spans: &mut Vec<Span>) {
+        let mut texts: Vec<&mut Text> = vec![];
+        let mut chars = vec![];
+        // the range of characters of each text in `chars`
+        let mut ranges = vec![];
+        for span in spans.iter_mut() {
+            let content = match &mut span.span {
+                Some(span::Span::Text(t)) => std::slice::from_mut(t),
+                Some(span::Span::Link(l)) => l.content.as_mut_slice(),
+                Some(span::Span::Footnote(f)) => f.content.as_mut_slice(),
+                Some(span::Span::Image(_)) => {
+                    chars.push(OBJECT);
+                    continue;
+                }
+                None => continue,
+            };
+            for text in content {
+                if text.font_style() == FontStyle::Code {
+                    chars.push(OBJECT);
+                    continue;
+                }
+                let start = chars.len();
+                chars.extend(text.value.chars());
+                ranges.push(start..chars.len());
+                texts.push(text);
+            }
+        }
+
+        self.replace_quotes(&mut chars);
+        self.replace_dashes(&mut chars);
+        replace_ellipses(&mut chars);
+        if self.rules.bound_short_words {
+            bind_short_words(&mut chars);
+        }
+
+        for (text, range) in texts.into_iter().zip(ranges) {
+            text.value = chars[range].iter().filter(|c| **c != REMOVED).collect();
+        }
+    }
+}
+
+impl Typography {
+    fn replace_quotes(&self, chars: &mut [char]) {
+        let [outer, inner] = self.rules.quotes;
+        let mut depth = 0;
+        for i in 0..chars.len() {
+            let previous = i.checked_sub(1).map(|i| chars[i]);
+            let next = chars.get(i + 1).copied();
+            let opening = previous
+                .is_none_or(|p| p.is_whitespace() || is_opening(p) || p == outer.0 || p == inner.0);
+            match chars[i] {
+                '"' if opening => {
+                    chars[i] = if depth == 0 { outer.0 } else { inner.0 };
+                    depth += 1;
+                }
+                '"' if depth > 0 => {
+                    depth -= 1;
+                    chars[i] = if depth == 0 { outer.1 } else { inner.1 };
+                }
+                '\'' if previous.is_some_and(char::is_alphanumeric)
+                    && next.is_some_and(char::is_alphanumeric) =>
+                {
+                    chars[i] = APOSTROPHE;
+                }
+                '\'' if self.rules.single_quotes && opening => chars[i] = inner.0,
+                '\'' if self.rules.single_quotes => chars[i] = inner.1,
+                c if c == outer.0 => depth += 1,
+                c if c == outer.1 && depth > 0 => depth -= 1,
+                _ => {}
+            }
+        }
+    }
+
+    fn replace_dashes(&self, chars: &mut [char]) {
+        for i in 0..chars.len() {
+            if !matches!(chars[i], '-' | '–') {
+                continue;
+            }
+            let next_space = chars.get(i + 1).is_some_and(|c| c.is_whitespace());
+            if i == 0 && next_space {
+                // a dash opening a line of dialogue
+                chars[i] = EM_DASH;
+                if self.rules.bound_dash {
+                    chars[i + 1] = NBSP;
+                }
+            } else if i > 0 && chars[i - 1].is_whitespace() && next_space {
+                chars[i] = EM_DASH;
+                if self.rules.bound_dash {
+                    chars[i - 1] = NBSP;
+                }
+            }
+        }
+    }
+}
+
+/// Replaces each three dots with an ellipsis, even if they are in different texts
+fn replace_ellipses(chars: &mut [char]) {
+    let mut i = 0;
+    while i + 3 <= chars.len() {
+        if chars[i..i + 3] == ['.'; 3] {
+            chars[i..i + 3].copy_from_slice(&[ELLIPSIS, REMOVED, REMOVED]);
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+}
+
+/// Replaces the space after each word of one or two letters with a no-break space, so that
+/// prepositions and conjunctions don't end a line
+fn bind_short_words(chars: &mut [char]) {
+    let mut word = 0;
+    for i in 0..chars.len() {
+        let c = chars[i];
+        if c.is_alphabetic() {
+            word += 1;
+            continue;
+        }
+        let starts_word = i
+            .checked_sub(word + 1)
+            .is_none_or(|s| chars[s].is_whitespace() || is_opening(chars[s]) || chars[s] == '«');
+        let next_word = chars.get(i + 1).is_some_and(|c| c.is_alphanumeric());
+        if c == ' ' && (1..=2).contains(&word) && starts_word && next_word {
+            chars[i] = NBSP;
+        }
+        word = 0;
+    }
+}
+
+/// Whether a character can precede an opening quote
+fn is_opening(c: char) -> bool {
+    matches!(c, '(' | '[' | '{' | '—' | '–' | '-')
+}
@@ -0,0 +1,574 @@
+use crate::{
+    annotation_element, cite_element, content, epigraph_element, poem_element, span, title_element,
+    Annotation, Book, Chapter, Cite, Content, Epigraph, Footnote, FootnoteType, Footnotes, Image,
+    Locator, Paragraph, Poem, Span, Stanza, Table, Title,
+};
+
+/// Walks every element of a book that contains text, allowing to modify it.
+///
+/// The default implementation of each method continues the walk into the children of the element,
+/// so an implementation overrides only the methods it's interested in.
+pub(crate) trait VisitMut {
+    fn visit_book_mut(&mut self, book: &mut Book) {
+        visit_book_mut(self, book)
+    }
+
+    fn visit_chapter_mut(&mut self, chapter: &mut Chapter) {
+        visit_chapter_mut(self, chapter)
+    }
+
+    fn visit_footnote_mut(&mut self, footnote: &mut Footnote) {
+        visit_footnote_mut(self, footnote)
+    }
+
+    fn visit_epigraph_mut(&mut self, epigraph: &mut Epigraph) {
+        visit_epigraph_mut(self, epigraph)
+    }
+
+    fn visit_poem_mut(&mut self, poem: &mut Poem) {
+        visit_poem_mut(self, poem)
+    }
+
+    fn visit_cite_mut(&mut self, cite: &mut Cite) {
+        visit_cite_mut(self, cite)
+    }
+
+    fn visit_paragraph_mut(&mut self, paragraph: &mut Paragraph) {
+        self.visit_spans_mut(&mut paragraph.content)
+    }
+
+    /// Called for the content of every paragraph and table cell
+    fn visit_spans_mut(&mut self, _spans: &mut Vec<Span>) {}
+}
+
+pub(crate) fn visit_book_mut<V: VisitMut + ?Sized>(v: &mut V, book: &mut Book) {
+    visit_front_matter_mut(v, book);
+    for chapter in &mut book.chapters {
+        v.visit_chapter_mut(chapter);
+    }
+    for footnotes in [&mut book.notes, &mut book.comments].into_iter().flatten() {
+        visit_footnotes_mut(v, footnotes);
+    }
+}
+
+/// Walks the title, the annotation and the epigraphs of the book, which precede its chapters
+pub(crate) fn visit_front_matter_mut<V: VisitMut + ?Sized>(v: &mut V, book: &mut Book) {
+    if let Some(title) = &mut book.title {
+        visit_title_mut(v, title);
+    }
+    if let Some(annotation) = &mut book.annotation {
+        visit_annotation_mut(v, annotation);
+    }
+    for epigraph in &mut book.epigraphs {
+        v.visit_epigraph_mut(epigraph);
+    }
+}
+
+pub(crate) fn visit_chapter_mut<V: VisitMut + ?Sized>(v: &mut V, chapter: &mut Chapter) {
+    if let Some(title) = &mut chapter.title {
+        visit_title_mut(v, title);
+    }
+    if let Some(annotation) = &mut chapter.annotation {
+        visit_annotation_mut(v, annotation);
+    }
+    for epigraph in &mut chapter.epigraphs {
+        v.visit_epigraph_mut(epigraph);
+    }
+    for content in &mut chapter.content {
+        visit_content_mut(v, content);
+    }
+    for sub_chapter in &mut chapter.sub_chapters {
+        v.visit_chapter_mut(sub_chapter);
+    }
+}
+
+pub(crate) fn visit_footnote_mut<V: VisitMut + ?Sized>(v: &mut V, footnote: &mut Footnote) {
+    if let Some(title) = &mut footnote.title {
+        visit_title_mut(v, title);
+    }
+    for content in &mut footnote.content {
+        visit_content_mut(v, content);
+    }
+}
+
+fn visit_footnotes_mut<V: VisitMut + ?Sized>(v: &mut V, footnotes: &mut Footnotes) {
+    if let Some(title) = &mut footnotes.title {
+        visit_title_mut(v, title);
+    }
+    for footnote in &mut footnotes.content {
+        v.visit_footnote_mut(footnote);
+    }
+}
+
+pub(crate) fn visit_title_mut<V: VisitMut + ?Sized>(v: &mut V, title: &mut Title) {
+    for element in &mut title.content {
+        match &mut element.title_element {
+            Some(title_element::TitleElement::Paragraph(p)) => v.visit_paragraph_mut(p),
+            Some(title_element::TitleElement::EmptyLine(_)) | None => {}
+        }
+    }
+}
+
+fn visit_annotation_mut<V: VisitMut + ?Sized>(v: &mut V, annotation: &mut Annotation) {
+    for element in &mut annotation.content {
+        match &mut element.annotation_element {
+            Some(annotation_element::AnnotationElement::Paragraph(p))
+            | Some(annotation_element::AnnotationElement::Subtitle(p)) => v.visit_paragraph_mut(p),
+            Some(annotation_element::AnnotationElement::Poem(p)) => v.visit_poem_mut(p),
+            Some(annotation_element::AnnotationElement::Cite(c)) => v.visit_cite_mut(c),
+            Some(annotation_element::AnnotationElement::Table(t)) => visit_table_mut(v, t),
+            Some(annotation_element::AnnotationElement::EmptyLine(_)) | None => {}
+        }
+    }
+}
+
+pub(crate) fn visit_epigraph_mut<V: VisitMut + ?Sized>(v: &mut V, epigraph: &mut Epigraph) {
+    for element in &mut epigraph.content {
+        match &mut element.epigraph_element {
+            Some(epigraph_element::EpigraphElement::Paragraph(p)) => v.visit_paragraph_mut(p),
+            Some(epigraph_element::EpigraphElement::Poem(p)) => v.visit_poem_mut(p),
+            Some(epigraph_element::EpigraphElement::Cite(c)) => v.visit_cite_mut(c),
+            Some(epigraph_element::EpigraphElement::EmptyLine(_)) | None => {}
+        }
+    }
+    for author in &mut epigraph.authors {
+        v.visit_paragraph_mut(author);
+    }
+}
+
+fn visit_content_mut<V: VisitMut + ?Sized>(v: &mut V, content: &mut Content) {
+    match &mut content.content {
+        Some(content::Content::Paragraph(p)) | Some(content::Content::Subtitle(p)) => {
+            v.visit_paragraph_mut(p)
+        }
+        Some(content::Content::Poem(p)) => v.visit_poem_mut(p),
+        Some(content::Content::Cite(c)) => v.visit_cite_mut(c),
+        Some(content::Content::Table(t)) => visit_table_mut(v, t),
+        Some(content::Content::EmptyLine(_)) | Some(content::Content::Image(_)) | None => {}
+    }
+}
+
+pub(crate) fn visit_poem_mut<V: VisitMut + ?Sized>(v: &mut V, poem: &mut Poem) {
+    if let Some(title) = &mut poem.title {
+        visit_title_mut(v, title);
+    }
+    for epigraph in &mut poem.epigraphs {
+        v.visit_epigraph_mut(epigraph);
+    }
+    for element in &mut poem.content {
+        match &mut element.poem_element {
+            Some(poem_element::PoemElement::Subtitle(p)) => v.visit_paragraph_mut(p),
+            Some(poem_element::PoemElement::Stanza(s)) => {
+                if let Some(title) = &mut s.title {
+                    visit_title_mut(v, title);
+                }
+                if let Some(subtitle) = &mut s.subtitle {
+                    v.visit_paragraph_mut(subtitle);
+                }
+                for line in &mut s.content {
+                    v.visit_paragraph_mut(line);
+                }
+            }
+            None => {}
+        }
+    }
+    for author in &mut poem.authors {
+        v.visit_paragraph_mut(author);
+    }
+}
+
+pub(crate) fn visit_cite_mut<V: VisitMut + ?Sized>(v: &mut V, cite: &mut Cite) {
+    for element in &mut cite.content {
+        match &mut element.cite_element {
+            Some(cite_element::CiteElement::Paragraph(p))
+            | Some(cite_element::CiteElement::Subtitle(p)) => v.visit_paragraph_mut(p),
+            Some(cite_element::CiteElement::Poem(p)) => v.visit_poem_mut(p),
+            Some(cite_element::CiteElement::Table(t)) => visit_table_mut(v, t),
+            Some(cite_element::CiteElement::EmptyLine(_)) | None => {}
+        }
+    }
+    for author in &mut cite.authors {
+        v.visit_paragraph_mut(author);
+    }
+}
+
+fn visit_table_mut<V: VisitMut + ?Sized>(v: &mut V, table: &mut Table) {
+    for row in &mut table.rows {
+        for cell in &mut row.cells {
+            v.visit_spans_mut(&mut cell.content);
+        }
+    }
+}
+
+/// Walks every element of a book in the order of reading.
+///
+/// The default implementation of each method continues the walk into the children of the element,
+/// so an implementation overrides only the methods it's interested in.
+pub(crate) trait Visit<'a> {
+    fn visit_book(&mut self, book: &'a Book) {
+        visit_book(self, book)
+    }
+
+    fn visit_chapter(&mut self, chapter: &'a Chapter) {
+        visit_chapter(self, chapter)
+    }
+
+    fn visit_footnotes(&mut self, footnote_type: FootnoteType, footnotes: &'a Footnotes) {
+        visit_footnotes(self, footnote_type, footnotes)
+    }
+
+    fn visit_footnote(&mut self, _type: FootnoteType, footnote: &'a Footnote) {
+        visit_footnote(self, footnote)
+    }
+
+    fn visit_epigraph(&mut self, epigraph: &'a Epigraph) {
+        visit_epigraph(self, epigraph)
+    }
+
+    fn visit_poem(&mut self, poem: &'a Poem) {
+        visit_poem(self, poem)
+    }
+
+    fn visit_cite(&mut self, cite: &'a Cite) {
+        visit_cite(self, cite)
+    }
+
+    fn visit_stanza(&mut self, stanza: &'a Stanza) {
+        visit_stanza(self, stanza)
+    }
+
+    fn visit_table(&mut self, table: &'a Table) {
+        visit_table(self, table)
+    }
+
+    fn visit_paragraph(&mut self, paragraph: &'a Paragraph) {
+        self.visit_spans(&paragraph.content)
+    }
+
+    fn visit_image(&mut self, _image: &'a Image) {}
+
+    fn visit_empty_line(&mut self) {}
+
+    /// Called for the content of every paragraph and table cell
+    fn visit_spans(&mut self, _spans: &'a [Span]) {}
+}
+
+pub(crate) fn visit_book<'a, V: Visit<'a> + ?Sized>(v: &mut V, book: &'a Book) {
+    if let Some(title) = &book.title {
+        visit_title(v, title);
+    }
+    if let Some(annotation) = &book.annotation {
+        visit_annotation(v, annotation);
+    }
+    for epigraph in &book.epigraphs {
+        v.visit_epigraph(epigraph);
+    }
+    for chapter in &book.chapters {
+        v.visit_chapter(chapter);
+    }
+    if let Some(notes) = &book.notes {
+        v.visit_footnotes(FootnoteType::Note, notes);
+    }
+    if let Some(comments) = &book.comments {
+        v.visit_footnotes(FootnoteType::Comment, comments);
+    }
+}
+
+pub(crate) fn visit_chapter<'a, V: Visit<'a> + ?Sized>(v: &mut V, chapter: &'a Chapter) {
+    if let Some(title) = &chapter.title {
+        visit_title(v, title);
+    }
+    if let Some(annotation) = &chapter.annotation {
+        visit_annotation(v, annotation);
+    }
+    if let Some(cover) = &chapter.cover {
+        v.visit_image(cover);
+    }
+    for epigraph in &chapter.epigraphs {
+        v.visit_epigraph(epigraph);
+    }
+    for content in &chapter.content {
+        visit_content(v, content);
+    }
+    for sub_chapter in &chapter.sub_chapters {
+        v.visit_chapter(sub_chapter);
+    }
+}
+
+pub(crate) fn visit_footnotes<'a, V: Visit<'a> + ?Sized>(
+    v: &mut V,
+    footnote_type: FootnoteType,
+    footnotes: &'a Footnotes,
+) {
+    if let Some(title) = &footnotes.title {
+        visit_title(v, title);
+    }
+    for footnote in &footnotes.content {
+        v.visit_footnote(footnote_type, footnote);
+    }
+}
+
+pub(crate) fn visit_footnote<'a, V: Visit<'a> + ?Sized>(v: &mut V, footnote: &'a Footnote) {
+    if let Some(title) = &footnote.title {
+        visit_title(v, title);
+    }
+    for content in &footnote.content {
+        visit_content(v, content);
+    }
+}
+
+pub(crate) fn visit_stanza<'a, V: Visit<'a> + ?Sized>(v: &mut V, stanza: &'a Stanza) {
+    if let Some(title) = &stanza.title {
+        visit_title(v, title);
+    }
+    if let Some(subtitle) = &stanza.subtitle {
+        v.visit_paragraph(subtitle);
+    }
+    for line in &stanza.content {
+        v.visit_paragraph(line);
+    }
+}
+
+pub(crate) fn visit_table<'a, V: Visit<'a> + ?Sized>(v: &mut V, table: &'a Table) {
+    for row in &table.rows {
+        for cell in &row.cells {
+            v.visit_spans(&cell.content);
+        }
+    }
+}
+
+fn visit_title<'a, V: Visit<'a> + ?Sized>(v: &mut V, title: &'a Title) {
+    for element in &title.content {
+        match &element.title_element {
+            Some(title_element::TitleElement::Paragraph(p)) => v.visit_paragraph(p),
+            Some(title_element::TitleElement::EmptyLine(_)) => v.visit_empty_line(),
+            None => {}
+        }
+    }
+}
+
+fn visit_annotation<'a, V: Visit<'a> + ?Sized>(v: &mut V, annotation: &'a Annotation) {
+    for element in &annotation.content {
+        match &element.annotation_element {
+            Some(annotation_element::AnnotationElement::Paragraph(p))
+            | Some(annotation_element::AnnotationElement::Subtitle(p)) => v.visit_paragraph(p),
+            Some(annotation_element::AnnotationElement::Poem(p)) => v.visit_poem(p),
+            Some(annotation_element::AnnotationElement::Cite(c)) => v.visit_cite(c),
+            Some(annotation_element::AnnotationElement::Table(t)) => v.visit_table(t),
+            Some(annotation_element::AnnotationElement::EmptyLine(_)) => v.visit_empty_line(),
+            None => {}
+        }
+    }
+}
+
+pub(crate) fn visit_epigraph<'a, V: Visit<'a> + ?Sized>(v: &mut V, epigraph: &'a Epigraph) {
+    for element in &epigraph.content {
+        match &element.epigraph_element {
+            Some(epigraph_element::EpigraphElement::Paragraph(p)) => v.visit_paragraph(p),
+            Some(epigraph_element::EpigraphElement::Poem(p)) => v.visit_poem(p),
+            Some(epigraph_element::EpigraphElement::Cite(c)) => v.visit_cite(c),
+            Some(epigraph_element::EpigraphElement::EmptyLine(_)) => v.visit_empty_line(),
+            None => {}
+        }
+    }
+    for author in &epigraph.authors {
+        v.visit_paragraph(author);
+    }
+}
+
+fn visit_content<'a, V: Visit<'a> + ?Sized>(v: &mut V, content: &'a Content) {
+    match &content.content {
+        Some(content::Content::Paragraph(p)) | Some(content::Content::Subtitle(p)) => {
+            v.visit_paragraph(p)
+        }
+        Some(content::Content::Poem(p)) => v.visit_poem(p),
+        Some(content::Content::Cite(c)) => v.visit_cite(c),
+        Some(content::Content::Table(t)) => v.visit_table(t),
+        Some(content::Content::Image(i)) => v.visit_image(i),
+        Some(content::Content::EmptyLine(_)) => v.visit_empty_line(),
+        None => {}
+    }
+}
+
+pub(crate) fn visit_poem<'a, V: Visit<'a> + ?Sized>(v: &mut V, poem: &'a Poem) {
+    if let Some(title) = &poem.title {
+        visit_title(v, title);
+    }
+    for epigraph in &poem.epigraphs {
+        v.visit_epigraph(epigraph);
+    }
+    for element in &poem.content {
+        match &element.poem_element {
+            Some(poem_element::PoemElement::Subtitle(p)) => v.visit_paragraph(p),
+            Some(poem_element::PoemElement::Stanza(s)) => v.visit_stanza(s),
+            None => {}
+        }
+    }
+    for author in &poem.authors {
+        v.visit_paragraph(author);
+    }
+}
+
+pub(crate) fn visit_cite<'a, V: Visit<'a> + ?Sized>(v: &mut V, cite: &'a Cite) {
+    for element in &cite.content {
+        match &element.cite_element {
+            Some(cite_element::CiteElement::Paragraph(p))
+            | Some(cite_element::CiteElement::Subtitle(p)) => v.visit_paragraph(p),
+            Some(cite_element::CiteElement::Poem(p)) => v.visit_poem(p),
+            Some(cite_element::CiteElement::Table(t)) => v.visit_table(t),
+            Some(cite_element::CiteElement::EmptyLine(_)) => v.visit_empty_line(),
+            None => {}
+        }
+    }
+    for author in &cite.authors {
+        v.visit_paragraph(author);
+    }
+}
+
+/// Keeps track of the position of a [`Visit`] in a book to make [`Locator`]s.
+///
+/// A visitor calls `enter_*` and `leave_*` methods as it walks into chapters and footnotes, and
+/// [`Position::next_paragraph`] for each content of paragraphs and table cells.
+pub(crate) struct Position {
+    // indices of the visited chapters, starting with the top-level one
+    chapters: Vec<u32>,
+    // the number of visited chapters at each level, including the top one
+    siblings: Vec<u32>,
+    // the visited footnote, and the title of the footnotes it belongs to, if any
+    footnotes: Vec<(String, FootnoteType)>,
+    // the number of visited paragraphs outside of chapters and in each of `chapters` or
+    // `footnotes`
+    paragraphs: Vec<u32>,
+}
+
+impl Position {
+    pub(crate) fn new() -> Position {
+        Position {
+            chapters: vec![],
+            siblings: vec![0],
+            footnotes: vec![],
+            paragraphs: vec![0],
+        }
+    }
+
+    /// The position right before the chapter located by `path`, as in [`Locator::chapters`]
+    pub(crate) fn before_chapter(path: &[u32]) -> Position {
+        let (index, parents) = path.split_last().expect("a chapter path can't be empty");
+        let mut siblings = vec![0; path.len()];
+        siblings[parents.len()] = *index;
+        Position {
+            chapters: parents.to_vec(),
+            siblings,
+            footnotes: vec![],
+            paragraphs: vec![0; path.len()],
+        }
+    }
+
+    pub(crate) fn enter_chapter(&mut self) {
+        let siblings = self.siblings.last_mut().unwrap();
+        self.chapters.push(*siblings);
+        *siblings += 1;
+        self.siblings.push(0);
+        self.paragraphs.push(0);
+    }
+
+    pub(crate) fn leave_chapter(&mut self) {
+        self.chapters.pop();
+        self.siblings.pop();
+        self.paragraphs.pop();
+    }
+
+    /// Enters a footnote, or the title of footnotes if `id` is empty
+    pub(crate) fn enter_footnote(&mut self, id: &str, footnote_type: FootnoteType) {
+        self.footnotes.push((id.to_string(), footnote_type));
+        self.paragraphs.push(0);
+    }
+
+    pub(crate) fn leave_footnote(&mut self) {
+        self.footnotes.pop();
+        self.paragraphs.pop();
+    }
+
+    /// Locates the start of the next paragraph and moves past it
+    pub(crate) fn next_paragraph(&mut self) -> Locator {
+        let locator = self.peek_paragraph();
+        *self.paragraphs.last_mut().unwrap() += 1;
+        locator
+    }
+
+    /// Locates the start of the next paragraph, or the end of the chapter or footnote if there's
+    /// no next paragraph
+    pub(crate) fn peek_paragraph(&self) -> Locator {
+        let (footnote, footnote_type) = self
+            .footnotes
+            .last()
+            .cloned()
+            .unwrap_or((String::new(), FootnoteType::Unknown));
+        Locator {
+            chapters: self.chapters.clone(),
+            footnote,
+            footnote_type: footnote_type.into(),
+            paragraph: *self.paragraphs.last().unwrap(),
+            offset: 0,
+        }
+    }
+}
+
+/// Calls `f` with the location and the content of every paragraph and table cell of the book in
+/// the order of reading, including footnotes
+pub(crate) fn for_each_paragraph<'a>(book: &'a Book, f: impl FnMut(Locator, &'a [Span])) {
+    Paragraphs {
+        position: Position::new(),
+        f,
+    }
+    .visit_book(book);
+}
+
+struct Paragraphs<F> {
+    position: Position,
+    f: F,
+}
+
+impl<'a, F: FnMut(Locator, &'a [Span])> Visit<'a> for Paragraphs<F> {
+    fn visit_chapter(&mut self, chapter: &'a Chapter) {
+        self.position.enter_chapter();
+        visit_chapter(self, chapter);
+        self.position.leave_chapter();
+    }
+
+    fn visit_footnotes(&mut self, footnote_type: FootnoteType, footnotes: &'a Footnotes) {
+        self.position.enter_footnote("", footnote_type);
+        visit_footnotes(self, footnote_type, footnotes);
+        self.position.leave_footnote();
+    }
+
+    fn visit_footnote(&mut self, footnote_type: FootnoteType, footnote: &'a Footnote) {
+        self.position.enter_footnote(&footnote.id, footnote_type);
+        visit_footnote(self, footnote);
+        self.position.leave_footnote();
+    }
+
+    fn visit_spans(&mut self, spans: &'a [Span]) {
+        let locator = self.position.next_paragraph();
+        (self.f)(locator, spans);
+    }
+}
+
+/// Stands in for an image in the text of a paragraph
+pub(crate) const OBJECT_REPLACEMENT: char = '\u{FFFC}';
+
+/// The text of a paragraph as counted by [`Locator::offset`]: the values of all its `Text`s,
+/// including the text of links, and a replacement character for each image
+pub(crate) fn paragraph_text(spans: &[Span]) -> String {
+    let mut text = String::new();
+    for span in spans {
+        match &span.span {
+            Some(span::Span::Text(t)) => text.push_str(&t.value),
+            Some(span::Span::Link(l)) => l.content.iter().for_each(|t| text.push_str(&t.value)),
+            Some(span::Span::Footnote(f)) => f.content.iter().for_each(|t| text.push_str(&t.value)),
+            Some(span::Span::Image(_)) => text.push(OBJECT_REPLACEMENT),
+            None => {}
+        }
+    }
+    text
+}
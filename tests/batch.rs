@@ -0,0 +1,141 @@
+use prost::Message;
+use protobook::{convert_library, BatchEntry, BatchOptions, BatchStatus, Container, SUMMARY_FILE};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+const FB2: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<FictionBook xmlns="http://www.gribuser.ru/xml/fictionbook/2.0" xmlns:l="http://www.w3.org/1999/xlink">
+  <description>
+    <title-info>
+      <genre>prose</genre>
+      <author><first-name>Антон</first-name><last-name>Макаренко</last-name></author>
+      <book-title>Библиотека</book-title>
+      <lang>ru</lang>
+    </title-info>
+  </description>
+  <body>
+    <section>
+      <p>Текст со <a l:href="javascript:alert(1)">ссылкой</a></p>
+      <image l:href="#pixel.png"/>
+    </section>
+  </body>
+  <binary id="pixel.png" content-type="image/png">AQID</binary>
+</FictionBook>"##;
+
+fn library() -> PathBuf {
+    let root = std::env::temp_dir().join(format!("protobook-batch-{}", Uuid::new_v4()));
+    let source = root.join("source");
+    fs::create_dir_all(source.join("shelf")).unwrap();
+    fs::write(source.join("shelf/book.fb2"), FB2).unwrap();
+    fs::write(source.join("broken.fb2"), "<FictionBook>").unwrap();
+    fs::write(source.join("readme.txt"), "not a book").unwrap();
+
+    let mut zip = ZipWriter::new(File::create(source.join("archive.zip")).unwrap());
+    zip.start_file("books/zipped.fb2", SimpleFileOptions::default())
+        .unwrap();
+    zip.write_all(FB2.as_bytes()).unwrap();
+    zip.finish().unwrap();
+    root
+}
+
+fn summary(output: &std::path::Path) -> Vec<BatchEntry> {
+    let mut entries: Vec<BatchEntry> = fs::read_to_string(output.join(SUMMARY_FILE))
+        .unwrap()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    entries.sort_by(|a, b| a.source.cmp(&b.source));
+    entries
+}
+
+#[test]
+fn convert() {
+    let root = library();
+    let output = root.join("output");
+    let options = BatchOptions {
+        threads: 2,
+        ..BatchOptions::default()
+    };
+    let counts = convert_library(&root.join("source"), &output, &options).unwrap();
+    assert_eq!((counts.converted, counts.failed, counts.skipped), (2, 1, 0));
+
+    let entries = summary(&output);
+    let sources: Vec<_> = entries.iter().map(|e| e.source.as_str()).collect();
+    assert_eq!(
+        sources,
+        [
+            "archive.zip/books/zipped.fb2",
+            "broken.fb2",
+            "shelf/book.fb2"
+        ]
+    );
+    assert_eq!(entries[1].status, BatchStatus::Failed);
+    assert!(entries[1].error.is_some());
+    assert_eq!(entries[2].status, BatchStatus::Converted);
+    assert_eq!(entries[2].output.as_deref(), Some("shelf/book.pbook"));
+    assert_eq!(entries[2].diagnostics.len(), 1);
+
+    let container = fs::read(output.join("archive.zip/books/zipped.pbook")).unwrap();
+    let container = Container::decode(container.as_slice()).unwrap();
+    let book = container.book.unwrap();
+    assert_eq!(book.short_title, "Библиотека");
+    assert_eq!(container.resources.len(), 1);
+    assert_eq!(container.resources[0].content, [1, 2, 3]);
+    assert_eq!(container.resources[0].content_type, "image/png");
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn resume() {
+    let root = library();
+    let output = root.join("output");
+    fs::create_dir_all(&output).unwrap();
+    // an interrupted run that converted one book and was writing the next line
+    let entry = BatchEntry {
+        source: "shelf/book.fb2".to_string(),
+        status: BatchStatus::Converted,
+        output: Some("shelf/book.pbook".to_string()),
+        error: None,
+        diagnostics: vec![],
+    };
+    let summary_line = serde_json::to_string(&entry).unwrap();
+    fs::write(
+        output.join(SUMMARY_FILE),
+        format!("{summary_line}\n{{\"source\":\"bro"),
+    )
+    .unwrap();
+
+    let options = BatchOptions::default();
+    let counts = convert_library(&root.join("source"), &output, &options).unwrap();
+    assert_eq!((counts.converted, counts.failed, counts.skipped), (1, 1, 1));
+    assert!(!output.join("shelf/book.pbook").exists());
+    assert_eq!(summary(&output).len(), 3);
+
+    let counts = convert_library(&root.join("source"), &output, &options).unwrap();
+    assert_eq!((counts.converted, counts.failed, counts.skipped), (0, 0, 3));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn oversized_books() {
+    let root = library();
+    let output = root.join("output");
+    let mut options = BatchOptions::default();
+    options.convert.limits.max_resource_size = 100;
+    let counts = convert_library(&root.join("source"), &output, &options).unwrap();
+    assert_eq!((counts.converted, counts.failed), (0, 3));
+
+    // the same check for books in archives and in directories
+    let entries = summary(&output);
+    for entry in [&entries[0], &entries[2]] {
+        assert!(entry.error.as_ref().unwrap().contains(&entry.source));
+    }
+
+    fs::remove_dir_all(root).unwrap();
+}
@@ -0,0 +1,116 @@
+use protobook::{Book, Date, DatePrecision, PartialDate};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+fn fb2_date(date: &str) -> Option<Date> {
+    let fb2 = format!(
+        r##"<?xml version="1.0" encoding="UTF-8"?>
+<FictionBook xmlns="http://www.gribuser.ru/xml/fictionbook/2.0">
+  <description>
+    <title-info>
+      <genre>prose</genre>
+      <book-title>Даты</book-title>
+      {date}
+      <lang>ru</lang>
+    </title-info>
+  </description>
+  <body>
+    <section>
+      <p>Текст</p>
+    </section>
+  </body>
+</FictionBook>"##
+    );
+    let fb2: fb2::FictionBook = quick_xml::de::from_str(&fb2).unwrap();
+    Book::from_fb2(fb2, Uuid::new_v4(), &HashMap::new())
+        .unwrap()
+        .date
+}
+
+#[test]
+fn parse() {
+    let date = Date::parse("1936").unwrap();
+    assert_eq!(date.iso_date, "1936");
+    assert_eq!(date.precision(), DatePrecision::Year);
+    assert_eq!(date.start(), Some(PartialDate::year(1936)));
+
+    let date = Date::parse("05.1936").unwrap();
+    assert_eq!(date.iso_date, "1936-05");
+    assert_eq!(date.precision(), DatePrecision::Month);
+
+    let date = Date::parse("12.05.1936 г.").unwrap();
+    assert_eq!(date.iso_date, "1936-05-12");
+    assert_eq!(date.display_date, "12.05.1936 г.");
+    assert_eq!(date.start(), PartialDate::day(1936, 5, 12));
+
+    let date = Date::parse("около 1936 года").unwrap();
+    assert_eq!(date.iso_date, "1936");
+    assert!(date.approximate);
+
+    let date = Date::parse("1925–1935 гг.").unwrap();
+    assert_eq!(
+        (date.iso_date.as_str(), date.iso_end_date.as_str()),
+        ("1925", "1935")
+    );
+    assert_eq!(date.end(), Some(PartialDate::year(1935)));
+
+    assert_eq!(Date::parse("1936-1937").unwrap().iso_end_date, "1937");
+    assert_eq!(Date::parse("1937-1936"), None);
+    assert_eq!(Date::parse("30.02.1936"), None);
+    assert_eq!(Date::parse("май 1936"), None);
+}
+
+#[test]
+fn partial_dates() {
+    let mut dates = [
+        PartialDate::day(1936, 1, 1).unwrap(),
+        PartialDate::month(1936, 1).unwrap(),
+        PartialDate::year(1936),
+        PartialDate::year(1935),
+    ];
+    dates.sort();
+    let dates: Vec<_> = dates.iter().map(ToString::to_string).collect();
+    assert_eq!(dates, ["1935", "1936", "1936-01", "1936-01-01"]);
+
+    assert_eq!(
+        PartialDate::month(1936, 2).unwrap().last_day(),
+        PartialDate::day(1936, 2, 29).unwrap()
+    );
+    assert_eq!(PartialDate::parse_iso("1936-13"), None);
+
+    // dates converted before they had a precision are known to the day
+    let legacy = Date {
+        iso_date: "1936-01-01".to_string(),
+        display_date: "1936".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(legacy.start(), PartialDate::day(1936, 1, 1));
+}
+
+#[test]
+fn from_fb2() {
+    let date = fb2_date(r#"<date value="1936-01-01">1936</date>"#).unwrap();
+    assert_eq!(date.iso_date, "1936");
+    assert_eq!(date.precision(), DatePrecision::Year);
+
+    let date = fb2_date(r#"<date value="1925-01-01">около 1925–1935 гг.</date>"#).unwrap();
+    assert_eq!(
+        (date.iso_date.as_str(), date.iso_end_date.as_str()),
+        ("1925", "1935")
+    );
+    assert!(date.approximate);
+
+    // the displayed date doesn't agree with the ISO one, or can't be understood
+    let date = fb2_date(r#"<date value="1936-05-12">1935</date>"#).unwrap();
+    assert_eq!(date.iso_date, "1936-05-12");
+    assert_eq!(date.display_date, "1935");
+    assert_eq!(date.precision(), DatePrecision::Day);
+    let date = fb2_date(r#"<date value="1936-05-12">12 мая 1936</date>"#).unwrap();
+    assert_eq!(date.precision(), DatePrecision::Day);
+
+    let date = fb2_date("<date>весна 1936</date>").unwrap();
+    assert_eq!(date.iso_date, "");
+    assert_eq!(date.display_date, "весна 1936");
+    assert_eq!(date.precision(), DatePrecision::Unknown);
+    assert_eq!(date.start(), None);
+}
@@ -0,0 +1,33 @@
+use chrono::NaiveDate;
+use protobook::{Date, PartialDate};
+use time::Month;
+
+#[test]
+fn chrono() {
+    let date = Date::parse("1925–1935").unwrap();
+    assert_eq!(date.first_naive_date(), NaiveDate::from_ymd_opt(1925, 1, 1));
+    assert_eq!(
+        date.last_naive_date(),
+        NaiveDate::from_ymd_opt(1935, 12, 31)
+    );
+
+    let day = NaiveDate::from_ymd_opt(1936, 5, 12).unwrap();
+    assert_eq!(
+        PartialDate::from(day),
+        PartialDate::day(1936, 5, 12).unwrap()
+    );
+    assert_eq!(Date::new(day.into()).iso_date, "1936-05-12");
+}
+
+#[test]
+fn time() {
+    let date = Date::parse("02.1936").unwrap();
+    let first = time::Date::from_calendar_date(1936, Month::February, 1).unwrap();
+    let last = time::Date::from_calendar_date(1936, Month::February, 29).unwrap();
+    assert_eq!(date.first_date(), Some(first));
+    assert_eq!(date.last_date(), Some(last));
+    assert_eq!(
+        PartialDate::from(last),
+        PartialDate::day(1936, 2, 29).unwrap()
+    );
+}
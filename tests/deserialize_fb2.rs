@@ -1,7 +1,7 @@
 use std::fs::File;
 use std::io::BufReader;
 use uuid::Uuid;
-use protobook::{Book, Date};
+use protobook::{Book, Date, DatePrecision};
 
 #[test]
 fn deserialize_fb2() {
@@ -16,9 +16,9 @@ fn deserialize_fb2() {
         .iter()
         .map(|binary| (binary.id.clone(), Uuid::new_v4()))
         .collect();
-    let book = Book::from_fb2(book, book_id, &binary_ids);
+    let book = Book::from_fb2(book, book_id, &binary_ids).unwrap();
 
     assert_eq!(book.id, book_id.to_string());
     assert_eq!(book.short_title, "Педагогическая поэма. Полная версия");
-    assert_eq!(book.date, Some(Date { iso_date: "1936-01-01".to_string(), display_date: "1936".to_string() }));
+    assert_eq!(book.date, Some(Date { iso_date: "1936".to_string(), display_date: "1936".to_string(), precision: DatePrecision::Year.into(), ..Default::default() }));
 }
@@ -0,0 +1,132 @@
+use protobook::{
+    content, link, span, Book, ConvertOptions, Direction, FontStyle, LimitError, Limits,
+};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+const ARTICLE: &str = r#"<!DOCTYPE html>
+<html lang="ru">
+<head>
+  <title>Статья</title>
+  <meta name="author" content="Антон Макаренко">
+  <style>p { color: red; }</style>
+</head>
+<body>
+  <script>alert(1)</script>
+  <p>Вступление</p>
+  <h1 id="first">Первая глава</h1>
+  <p>Текст <em>курсивом</em> и <a href="https://example.com">ссылка</a></p>
+  <h2>Подглава</h2>
+  <blockquote><p>Цитата</p></blockquote>
+  <figure><img src="pic.png" alt="Рисунок"><figcaption>Подпись</figcaption></figure>
+  <h1>Вторая глава</h1>
+  <table><tr><th>A</th><th>B</th></tr><tr><td>1</td><td><code>2</code></td></tr></table>
+</body>
+</html>"#;
+
+#[test]
+fn deserialize_html() {
+    let book_id = Uuid::new_v4();
+    let image_id = Uuid::new_v4();
+    let image_ids = HashMap::from([("pic.png".to_string(), image_id)]);
+    let book = Book::from_html(ARTICLE, book_id, &image_ids).unwrap();
+
+    assert_eq!(book.id, book_id.to_string());
+    assert_eq!(book.short_title, "Статья");
+    assert_eq!(book.language, "ru");
+    assert_eq!(book.authors[0].full_name, "Антон Макаренко");
+    assert_eq!(book.chapters.len(), 3);
+
+    let first = &book.chapters[1];
+    assert_eq!(first.anchor, "first");
+    assert_eq!(first.sub_chapters.len(), 1);
+    let Some(content::Content::Paragraph(paragraph)) = &first.content[0].content else {
+        panic!("expected a paragraph");
+    };
+    let Some(span::Span::Text(italic)) = &paragraph.content[1].span else {
+        panic!("expected text");
+    };
+    assert_eq!(italic.font_style(), FontStyle::Italic);
+    let Some(span::Span::Link(link)) = &paragraph.content[3].span else {
+        panic!("expected a link");
+    };
+    assert_eq!(
+        link.href,
+        Some(link::Href::Remote("https://example.com/".to_string()))
+    );
+
+    let sub_chapter = &first.sub_chapters[0];
+    assert!(matches!(
+        sub_chapter.content[0].content,
+        Some(content::Content::Cite(_))
+    ));
+    let Some(content::Content::Image(image)) = &sub_chapter.content[1].content else {
+        panic!("expected an image");
+    };
+    assert_eq!(image.id, image_id.to_string());
+    assert_eq!(image.title, "Подпись");
+
+    let Some(content::Content::Table(table)) = &book.chapters[2].content[0].content else {
+        panic!("expected a table");
+    };
+    assert!(table.header_row);
+    assert_eq!(table.rows.len(), 2);
+}
+
+#[test]
+fn nesting_limit() {
+    let html = format!("<p>{}текст{}</p>", "<b>".repeat(100), "</b>".repeat(100));
+    let options = ConvertOptions {
+        limits: Limits {
+            max_depth: 32,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let result = Book::from_html_with_options(&html, Uuid::new_v4(), &HashMap::new(), &options);
+    assert!(matches!(result, Err(LimitError::Depth(32))));
+    assert!(Book::from_html(&html, Uuid::new_v4(), &HashMap::new()).is_err());
+    assert!(Book::from_html("<p><b>текст</b></p>", Uuid::new_v4(), &HashMap::new()).is_ok());
+
+    // wrappers don't become structures of the book, so they aren't limited
+    let html = format!(
+        "{}<p>{}текст{}</p>{}",
+        "<div>".repeat(100),
+        "<span>".repeat(100),
+        "</span>".repeat(100),
+        "</div>".repeat(100)
+    );
+    assert!(Book::from_html(&html, Uuid::new_v4(), &HashMap::new()).is_ok());
+}
+
+#[test]
+fn directions() {
+    let html = r#"<html lang="he"><body>
+      <p>שלום עולם</p>
+      <p dir="ltr">123</p>
+      <p>Hello, world</p>
+    </body></html>"#;
+    let book = Book::from_html(html, Uuid::new_v4(), &HashMap::new()).unwrap();
+    assert_eq!(book.direction(), Direction::Rtl);
+    let directions: Vec<_> = book.chapters[0]
+        .content
+        .iter()
+        .map(|c| match &c.content {
+            Some(content::Content::Paragraph(p)) => p.direction(),
+            _ => panic!("expected a paragraph"),
+        })
+        .collect();
+    assert_eq!(
+        directions,
+        [Direction::Unknown, Direction::Ltr, Direction::Ltr]
+    );
+}
+
+#[test]
+fn chapters_without_content() {
+    let html = "<h1>Первая</h1><h1>Вторая</h1><p>Текст</p><h1></h1>";
+    let book = Book::from_html(html, Uuid::new_v4(), &HashMap::new()).unwrap();
+    assert_eq!(book.chapters.len(), 2);
+    assert!(book.chapters[0].content.is_empty());
+    assert!(book.chapters[0].title.is_some());
+}
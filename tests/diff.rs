@@ -0,0 +1,257 @@
+use protobook::{
+    content, span, title_element, Book, Change, ChangeKind, ChangeTarget, Chapter, Container,
+    Content, Date, Direction, Footnote, Footnotes, InlineImage, Locator, Paragraph, Resource, Span,
+    Text, Title, TitleElement,
+};
+
+fn text(value: &str) -> Paragraph {
+    Paragraph {
+        anchor: String::new(),
+        language: String::new(),
+        direction: Direction::Unknown.into(),
+        classes: vec![],
+        content: vec![Span {
+            span: Some(span::Span::Text(Text {
+                value: value.to_string(),
+                ..Default::default()
+            })),
+        }],
+    }
+}
+
+fn paragraph(value: &str) -> Content {
+    Content {
+        content: Some(content::Content::Paragraph(text(value))),
+    }
+}
+
+fn chapter(anchor: &str, title: &str, paragraphs: &[&str]) -> Chapter {
+    Chapter {
+        anchor: anchor.to_string(),
+        title: Some(Title {
+            content: vec![TitleElement {
+                title_element: Some(title_element::TitleElement::Paragraph(text(title))),
+            }],
+        }),
+        content: paragraphs.iter().map(|p| paragraph(p)).collect(),
+        ..Default::default()
+    }
+}
+
+fn note(id: &str, value: &str) -> Footnote {
+    Footnote {
+        title: None,
+        content: vec![paragraph(value)],
+        id: id.to_string(),
+        label: String::new(),
+    }
+}
+
+fn book() -> Book {
+    Book {
+        short_title: "Ёлка".to_string(),
+        cover: Some(InlineImage {
+            id: "cover".to_string(),
+            alt: String::new(),
+        }),
+        chapters: vec![
+            chapter("one", "Первая", &["Раз", "Два", "Три"]),
+            chapter("", "Вторая", &["Четыре"]),
+        ],
+        notes: Some(Footnotes {
+            title: None,
+            content: vec![note("n1", "Сноска")],
+        }),
+        ..Default::default()
+    }
+}
+
+fn at(chapters: &[u32], paragraph: u32) -> Option<Locator> {
+    Some(Locator {
+        chapters: chapters.to_vec(),
+        paragraph,
+        ..Default::default()
+    })
+}
+
+#[test]
+fn same() {
+    assert_eq!(book().diff(&book()).changes, []);
+}
+
+#[test]
+fn changes() {
+    let old = book();
+    let mut new = book();
+    new.short_title = "Ёлочка".to_string();
+    new.cover.as_mut().unwrap().id = "new cover".to_string();
+    new.chapters[0] = chapter("one", "Первая глава", &["Раз", "Два!", "Три", "Пять"]);
+    new.chapters[1].sub_chapters = vec![chapter("", "Подглава", &[])];
+    new.chapters
+        .insert(0, chapter("zero", "Вступление", &["Ноль"]));
+    new.notes
+        .as_mut()
+        .unwrap()
+        .content
+        .push(note("n2", "Ещё сноска"));
+
+    let diff = old.diff(&new);
+    let summary: Vec<_> = diff
+        .changes
+        .iter()
+        .map(|c| (c.kind(), c.target(), c.old.clone(), c.new.clone()))
+        .collect();
+    use ChangeKind::*;
+    use ChangeTarget::*;
+    assert_eq!(
+        summary,
+        [
+            (Modified, Metadata, None, None),
+            (Added, Chapter, None, at(&[0], 0)),
+            (Modified, Chapter, at(&[0], 0), at(&[1], 0)),
+            (Modified, Paragraph, at(&[0], 0), at(&[1], 0)),
+            (Modified, Paragraph, at(&[0], 2), at(&[1], 2)),
+            (Added, Paragraph, None, at(&[1], 4)),
+            (Added, Chapter, None, at(&[2, 0], 0)),
+            (Added, Footnote, None, diff.changes[7].new.clone()),
+            (Removed, Resource, None, None),
+            (Added, Resource, None, None),
+        ]
+    );
+    assert_eq!(diff.changes[7].new.as_ref().unwrap().footnote, "n2");
+    assert_eq!(
+        diff.to_string(),
+        "~ short_title: Ёлка → Ёлочка\n\
+         + chapter 1: Вступление\n\
+         ~ chapter 2: Первая → Первая глава\n\
+         ~ paragraph 2¶1: Первая → Первая глава\n\
+         ~ paragraph 2¶3: Два → Два!\n\
+         + paragraph 2¶5: Пять\n\
+         + chapter 3.1: Подглава\n\
+         + note n2: Ещё сноска\n\
+         - resource cover\n\
+         + resource new cover\n"
+    );
+}
+
+#[test]
+fn date() {
+    let mut old = book();
+    old.date = Date::parse("1936");
+    let mut new = book();
+    new.date = Date::parse("около 05.1936");
+
+    let changes: Vec<_> = old
+        .diff(&new)
+        .changes
+        .into_iter()
+        .map(|c| (c.kind(), c.name, c.old_text, c.new_text))
+        .collect();
+    let change = |kind, name: &str, old: &str, new: &str| {
+        (kind, name.to_string(), old.to_string(), new.to_string())
+    };
+    assert_eq!(
+        changes,
+        [
+            change(ChangeKind::Modified, "date.iso_date", "1936", "1936-05"),
+            change(ChangeKind::Modified, "date.precision", "year", "month"),
+            change(ChangeKind::Added, "date.approximate", "", "true"),
+            change(
+                ChangeKind::Modified,
+                "date.display_date",
+                "1936",
+                "около 05.1936"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn containers() {
+    let resource = |id: &str, content: &[u8]| Resource {
+        id: id.to_string(),
+        content_type: "image/png".to_string(),
+        content: content.to_vec(),
+    };
+    let old = Container {
+        book: Some(Book::default()),
+        resources: vec![resource("a", b"cover"), resource("b", b"image")],
+    };
+    // a book imported again, with new ids
+    let new = Container {
+        book: Some(Book::default()),
+        resources: vec![resource("c", b"cover"), resource("d", b"image")],
+    };
+    assert_eq!(old.diff(&new).changes, []);
+
+    let new = Container {
+        book: Some(Book::default()),
+        resources: vec![resource("a", b"new cover"), resource("e", b"new image")],
+    };
+    let changes: Vec<_> = old
+        .diff(&new)
+        .changes
+        .into_iter()
+        .map(|c: Change| (c.kind(), c.name))
+        .collect();
+    assert_eq!(
+        changes,
+        [
+            (ChangeKind::Removed, "b".to_string()),
+            (ChangeKind::Modified, "a".to_string()),
+            (ChangeKind::Added, "e".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn scattered_paragraphs() {
+    let paragraphs: Vec<_> = (0..200).map(|i| format!("Абзац {i}")).collect();
+    let mut changed = paragraphs.clone();
+    changed[150] = "Изменённый абзац".to_string();
+    changed.insert(120, "Новый абзац".to_string());
+    changed.remove(50);
+    let old: Vec<_> = paragraphs.iter().map(String::as_str).collect();
+    let new: Vec<_> = changed.iter().map(String::as_str).collect();
+    let old = Book {
+        chapters: vec![chapter("one", "Глава", &old)],
+        ..Default::default()
+    };
+    let new = Book {
+        chapters: vec![chapter("one", "Глава", &new)],
+        ..Default::default()
+    };
+
+    let changes: Vec<_> = old
+        .diff(&new)
+        .changes
+        .into_iter()
+        .map(|c| (c.kind(), c.target(), c.old_text, c.new_text))
+        .collect();
+    let modified = (
+        ChangeKind::Modified,
+        ChangeTarget::Chapter,
+        "Глава",
+        "Глава",
+    );
+    assert_eq!(
+        changes,
+        [
+            modified,
+            (ChangeKind::Removed, ChangeTarget::Paragraph, "Абзац 50", ""),
+            (
+                ChangeKind::Added,
+                ChangeTarget::Paragraph,
+                "",
+                "Новый абзац"
+            ),
+            (
+                ChangeKind::Modified,
+                ChangeTarget::Paragraph,
+                "Абзац 150",
+                "Изменённый абзац"
+            ),
+        ]
+        .map(|(kind, target, old, new)| (kind, target, old.to_string(), new.to_string()))
+    );
+}
@@ -0,0 +1,107 @@
+use protobook::{content, span, Book, Chapter, Content, Direction, Locator, Paragraph, Span, Text};
+
+fn paragraph(value: &str, language: &str) -> Content {
+    Content {
+        content: Some(content::Content::Paragraph(Paragraph {
+            content: vec![Span {
+                span: Some(span::Span::Text(Text {
+                    value: value.to_string(),
+                    ..Default::default()
+                })),
+            }],
+            language: language.to_string(),
+            ..Default::default()
+        })),
+    }
+}
+
+fn directions(chapter: &Chapter) -> Vec<Direction> {
+    chapter
+        .content
+        .iter()
+        .map(|c| match &c.content {
+            Some(content::Content::Paragraph(p)) => p.direction(),
+            _ => panic!("expected a paragraph"),
+        })
+        .collect()
+}
+
+fn at(chapters: &[u32], paragraph: u32) -> Locator {
+    Locator {
+        chapters: chapters.to_vec(),
+        paragraph,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn of_language() {
+    let directions = [
+        "he",
+        "ar-EG",
+        "az-Arab",
+        "yi",
+        "he-Latn",
+        "ru",
+        "en-x-hebr",
+        "",
+    ]
+    .map(Direction::of_language);
+    use Direction::*;
+    assert_eq!(directions, [Rtl, Rtl, Rtl, Rtl, Ltr, Ltr, Ltr, Unknown]);
+}
+
+#[test]
+fn assign_directions() {
+    let mut book = Book {
+        language: "ru".to_string(),
+        chapters: vec![
+            Chapter {
+                content: vec![
+                    paragraph("Русский текст", ""),
+                    paragraph("שלום עולם", ""),
+                    paragraph("42", "ar"),
+                ],
+                ..Default::default()
+            },
+            Chapter {
+                language: "he".to_string(),
+                content: vec![paragraph("שלום", ""), paragraph("Привет", "")],
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+    book.assign_directions();
+
+    assert_eq!(book.direction(), Direction::Ltr);
+    assert_eq!(
+        directions(&book.chapters[0]),
+        [Direction::Unknown, Direction::Rtl, Direction::Rtl]
+    );
+    assert_eq!(book.chapters[1].direction(), Direction::Rtl);
+    assert_eq!(
+        directions(&book.chapters[1]),
+        [Direction::Unknown, Direction::Ltr]
+    );
+
+    assert_eq!(book.direction_at(&at(&[0], 0)), Direction::Ltr);
+    assert_eq!(book.direction_at(&at(&[0], 1)), Direction::Rtl);
+    assert_eq!(book.direction_at(&at(&[1], 0)), Direction::Rtl);
+    assert_eq!(book.direction_at(&at(&[1], 1)), Direction::Ltr);
+    assert_eq!(book.direction_at(&at(&[7], 0)), Direction::Ltr);
+}
+
+#[test]
+fn direction_without_assigned_directions() {
+    let book = Book {
+        language: "ar".to_string(),
+        chapters: vec![Chapter {
+            content: vec![paragraph("text", "en")],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    assert_eq!(book.direction_at(&at(&[0], 0)), Direction::Ltr);
+    assert_eq!(book.direction_at(&Locator::default()), Direction::Rtl);
+}
@@ -0,0 +1,127 @@
+use protobook::{Book, ConvertOptions, ImportError, LimitError, Limits, ResourceSink};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, Cursor, Write};
+use std::rc::Rc;
+use uuid::Uuid;
+
+const FB2: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<FictionBook xmlns="http://www.gribuser.ru/xml/fictionbook/2.0" xmlns:l="http://www.w3.org/1999/xlink">
+  <description>
+    <title-info>
+      <genre>prose</genre>
+      <author><first-name>Антон</first-name><last-name>Макаренко</last-name></author>
+      <book-title>Поток</book-title>
+      <annotation><p>Аннотация со <a l:href="#n1" type="note">сноской</a></p></annotation>
+      <lang>ru</lang>
+      <coverpage><image l:href="#cover.png"/></coverpage>
+    </title-info>
+  </description>
+  <body>
+    <title><p>Книга</p></title>
+    <epigraph><p>Эпиграф</p></epigraph>
+    <section id="one">
+      <title><p>Первая глава</p></title>
+      <p>Текст<a l:href="#n1" type="note">1</a> и <a l:href="#c1">комментарий</a></p>
+      <image l:href="#cover.png"/>
+      <section><p>Подглава</p></section>
+    </section>
+    <p>Абзац вне глав</p>
+    <epigraph><p>Эпиграф после глав</p></epigraph>
+    <section id="two">
+      <p>Вторая глава со <a l:href="#extra" type="note">сноской</a></p>
+    </section>
+  </body>
+  <body name="notes">
+    <title><p>Примечания</p></title>
+    <section id="n1"><p>Сноска</p></section>
+  </body>
+  <body name="comments">
+    <section id="c1"><p>Комментарий</p></section>
+  </body>
+  <body name="extra">
+    <section id="extra"><p>Сноска в другом теле</p></section>
+  </body>
+  <binary id="cover.png" content-type="image/png">
+    iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR4nGNgYGD4DwABBAEAwS2O
+    UAAAAABJRU5ErkJggg==
+  </binary>
+</FictionBook>"##;
+
+/// Collects binaries in memory
+#[derive(Default)]
+struct Resources {
+    ids: HashMap<String, Uuid>,
+    contents: Rc<RefCell<HashMap<Uuid, Vec<u8>>>>,
+}
+
+struct Content {
+    id: Uuid,
+    contents: Rc<RefCell<HashMap<Uuid, Vec<u8>>>>,
+}
+
+impl Write for Content {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut contents = self.contents.borrow_mut();
+        contents.entry(self.id).or_default().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl ResourceSink for Resources {
+    type Writer = Content;
+
+    fn create(&mut self, id: &str, _content_type: &str) -> io::Result<(Uuid, Content)> {
+        let uuid = Uuid::new_v4();
+        self.ids.insert(id.to_string(), uuid);
+        let content = Content {
+            id: uuid,
+            contents: self.contents.clone(),
+        };
+        Ok((uuid, content))
+    }
+}
+
+#[test]
+fn same_as_from_fb2() {
+    let mut resources = Resources::default();
+    let book_id = Uuid::new_v4();
+    let options = ConvertOptions::default();
+    let (streamed, report) =
+        Book::from_fb2_reader(Cursor::new(FB2), book_id, &mut resources, &options).unwrap();
+
+    let fb2: fb2::FictionBook = quick_xml::de::from_str(FB2).unwrap();
+    let (book, expected) =
+        Book::from_fb2_with_report(fb2, book_id, &resources.ids, &options).unwrap();
+    assert_eq!(streamed, book);
+    assert_eq!(report, expected);
+    assert!(streamed.cover.is_some());
+}
+
+#[test]
+fn binaries() {
+    let mut resources = Resources::default();
+    let options = ConvertOptions::default();
+    Book::from_fb2_reader(Cursor::new(FB2), Uuid::new_v4(), &mut resources, &options).unwrap();
+
+    let content = resources.contents.borrow()[&resources.ids["cover.png"]].clone();
+    assert_eq!(&content[..8], b"\x89PNG\r\n\x1a\n");
+    assert_eq!(content.len(), 70);
+
+    let options = ConvertOptions {
+        limits: Limits {
+            max_resource_size: 16,
+            ..Limits::default()
+        },
+        ..ConvertOptions::default()
+    };
+    let result = Book::from_fb2_reader(Cursor::new(FB2), Uuid::new_v4(), &mut resources, &options);
+    assert!(matches!(
+        result,
+        Err(ImportError::Limit(LimitError::ResourceSize(id, 16))) if id == "cover.png"
+    ));
+}
@@ -0,0 +1,44 @@
+use prost::Message;
+use protobook::{Book, Chapter, Footnote, Footnotes, Title};
+
+fn chapter(anchor: &str) -> Chapter {
+    Chapter {
+        anchor: anchor.to_string(),
+        ..Default::default()
+    }
+}
+
+fn book() -> Book {
+    Book {
+        chapters: vec![chapter("one"), chapter("two")],
+        notes: Some(Footnotes {
+            title: None,
+            content: (1..100)
+                .map(|i| Footnote {
+                    id: format!("n{i}"),
+                    label: i.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+        }),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn fingerprint() {
+    let (book, mut same) = (book(), book());
+    assert_eq!(book.encode_to_vec(), book.encode_to_vec());
+    assert_eq!(book.fingerprint(), same.fingerprint());
+
+    same.chapters[1].title = Some(Title::default());
+    assert_ne!(book.fingerprint(), same.fingerprint());
+    assert_eq!(
+        book.chapters[0].fingerprint(),
+        same.chapters[0].fingerprint()
+    );
+    assert_ne!(
+        book.chapters[1].fingerprint(),
+        same.chapters[1].fingerprint()
+    );
+}
@@ -0,0 +1,177 @@
+use protobook::{
+    content, link, span, Book, ConvertOptions, Footnote, FootnoteLink, FootnoteType, Limits,
+    Locator, Span,
+};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+const FB2: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<FictionBook xmlns="http://www.gribuser.ru/xml/fictionbook/2.0" xmlns:l="http://www.w3.org/1999/xlink">
+  <description>
+    <title-info>
+      <genre>prose</genre>
+      <book-title>Сноски</book-title>
+      <lang>ru</lang>
+    </title-info>
+  </description>
+  <body>
+    <section>
+      <p>Текст<a l:href="#n2" type="note">*</a> и ещё<a l:href="#n1" type="note">1</a></p>
+    </section>
+  </body>
+  <body name="notes">
+    <section id="n2">
+      <title><p>*</p></title>
+      <p>Первая сноска</p>
+    </section>
+    <section id="n1">
+      <p>Вторая сноска</p>
+    </section>
+  </body>
+</FictionBook>"##;
+
+#[test]
+fn ordered_footnotes() {
+    let fb2: fb2::FictionBook = quick_xml::de::from_str(FB2).unwrap();
+    let book = Book::from_fb2(fb2, Uuid::new_v4(), &HashMap::new()).unwrap();
+    let notes = book.notes.unwrap();
+
+    let footnotes = notes
+        .content
+        .iter()
+        .map(|f| (f.id.as_str(), f.label.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(footnotes, [("n2", "*"), ("n1", "2")]);
+    assert_eq!(notes.get("n1").map(|f| f.label.as_str()), Some("2"));
+    assert_eq!(notes.index()["n1"], 1);
+
+    let paragraph = match &book.chapters[0].content[0].content {
+        Some(content::Content::Paragraph(p)) => p,
+        _ => panic!("expected a paragraph"),
+    };
+    let Some(span::Span::Footnote(link)) = &paragraph.content[1].span else {
+        panic!("expected a footnote link");
+    };
+    assert_eq!(link.id, "n2");
+    assert_eq!(link.r#type(), FootnoteType::Note);
+}
+
+#[test]
+fn footnote_references() {
+    let fb2: fb2::FictionBook = quick_xml::de::from_str(FB2).unwrap();
+    let mut book = Book::from_fb2(fb2, Uuid::new_v4(), &HashMap::new()).unwrap();
+    let Some(content::Content::Paragraph(paragraph)) = &mut book.chapters[0].content[0].content
+    else {
+        panic!("expected a paragraph");
+    };
+    paragraph.content.push(Span {
+        span: Some(span::Span::Footnote(FootnoteLink {
+            id: "n3".to_string(),
+            r#type: FootnoteType::Note.into(),
+            content: vec![],
+        })),
+    });
+    let notes = book.notes.as_mut().unwrap();
+    notes.content.push(Footnote {
+        id: "n4".to_string(),
+        ..Default::default()
+    });
+
+    let locator = |offset| Locator {
+        chapters: vec![0],
+        offset,
+        ..Default::default()
+    };
+    let references = book.footnote_references();
+    assert_eq!(references.links_to(FootnoteType::Note, "n2"), [locator(6)]);
+    assert_eq!(references.links_to(FootnoteType::Note, "n1"), [locator(14)]);
+    assert_eq!(
+        references.unreferenced,
+        [(FootnoteType::Note, "n4".to_string())]
+    );
+    assert_eq!(references.dangling, [locator(15)]);
+}
+
+const FB2_NOTE_TARGETS: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<FictionBook xmlns="http://www.gribuser.ru/xml/fictionbook/2.0" xmlns:l="http://www.w3.org/1999/xlink">
+  <description>
+    <title-info>
+      <genre>prose</genre>
+      <book-title>Сноски</book-title>
+      <lang>ru</lang>
+    </title-info>
+  </description>
+  <body>
+    <section>
+      <p>Текст<a l:href="#n1" type="note">1</a> и<a l:href="#c1" type="note">2</a> ещё<a l:href="#x" type="note">3</a> см.<a l:href="#ch" type="note">4</a></p>
+    </section>
+    <section id="ch">
+      <title><p>Глава</p></title>
+      <section>
+        <p>Подглава</p>
+      </section>
+    </section>
+    <section id="c1">
+      <title><p>2</p></title>
+      <p>Сноска в тексте</p>
+    </section>
+  </body>
+  <body name="Footnotes">
+    <section id="n1">
+      <p>Первая сноска</p>
+    </section>
+  </body>
+</FictionBook>"##;
+
+#[test]
+fn note_link_targets() {
+    let fb2: fb2::FictionBook = quick_xml::de::from_str(FB2_NOTE_TARGETS).unwrap();
+    let book = Book::from_fb2(fb2, Uuid::new_v4(), &HashMap::new()).unwrap();
+    // the chapter with a sub-chapter can't be a note
+    assert_eq!(book.chapters.len(), 2);
+    assert_eq!(book.chapters[1].anchor, "ch");
+
+    let notes = book.notes.as_ref().unwrap();
+    let footnotes = notes
+        .content
+        .iter()
+        .map(|f| (f.id.as_str(), f.label.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(footnotes, [("n1", "1"), ("c1", "2")]);
+
+    let Some(content::Content::Paragraph(paragraph)) = &book.chapters[0].content[0].content else {
+        panic!("expected a paragraph");
+    };
+    let links = paragraph
+        .content
+        .iter()
+        .filter_map(|s| match &s.span {
+            Some(span::Span::Footnote(link)) => Some(link.id.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(links, ["n1", "c1"]);
+    let Some(span::Span::Link(link)) = &paragraph.content.last().unwrap().span else {
+        panic!("expected a link");
+    };
+    assert_eq!(link.href, Some(link::Href::Local("ch".to_string())));
+    assert!(book.footnote_references().dangling.is_empty());
+}
+
+#[test]
+fn empty_footnotes_within_limits() {
+    let fb2 = FB2.replace(
+        r#"<body name="notes">"#,
+        r#"<body name="notes"><section id="n0"><p/></section>"#,
+    );
+    let fb2: fb2::FictionBook = quick_xml::de::from_str(&fb2).unwrap();
+    let options = ConvertOptions {
+        limits: Limits {
+            max_footnotes: 2,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let book = Book::from_fb2_with_options(fb2, Uuid::new_v4(), &HashMap::new(), &options);
+    assert_eq!(book.unwrap().notes.unwrap().content.len(), 2);
+}
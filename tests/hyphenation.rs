@@ -0,0 +1,100 @@
+use protobook::{
+    content, span, Book, Chapter, Content, Direction, HyphenationOptions, Paragraph, Span, Text,
+};
+use std::collections::HashMap;
+
+fn chapter(anchor: &str, value: &str) -> Chapter {
+    Chapter {
+        anchor: anchor.to_string(),
+        content: vec![Content {
+            content: Some(content::Content::Paragraph(Paragraph {
+                anchor: String::new(),
+                language: String::new(),
+                direction: Direction::Unknown.into(),
+                classes: vec![],
+                content: vec![Span {
+                    span: Some(span::Span::Text(Text {
+                        value: value.to_string(),
+                        ..Default::default()
+                    })),
+                }],
+            })),
+        }],
+        ..Default::default()
+    }
+}
+
+fn text(chapter: &Chapter) -> &str {
+    let Some(content::Content::Paragraph(paragraph)) = &chapter.content[0].content else {
+        panic!("expected a paragraph");
+    };
+    let Some(span::Span::Text(text)) = &paragraph.content[0].span else {
+        panic!("expected text");
+    };
+    &text.value
+}
+
+#[test]
+fn hyphenate() {
+    let mut book = Book {
+        language: "ru".to_string(),
+        chapters: vec![
+            chapter("ru", "Педагогическая поэма, кот"),
+            chapter("en", "extensive hyphenation"),
+        ],
+        ..Default::default()
+    };
+    let options = HyphenationOptions {
+        chapter_languages: HashMap::from([("en".to_string(), "en-GB".to_string())]),
+        ..Default::default()
+    };
+    book.hyphenate(&options);
+
+    assert_eq!(
+        text(&book.chapters[0]),
+        "Пе\u{ad}да\u{ad}го\u{ad}ги\u{ad}че\u{ad}ская по\u{ad}э\u{ad}ма, кот"
+    );
+    assert_eq!(
+        text(&book.chapters[1]),
+        "ex\u{ad}ten\u{ad}sive hy\u{ad}phen\u{ad}ation"
+    );
+
+    let hyphenated = book.clone();
+    book.hyphenate(&options);
+    assert_eq!(book, hyphenated);
+}
+
+#[test]
+fn languages_of_elements() {
+    let mut english = chapter("", "extensive hyphenation");
+    english.language = "en".to_string();
+    let mut quote = chapter("", "Педагогическая ");
+    let Some(content::Content::Paragraph(paragraph)) = &mut quote.content[0].content else {
+        panic!("expected a paragraph");
+    };
+    paragraph.content.push(Span {
+        span: Some(span::Span::Text(Text {
+            value: "hyphenation".to_string(),
+            language: "en".to_string(),
+            ..Default::default()
+        })),
+    });
+    let mut book = Book {
+        language: "ru".to_string(),
+        chapters: vec![english, quote],
+        ..Default::default()
+    };
+    book.hyphenate(&HyphenationOptions::default());
+
+    assert_eq!(
+        text(&book.chapters[0]),
+        "ex\u{ad}ten\u{ad}sive hy\u{ad}phen\u{ad}ation"
+    );
+    let Some(content::Content::Paragraph(paragraph)) = &book.chapters[1].content[0].content else {
+        panic!("expected a paragraph");
+    };
+    let Some(span::Span::Text(english)) = &paragraph.content[1].span else {
+        panic!("expected text");
+    };
+    assert_eq!(english.value, "hy\u{ad}phen\u{ad}ation");
+}
@@ -0,0 +1,82 @@
+use protobook::{content, span, Book, Locator};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+const FB2: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<FictionBook xmlns="http://www.gribuser.ru/xml/fictionbook/2.0" xmlns:l="http://www.w3.org/1999/xlink">
+  <description>
+    <title-info>
+      <genre>poetry</genre>
+      <book-title>Переводы</book-title>
+      <lang>ru</lang>
+    </title-info>
+  </description>
+  <body>
+    <section>
+      <p>Как сказал поэт: <emphasis xml:lang="en">to be, or not to be</emphasis></p>
+      <cite xml:lang="de">
+        <p>Ich weiß nicht, was soll es bedeuten</p>
+        <p xml:lang="ru">Не знаю, что значит такое</p>
+      </cite>
+    </section>
+    <section xml:lang="fr">
+      <poem>
+        <stanza>
+          <v>Il pleure dans mon cœur</v>
+          <v>Comme il pleut sur la ville</v>
+        </stanza>
+      </poem>
+    </section>
+  </body>
+</FictionBook>"##;
+
+fn at(chapters: &[u32], paragraph: u32, offset: u32) -> Locator {
+    Locator {
+        chapters: chapters.to_vec(),
+        paragraph,
+        offset,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn from_fb2() {
+    let fb2: fb2::FictionBook = quick_xml::de::from_str(FB2).unwrap();
+    let book = Book::from_fb2(fb2, Uuid::new_v4(), &HashMap::new()).unwrap();
+    assert_eq!(book.language, "ru");
+    assert_eq!(book.chapters[0].language, "");
+    assert_eq!(book.chapters[1].language, "fr");
+
+    let Some(content::Content::Paragraph(paragraph)) = &book.chapters[0].content[0].content else {
+        panic!("expected a paragraph");
+    };
+    let languages: Vec<_> = paragraph
+        .content
+        .iter()
+        .map(|s| match &s.span {
+            Some(span::Span::Text(t)) => t.language.as_str(),
+            _ => panic!("expected a text"),
+        })
+        .collect();
+    assert_eq!(languages, ["", "en"]);
+    let Some(content::Content::Cite(cite)) = &book.chapters[0].content[1].content else {
+        panic!("expected a cite");
+    };
+    assert_eq!(cite.language, "de");
+}
+
+#[test]
+fn language_at() {
+    let fb2: fb2::FictionBook = quick_xml::de::from_str(FB2).unwrap();
+    let book = Book::from_fb2(fb2, Uuid::new_v4(), &HashMap::new()).unwrap();
+
+    assert_eq!(book.language_at(&at(&[0], 0, 0)), "ru");
+    assert_eq!(book.language_at(&at(&[0], 0, 17)), "en");
+    // the end of a paragraph is in the language of its last text
+    assert_eq!(book.language_at(&at(&[0], 0, 36)), "en");
+    assert_eq!(book.language_at(&at(&[0], 1, 0)), "de");
+    assert_eq!(book.language_at(&at(&[0], 2, 0)), "ru");
+    assert_eq!(book.language_at(&at(&[1], 1, 5)), "fr");
+    assert_eq!(book.language_at(&at(&[5], 0, 0)), "ru");
+    assert_eq!(book.language_at(&Locator::default()), "ru");
+}
@@ -0,0 +1,158 @@
+use protobook::{
+    content, poem_element, span, Book, Chapter, Content, Direction, FixedAdvance, Layout, Locator,
+    Margins, Paragraph, Poem, PoemElement, Span, Stanza, Table, TableCell, TableRow, Text,
+    Viewport,
+};
+
+fn paragraph(value: &str) -> Paragraph {
+    Paragraph {
+        anchor: String::new(),
+        language: String::new(),
+        direction: Direction::Unknown.into(),
+        classes: vec![],
+        content: vec![Span {
+            span: Some(span::Span::Text(Text {
+                value: value.to_string(),
+                ..Default::default()
+            })),
+        }],
+    }
+}
+
+fn locator(paragraph: u32, offset: u32) -> Locator {
+    Locator {
+        chapters: vec![0],
+        paragraph,
+        offset,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn paginate() {
+    // three lines of ten characters per page
+    let layout = Layout::new(
+        Viewport {
+            width: 12.0,
+            height: 5.0,
+        },
+        Margins {
+            top: 1.0,
+            right: 1.0,
+            bottom: 1.0,
+            left: 1.0,
+        },
+        FixedAdvance {
+            advance: 1.0,
+            line_height: 1.0,
+        },
+    );
+    let stanza = Stanza {
+        title: None,
+        subtitle: None,
+        content: vec![paragraph("one"), paragraph("two"), paragraph("three")],
+    };
+    let chapter = Chapter {
+        content: vec![
+            Content {
+                content: Some(content::Content::Paragraph(paragraph(
+                    "The quick brown fox jumps over the lazy dog",
+                ))),
+            },
+            Content {
+                content: Some(content::Content::Poem(Poem {
+                    content: vec![PoemElement {
+                        poem_element: Some(poem_element::PoemElement::Stanza(stanza)),
+                    }],
+                    ..Default::default()
+                })),
+            },
+        ],
+        ..Default::default()
+    };
+    let pages = layout.paginate(&chapter, &[0]);
+
+    assert_eq!(pages.len(), 3);
+    assert_eq!(pages[0].start, locator(0, 0));
+    assert_eq!(pages[0].end, locator(0, 31));
+    assert_eq!(pages[1].start, locator(0, 31));
+    assert_eq!(pages[1].end, locator(0, 43));
+    // the stanza doesn't fit after the last two lines of the paragraph
+    assert_eq!(pages[2].start, locator(1, 0));
+    assert_eq!(pages[2].end, locator(3, 5));
+
+    let book = Book {
+        chapters: vec![chapter],
+        ..Default::default()
+    };
+    assert_eq!(layout.estimate_pages(&book), 3);
+}
+
+#[test]
+fn margins_wider_than_viewport() {
+    let layout = Layout::new(
+        Viewport {
+            width: 10.0,
+            height: 10.0,
+        },
+        Margins {
+            left: 6.0,
+            right: 6.0,
+            ..Default::default()
+        },
+        FixedAdvance {
+            advance: 1.0,
+            line_height: 1.0,
+        },
+    );
+    let chapter = Chapter {
+        content: vec![Content {
+            content: Some(content::Content::Paragraph(paragraph("ab cd"))),
+        }],
+        ..Default::default()
+    };
+    // a character on each line, or two for the space ending the line
+    let pages = layout.paginate(&chapter, &[0]);
+    assert_eq!(pages.len(), 1);
+    assert_eq!(pages[0].end, locator(0, 5));
+}
+
+#[test]
+fn table_height() {
+    let mut layout = Layout::new(
+        Viewport {
+            width: 10.0,
+            height: 4.0,
+        },
+        Margins::default(),
+        FixedAdvance {
+            advance: 1.0,
+            line_height: 1.0,
+        },
+    );
+    layout.block_spacing = 1.0;
+    let row = |value: &str| TableRow {
+        cells: vec![TableCell {
+            anchor: String::new(),
+            content: paragraph(value).content,
+        }],
+    };
+    let chapter = Chapter {
+        content: vec![
+            Content {
+                content: Some(content::Content::Paragraph(paragraph("one"))),
+            },
+            Content {
+                content: Some(content::Content::Table(Table {
+                    rows: vec![row("two"), row("three")],
+                    ..Default::default()
+                })),
+            },
+        ],
+        ..Default::default()
+    };
+    // the table is spaced from the paragraph, but its rows aren't spaced from each other
+    let pages = layout.paginate(&chapter, &[0]);
+    assert_eq!(pages.len(), 1);
+    assert_eq!(pages[0].end, locator(2, 5));
+}
@@ -0,0 +1,106 @@
+use prost::Message;
+use protobook::{
+    span, Book, Chapter, Content, Direction, LimitError, Limits, Paragraph, Span, Text,
+};
+
+fn nested(depth: usize) -> Chapter {
+    let mut chapter = Chapter::default();
+    for _ in 1..depth {
+        chapter = Chapter {
+            sub_chapters: vec![chapter],
+            ..Default::default()
+        };
+    }
+    chapter
+}
+
+fn paragraph(text: &str) -> Content {
+    Content {
+        content: Some(protobook::content::Content::Paragraph(Paragraph {
+            anchor: String::new(),
+            language: String::new(),
+            direction: Direction::Unknown.into(),
+            classes: vec![],
+            content: vec![Span {
+                span: Some(span::Span::Text(Text {
+                    value: text.to_string(),
+                    ..Default::default()
+                })),
+            }],
+        })),
+    }
+}
+
+#[test]
+fn decode_checked() {
+    let limits = Limits {
+        max_depth: 8,
+        max_text_length: 16,
+        ..Default::default()
+    };
+    let book = Book {
+        chapters: vec![nested(8)],
+        ..Default::default()
+    };
+    let decoded = Book::decode_checked(&book.encode_to_vec(), &limits).unwrap();
+    assert_eq!(decoded, book);
+
+    let deep = Book {
+        chapters: vec![nested(9)],
+        ..Default::default()
+    };
+    assert!(matches!(
+        Book::decode_checked(&deep.encode_to_vec(), &limits),
+        Err(LimitError::Depth(8))
+    ));
+
+    let long = Book {
+        chapters: vec![Chapter {
+            content: vec![paragraph("текст длиннее шестнадцати байт")],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    assert!(matches!(
+        Book::decode_checked(&long.encode_to_vec(), &limits),
+        Err(LimitError::TextLength(16))
+    ));
+}
+
+#[test]
+fn decode_errors() {
+    let limits = Limits {
+        max_resource_size: 4,
+        ..Default::default()
+    };
+    let book = Book {
+        id: "book".to_string(),
+        ..Default::default()
+    };
+    assert!(matches!(
+        Book::decode_checked(&book.encode_to_vec(), &limits),
+        Err(LimitError::ResourceSize(id, 4)) if id.is_empty()
+    ));
+    assert!(matches!(
+        Book::decode_checked(&[0xff], &Limits::default()),
+        Err(LimitError::Decode(_))
+    ));
+}
+
+#[test]
+fn counts() {
+    let book = Book {
+        chapters: vec![Chapter::default(), nested(2)],
+        ..Default::default()
+    };
+    let limits = Limits {
+        max_chapters: 2,
+        ..Default::default()
+    };
+    assert!(matches!(limits.check(&book), Err(LimitError::Chapters(2))));
+    let limits = Limits {
+        max_chapters: 3,
+        ..Default::default()
+    };
+    assert!(limits.check(&book).is_ok());
+}
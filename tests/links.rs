@@ -0,0 +1,88 @@
+use protobook::{
+    content, link, span, Book, ConvertOptions, LinkRejection, Paragraph, RejectedLink,
+};
+use std::collections::HashMap;
+use url::Url;
+use uuid::Uuid;
+
+const HTML: &str = r#"<html lang="ru">
+<body>
+  <p><a href="https://пример.рф/путь к файлу?q=да">Пример</a>
+  <a href="javascript:alert(1)">скрипт</a>
+  <a href="../about.html">О нас</a>
+  <a href="MAILTO:someone@example.com">почта</a></p>
+</body>
+</html>"#;
+
+fn paragraph(book: &Book) -> &Paragraph {
+    let Some(content::Content::Paragraph(paragraph)) = &book.chapters[0].content[0].content else {
+        panic!("expected a paragraph");
+    };
+    paragraph
+}
+
+fn hrefs(paragraph: &Paragraph) -> Vec<&str> {
+    paragraph
+        .content
+        .iter()
+        .filter_map(|s| match &s.span {
+            Some(span::Span::Link(l)) => l.href.as_ref().map(link::Href::as_ref),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn rejected_links() {
+    let (book, report) = Book::from_html_with_report(
+        HTML,
+        Uuid::new_v4(),
+        &HashMap::new(),
+        &ConvertOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        hrefs(paragraph(&book)),
+        [
+            "https://xn--e1afmkfd.xn--p1ai/%D0%BF%D1%83%D1%82%D1%8C%20%D0%BA%20%D1%84%D0%B0%D0%B9%D0%BB%D1%83?q=%D0%B4%D0%B0",
+            "mailto:someone@example.com",
+        ]
+    );
+    assert_eq!(
+        report.rejected_links,
+        [
+            RejectedLink {
+                href: "javascript:alert(1)".to_string(),
+                text: "скрипт".to_string(),
+                reason: LinkRejection::Scheme("javascript".to_string()),
+            },
+            RejectedLink {
+                href: "../about.html".to_string(),
+                text: "О нас".to_string(),
+                reason: LinkRejection::Relative,
+            },
+        ]
+    );
+    let text = paragraph(&book)
+        .content
+        .iter()
+        .filter_map(|s| match &s.span {
+            Some(span::Span::Text(t)) => Some(t.value.as_str()),
+            _ => None,
+        })
+        .collect::<String>();
+    assert!(text.contains("скрипт"));
+    assert!(text.contains("О нас"));
+}
+
+#[test]
+fn relative_links() {
+    let options = ConvertOptions {
+        base_url: Some(Url::parse("https://example.com/blog/post.html").unwrap()),
+        ..Default::default()
+    };
+    let (book, report) =
+        Book::from_html_with_report(HTML, Uuid::new_v4(), &HashMap::new(), &options).unwrap();
+    assert!(hrefs(paragraph(&book)).contains(&"https://example.com/about.html"));
+    assert_eq!(report.rejected_links.len(), 1);
+}
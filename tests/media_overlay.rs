@@ -0,0 +1,92 @@
+use protobook::{AudioClip, MediaOverlay, OverlayError};
+
+fn clip(anchor: &str, audio: &str, start: u64, end: u64) -> AudioClip {
+    AudioClip {
+        anchor: anchor.to_string(),
+        audio: audio.to_string(),
+        start,
+        end,
+    }
+}
+
+#[test]
+fn from_smil() {
+    let smil = r#"<?xml version="1.0" encoding="UTF-8"?>
+<smil xmlns="http://www.w3.org/ns/SMIL" xmlns:epub="http://www.idpf.org/2007/ops" version="3.0">
+  <body>
+    <seq epub:textref="chapter1.xhtml" epub:type="chapter">
+      <par id="par1">
+        <text src="chapter1.xhtml#p1"/>
+        <audio src="audio/chapter1.mp3" clipBegin="0:00:00.000" clipEnd="0:00:02.500"/>
+      </par>
+      <par id="par2">
+        <text src="chapter1.xhtml#p2"/>
+        <audio src="audio/chapter1.mp3" clipBegin="2.5s" clipEnd="4500ms"/>
+      </par>
+      <par id="par3">
+        <text src="chapter2.xhtml#p3"/>
+        <audio src="audio/chapter2.mp3" clipBegin="0s"/>
+      </par>
+    </seq>
+  </body>
+</smil>"#;
+    let overlay = MediaOverlay::from_smil(smil).unwrap();
+    assert_eq!(
+        overlay.clips,
+        [
+            clip("p1", "audio/chapter1.mp3", 0, 2500),
+            clip("p2", "audio/chapter1.mp3", 2500, 4500),
+            clip("p3", "audio/chapter2.mp3", 0, u64::MAX),
+        ]
+    );
+
+    assert_eq!(
+        overlay
+            .clip_at("audio/chapter1.mp3", 2500)
+            .map(|c| c.anchor.as_str()),
+        Some("p2")
+    );
+    assert_eq!(overlay.clip_at("audio/chapter1.mp3", 4500), None);
+    // the last clip lasts until the end of its audio file
+    assert_eq!(
+        overlay
+            .clip_at("audio/chapter2.mp3", 3_600_000)
+            .map(|c| c.anchor.as_str()),
+        Some("p3")
+    );
+    assert_eq!(overlay.clip_of("p2").map(|c| c.start), Some(2500));
+}
+
+#[test]
+fn from_alignment() {
+    let json = r#"{"fragments": [
+        {"id": "s-0-0-0", "begin": "0.000", "end": "1.250", "lines": ["One."]},
+        {"id": "s-0-0-1", "begin": 1.25, "end": 3, "lines": ["Two."]}
+    ]}"#;
+    let expected = [
+        clip("s-0-0-0", "book.mp3", 0, 1250),
+        clip("s-0-0-1", "book.mp3", 1250, 3000),
+    ];
+    assert_eq!(
+        MediaOverlay::from_json(json, "book.mp3").unwrap().clips,
+        expected
+    );
+
+    let csv = "id,begin,end,text\ns-0-0-0,0.000,1.250,\"One.\"\ns-0-0-1,1.250,3.000,\"Two.\"\n";
+    assert_eq!(
+        MediaOverlay::from_csv(csv, "book.mp3").unwrap().clips,
+        expected
+    );
+
+    assert!(MediaOverlay::from_csv("s-0-0-0,0.000\n", "book.mp3").is_err());
+    let csv = "\nid,begin,end\ns-0-0-0,0.000,1.250\ns-0-0-1,x,3.000\n";
+    assert!(matches!(
+        MediaOverlay::from_csv(csv, "book.mp3"),
+        Err(OverlayError::Time(_))
+    ));
+    let json = r#"{"fragments": [{"id": "s-0-0-0", "begin": -1.5, "end": 0}]}"#;
+    assert!(matches!(
+        MediaOverlay::from_json(json, "book.mp3"),
+        Err(OverlayError::Time(_))
+    ));
+}
@@ -0,0 +1,98 @@
+use protobook::{
+    content, span, Book, Chapter, Content, Direction, FontStyle, Link, Paragraph, Span, Text,
+};
+
+fn text(value: &str) -> Text {
+    Text {
+        value: value.to_string(),
+        ..Default::default()
+    }
+}
+
+fn italic(value: &str) -> Text {
+    let mut text = text(value);
+    text.set_font_style(FontStyle::Italic);
+    text
+}
+
+fn english(value: &str) -> Text {
+    Text {
+        language: "en".to_string(),
+        ..text(value)
+    }
+}
+
+fn book(spans: Vec<span::Span>) -> Book {
+    Book {
+        chapters: vec![Chapter {
+            content: vec![Content {
+                content: Some(content::Content::Paragraph(Paragraph {
+                    anchor: String::new(),
+                    language: String::new(),
+                    direction: Direction::Unknown.into(),
+                    classes: vec![],
+                    content: spans.into_iter().map(|s| Span { span: Some(s) }).collect(),
+                })),
+            }],
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn merge_text_spans() {
+    let mut merged = book(vec![
+        span::Span::Text(text("a ")),
+        span::Span::Text(text("b")),
+        span::Span::Text(text(" c")),
+        span::Span::Text(italic("d")),
+        span::Span::Link(Link {
+            href: None,
+            content: vec![italic("e"), italic("f"), text("g")],
+        }),
+        span::Span::Text(italic("h")),
+        span::Span::Text(text("i")),
+        span::Span::Text(english("j")),
+    ]);
+    merged.merge_text_spans();
+
+    let expected = book(vec![
+        span::Span::Text(text("a b c")),
+        span::Span::Text(italic("d")),
+        span::Span::Link(Link {
+            href: None,
+            content: vec![italic("ef"), text("g")],
+        }),
+        span::Span::Text(italic("h")),
+        span::Span::Text(text("i")),
+        span::Span::Text(english("j")),
+    ]);
+    assert_eq!(merged, expected);
+}
+
+#[test]
+fn collapse_whitespace() {
+    let mut code = text("  a\n  b ");
+    code.set_font_style(FontStyle::Code);
+    let mut collapsed = book(vec![
+        span::Span::Text(text("\n  one \t")),
+        span::Span::Text(italic(" two ")),
+        span::Span::Text(text("\n")),
+        span::Span::Text(code.clone()),
+        span::Span::Text(text(" three\n ")),
+        span::Span::Link(Link {
+            href: None,
+            content: vec![text("  ")],
+        }),
+    ]);
+    collapsed.collapse_whitespace();
+
+    let expected = book(vec![
+        span::Span::Text(text("one ")),
+        span::Span::Text(italic("two ")),
+        span::Span::Text(code),
+        span::Span::Text(text(" three")),
+    ]);
+    assert_eq!(collapsed, expected);
+}
@@ -0,0 +1,228 @@
+use prost::Message;
+use protobook::{
+    content, patch_operation, span, Book, Chapter, Content, Date, Direction, FontStyle, Footnote,
+    FootnoteType, Footnotes, InsertContent, Locator, Paragraph, Patch, PatchError, PatchOperation,
+    RemoveContent, ReplaceText, SetMetadata, Span, Text,
+};
+
+fn text(value: &str, font_style: Option<FontStyle>) -> Span {
+    Span {
+        span: Some(span::Span::Text(Text {
+            value: value.to_string(),
+            font_style: font_style.map(Into::into),
+            ..Default::default()
+        })),
+    }
+}
+
+fn paragraph(spans: Vec<Span>) -> Content {
+    Content {
+        content: Some(content::Content::Paragraph(Paragraph {
+            anchor: String::new(),
+            language: String::new(),
+            direction: Direction::Unknown.into(),
+            classes: vec![],
+            content: spans,
+        })),
+    }
+}
+
+fn book() -> Book {
+    Book {
+        short_title: "Опечатки".to_string(),
+        date: Some(Date {
+            iso_date: "1935".to_string(),
+            display_date: "1935".to_string(),
+            ..Default::default()
+        }),
+        chapters: vec![Chapter {
+            content: vec![
+                paragraph(vec![
+                    text("Превет, ", None),
+                    text("мир", Some(FontStyle::Italic)),
+                ]),
+                paragraph(vec![text("Второй абзац", None)]),
+            ],
+            ..Default::default()
+        }],
+        notes: Some(Footnotes {
+            title: None,
+            content: vec![Footnote {
+                title: None,
+                content: vec![paragraph(vec![text("Сноска с ашибкой", None)])],
+                id: "n1".to_string(),
+                label: "1".to_string(),
+            }],
+        }),
+        ..Default::default()
+    }
+}
+
+fn replace(start: Locator, old_text: &str, new_text: &str) -> PatchOperation {
+    PatchOperation {
+        operation: Some(patch_operation::Operation::ReplaceText(ReplaceText {
+            start: Some(start),
+            length: old_text.chars().count() as u32,
+            old_text: old_text.to_string(),
+            new_text: new_text.to_string(),
+        })),
+    }
+}
+
+fn at(chapters: &[u32], paragraph: u32, offset: u32) -> Locator {
+    Locator {
+        chapters: chapters.to_vec(),
+        paragraph,
+        offset,
+        ..Default::default()
+    }
+}
+
+fn spans(book: &Book, i: usize) -> Vec<Span> {
+    match &book.chapters[0].content[i].content {
+        Some(content::Content::Paragraph(p)) => p.content.clone(),
+        _ => panic!("not a paragraph"),
+    }
+}
+
+#[test]
+fn replace_text() {
+    let book = book();
+    let note = Locator {
+        footnote: "n1".to_string(),
+        footnote_type: FootnoteType::Note.into(),
+        offset: 9,
+        ..Default::default()
+    };
+    let operations = vec![
+        replace(at(&[0], 0, 1), "ре", "ри"),
+        replace(note, "ашибкой", "ошибкой"),
+    ];
+    let patch = Patch::new(&book, operations).unwrap();
+    let patch = Patch::decode(patch.encode_to_vec().as_slice()).unwrap();
+
+    let patched = patch.apply(&book).unwrap();
+    assert_eq!(
+        spans(&patched, 0),
+        [text("Привет, ", None), text("мир", Some(FontStyle::Italic))]
+    );
+    let note = &patched.notes.as_ref().unwrap().content[0].content[0];
+    assert_eq!(*note, paragraph(vec![text("Сноска с ошибкой", None)]));
+    assert_eq!(patch.apply(&patched), Err(PatchError::Fingerprint));
+
+    let inverse = patch.invert(&book).unwrap();
+    assert_eq!(inverse.apply(&patched).unwrap(), book);
+}
+
+#[test]
+fn replace_across_spans() {
+    let book = book();
+    let patch = Patch::new(&book, vec![replace(at(&[0], 0, 6), ", мир", "!")]).unwrap();
+    let patched = patch.apply(&book).unwrap();
+    assert_eq!(spans(&patched, 0), [text("Превет!", None)]);
+
+    // the italic text can't be restored
+    assert_eq!(patch.invert(&book), Err(PatchError::Irreversible));
+
+    let image = Span {
+        span: Some(span::Span::Image(Default::default())),
+    };
+    let mut with_image = book.clone();
+    with_image.chapters[0].content[0] = paragraph(vec![text("До", None), image]);
+    let operations = vec![replace(at(&[0], 0, 1), "о\u{FFFC}", "")];
+    assert_eq!(
+        Patch::new(&with_image, operations),
+        Err(PatchError::Mismatch(0))
+    );
+}
+
+#[test]
+fn blocks_and_metadata() {
+    let book = book();
+    let chapter = Locator {
+        chapters: vec![0],
+        ..Default::default()
+    };
+    let operations = vec![
+        PatchOperation {
+            operation: Some(patch_operation::Operation::RemoveContent(RemoveContent {
+                target: Some(chapter.clone()),
+                index: 1,
+                content: Some(book.chapters[0].content[1].clone()),
+            })),
+        },
+        PatchOperation {
+            operation: Some(patch_operation::Operation::InsertContent(InsertContent {
+                target: Some(chapter.clone()),
+                index: 0,
+                content: Some(paragraph(vec![text("Первый абзац", None)])),
+            })),
+        },
+        PatchOperation {
+            operation: Some(patch_operation::Operation::SetMetadata(SetMetadata {
+                field: "short_title".to_string(),
+                old_value: "Опечатки".to_string(),
+                new_value: "Без опечаток".to_string(),
+            })),
+        },
+    ];
+    let patch = Patch::new(&book, operations.clone()).unwrap();
+    let patched = patch.apply(&book).unwrap();
+    assert_eq!(patched.short_title, "Без опечаток");
+    assert_eq!(
+        patched.chapters[0].content,
+        [
+            paragraph(vec![text("Первый абзац", None)]),
+            book.chapters[0].content[0].clone(),
+        ]
+    );
+    assert_eq!(patch.invert(&book).unwrap().apply(&patched).unwrap(), book);
+
+    // the second block was removed, so it's not there for the same operations again
+    assert_eq!(
+        Patch::new(&patched, operations[..1].to_vec()),
+        Err(PatchError::Mismatch(0))
+    );
+    let missing = PatchOperation {
+        operation: Some(patch_operation::Operation::InsertContent(InsertContent {
+            target: Some(Locator {
+                chapters: vec![3],
+                ..Default::default()
+            }),
+            index: 0,
+            content: None,
+        })),
+    };
+    assert_eq!(Patch::new(&book, vec![missing]), Err(PatchError::Target(0)));
+}
+
+#[test]
+fn date() {
+    let book = book();
+    let set = |field: &str, old_value: &str, new_value: &str| PatchOperation {
+        operation: Some(patch_operation::Operation::SetMetadata(SetMetadata {
+            field: field.to_string(),
+            old_value: old_value.to_string(),
+            new_value: new_value.to_string(),
+        })),
+    };
+    let operations = vec![
+        set("date.iso_end_date", "", "1937"),
+        set("date.precision", "", "year"),
+        set("date.approximate", "", "true"),
+        set("date.display_date", "1935", "около 1935–1937"),
+    ];
+    let patch = Patch::new(&book, operations).unwrap();
+    let patched = patch.apply(&book).unwrap();
+    assert_eq!(patched.date, Date::parse("около 1935–1937"));
+    assert_eq!(patch.invert(&book).unwrap().apply(&patched).unwrap(), book);
+
+    assert_eq!(
+        Patch::new(&book, vec![set("date.precision", "", "week")]),
+        Err(PatchError::Mismatch(0))
+    );
+    assert_eq!(
+        Patch::new(&book, vec![set("authors", "", "Автор")]),
+        Err(PatchError::Target(0))
+    );
+}
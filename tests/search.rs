@@ -0,0 +1,99 @@
+use prost::Message;
+use protobook::{
+    content, span, Book, Chapter, Content, Direction, Footnote, FootnoteType, Footnotes, Locator,
+    Paragraph, SearchHit, SearchIndex, Span, Text,
+};
+
+fn paragraph(spans: &[&str]) -> Content {
+    Content {
+        content: Some(content::Content::Paragraph(Paragraph {
+            anchor: String::new(),
+            language: String::new(),
+            direction: Direction::Unknown.into(),
+            classes: vec![],
+            content: spans
+                .iter()
+                .map(|value| Span {
+                    span: Some(span::Span::Text(Text {
+                        value: value.to_string(),
+                        ..Default::default()
+                    })),
+                })
+                .collect(),
+        })),
+    }
+}
+
+fn book() -> Book {
+    Book {
+        chapters: vec![Chapter {
+            content: vec![
+                paragraph(&["Ёлка стоит в лесу"]),
+                paragraph(&["В лесу родилась ", "ёлочка, в лесу она росла"]),
+            ],
+            ..Default::default()
+        }],
+        notes: Some(Footnotes {
+            title: None,
+            content: vec![Footnote {
+                title: None,
+                content: vec![paragraph(&["Песня о ёлке"])],
+                id: "n1".to_string(),
+                label: "1".to_string(),
+            }],
+        }),
+        ..Default::default()
+    }
+}
+
+fn locator(paragraph: u32, offset: u32) -> Locator {
+    Locator {
+        chapters: vec![0],
+        paragraph,
+        offset,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn search() {
+    let book = book();
+    let index = SearchIndex::new(&book);
+    let index = SearchIndex::decode(index.encode_to_vec().as_slice()).unwrap();
+
+    let hits = index.search(&book, "в ЛЕСУ");
+    assert_eq!(
+        hits.iter().map(|h| (&h.start, &h.end)).collect::<Vec<_>>(),
+        [
+            (&locator(0, 11), &locator(0, 17)),
+            (&locator(1, 0), &locator(1, 6)),
+            (&locator(1, 24), &locator(1, 30)),
+        ]
+    );
+
+    let hits = index.search(&book, "елочка");
+    assert_eq!(
+        hits,
+        [SearchHit {
+            start: locator(1, 16),
+            end: locator(1, 22),
+            snippet: "В лесу родилась ёлочка, в лесу она росла".to_string(),
+            snippet_range: 16..22,
+        }]
+    );
+
+    let hits = index.search(&book, "ёлке");
+    assert_eq!(hits.len(), 1);
+    assert_eq!(
+        hits[0].start,
+        Locator {
+            footnote: "n1".to_string(),
+            footnote_type: FootnoteType::Note.into(),
+            paragraph: 0,
+            offset: 8,
+            ..Default::default()
+        }
+    );
+
+    assert!(index.search(&book, "лесу в").is_empty());
+}
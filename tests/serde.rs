@@ -0,0 +1,61 @@
+use protobook::{
+    content, link, span, Book, Chapter, Content, Direction, FontStyle, Link, Paragraph, Span,
+    Statistics, Text,
+};
+
+#[test]
+fn json_round_trip() {
+    let mut italic = Text {
+        value: "глава".to_string(),
+        ..Default::default()
+    };
+    italic.set_font_style(FontStyle::Italic);
+    let book = Book {
+        short_title: "Книга".to_string(),
+        chapters: vec![Chapter {
+            content: vec![Content {
+                content: Some(content::Content::Paragraph(Paragraph {
+                    anchor: String::new(),
+                    language: String::new(),
+                    direction: Direction::Unknown.into(),
+                    classes: vec![],
+                    content: vec![
+                        Span {
+                            span: Some(span::Span::Text(italic)),
+                        },
+                        Span {
+                            span: Some(span::Span::Link(Link {
+                                href: Some(link::Href::Remote("https://example.com".to_string())),
+                                content: vec![],
+                            })),
+                        },
+                    ],
+                })),
+            }],
+            ..Default::default()
+        }],
+        statistics: Some(Statistics {
+            words: 1,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    let json = serde_json::to_value(&book).unwrap();
+    let expected = serde_json::json!({
+        "shortTitle": "Книга",
+        "chapters": [{
+            "content": [{
+                "paragraph": {
+                    "content": [
+                        {"text": {"value": "глава", "fontStyle": "FONT_STYLE_ITALIC"}},
+                        {"link": {"remote": "https://example.com"}},
+                    ],
+                },
+            }],
+        }],
+        "statistics": {"words": "1"},
+    });
+    assert_eq!(json, expected);
+    assert_eq!(serde_json::from_value::<Book>(json).unwrap(), book);
+}
@@ -0,0 +1,123 @@
+use protobook::{
+    content, span, Book, Chapter, Content, Direction, EmptyLine, FontStyle, Locator, Paragraph,
+    Sentence, Span, SpeechOptions, Text,
+};
+
+fn text(value: &str, font_style: FontStyle) -> Span {
+    let mut text = Text {
+        value: value.to_string(),
+        ..Default::default()
+    };
+    if font_style != FontStyle::Unknown {
+        text.set_font_style(font_style);
+    }
+    Span {
+        span: Some(span::Span::Text(text)),
+    }
+}
+
+fn book() -> Book {
+    Book {
+        language: "en".to_string(),
+        chapters: vec![Chapter {
+            content: vec![
+                Content {
+                    content: Some(content::Content::Paragraph(Paragraph {
+                        anchor: String::new(),
+                        language: String::new(),
+                        direction: Direction::Unknown.into(),
+                        classes: vec![],
+                        content: vec![
+                            text("Mr. Smith met J. R. R. Tolkien. ", FontStyle::Unknown),
+                            text("Really", FontStyle::Italic),
+                            text("? Yes & no.", FontStyle::Unknown),
+                        ],
+                    })),
+                },
+                Content {
+                    content: Some(content::Content::EmptyLine(EmptyLine {})),
+                },
+            ],
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+fn locator(offset: u32) -> Locator {
+    Locator {
+        chapters: vec![0],
+        offset,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn sentences() {
+    let sentences = book().sentences(&SpeechOptions::default());
+    assert_eq!(
+        sentences,
+        [
+            Sentence {
+                id: "s-0-0-0".to_string(),
+                start: locator(0),
+                end: locator(31),
+                text: "Mr. Smith met J. R. R. Tolkien.".to_string(),
+            },
+            Sentence {
+                id: "s-0-0-1".to_string(),
+                start: locator(32),
+                end: locator(39),
+                text: "Really?".to_string(),
+            },
+            Sentence {
+                id: "s-0-0-2".to_string(),
+                start: locator(40),
+                end: locator(49),
+                text: "Yes & no.".to_string(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn to_ssml() {
+    let ssml = book().to_ssml(&SpeechOptions::default());
+    assert_eq!(
+        ssml,
+        concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<speak version=\"1.1\" xmlns=\"http://www.w3.org/2001/10/synthesis\" xml:lang=\"en\">\n",
+            "<p><s><mark name=\"s-0-0-0\"/>Mr. Smith met J. R. R. Tolkien.</s>",
+            "<s><mark name=\"s-0-0-1\"/><emphasis level=\"moderate\">Really</emphasis>?</s>",
+            "<s><mark name=\"s-0-0-2\"/>Yes &amp; no.</s></p>\n",
+            "<break strength=\"strong\"/>\n",
+            "</speak>\n",
+        )
+    );
+}
+
+#[test]
+fn languages() {
+    let english = Span {
+        span: Some(span::Span::Text(Text {
+            value: "the end".to_string(),
+            language: "en".to_string(),
+            ..Default::default()
+        })),
+    };
+    let mut book = book();
+    book.chapters[0].content.truncate(1);
+    book.chapters[0].language = "fr".to_string();
+    let Some(content::Content::Paragraph(paragraph)) = &mut book.chapters[0].content[0].content
+    else {
+        panic!("expected a paragraph");
+    };
+    paragraph.content = vec![text("C'est ", FontStyle::Unknown), english];
+
+    let ssml = book.to_ssml(&SpeechOptions::default());
+    assert!(ssml.contains(concat!(
+        "<p xml:lang=\"fr\"><s><mark name=\"s-0-0-0\"/>C'est ",
+        "<lang xml:lang=\"en\">the end</lang></s></p>"
+    )));
+}
@@ -0,0 +1,65 @@
+use protobook::{
+    content, span, Book, Chapter, Content, Direction, InlineImage, Paragraph, ReadingSpeed, Span,
+    Statistics, Text,
+};
+
+fn text(value: &str) -> span::Span {
+    span::Span::Text(Text {
+        value: value.to_string(),
+        ..Default::default()
+    })
+}
+
+fn chapter(spans: Vec<span::Span>, sub_chapters: Vec<Chapter>) -> Chapter {
+    Chapter {
+        content: vec![Content {
+            content: Some(content::Content::Paragraph(Paragraph {
+                anchor: String::new(),
+                language: String::new(),
+                direction: Direction::Unknown.into(),
+                classes: vec![],
+                content: spans.into_iter().map(|s| Span { span: Some(s) }).collect(),
+            })),
+        }],
+        sub_chapters,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn store_statistics() {
+    let image = span::Span::Image(InlineImage::default());
+    let mut book = Book {
+        language: "ru".to_string(),
+        chapters: vec![chapter(
+            vec![text("Раз, два, т"), text("ри."), image],
+            vec![chapter(vec![text("Четыре пять")], vec![])],
+        )],
+        ..Default::default()
+    };
+    let mut speed = ReadingSpeed::default();
+    speed.languages.insert("ru".to_string(), 60);
+    book.store_statistics(&speed);
+
+    let sub_chapter = Statistics {
+        words: 2,
+        characters: 11,
+        paragraphs: 1,
+        reading_time: 2,
+        ..Default::default()
+    };
+    let chapter = Statistics {
+        words: 5,
+        characters: 25,
+        paragraphs: 2,
+        images: 1,
+        reading_time: 5,
+        ..Default::default()
+    };
+    assert_eq!(
+        book.chapters[0].sub_chapters[0].statistics,
+        Some(sub_chapter)
+    );
+    assert_eq!(book.chapters[0].statistics, Some(chapter));
+    assert_eq!(book.statistics, Some(chapter));
+}
@@ -0,0 +1,35 @@
+use protobook::{
+    content, span, Book, Chapter, Content, Direction, Paragraph, SearchIndex, Span, Text,
+};
+
+#[test]
+fn search_stems() {
+    let book = Book {
+        language: "ru".to_string(),
+        chapters: vec![Chapter {
+            content: vec![Content {
+                content: Some(content::Content::Paragraph(Paragraph {
+                    anchor: String::new(),
+                    language: String::new(),
+                    direction: Direction::Unknown.into(),
+                    classes: vec![],
+                    content: vec![Span {
+                        span: Some(span::Span::Text(Text {
+                            value: "Ночь, улица, фонарь, аптека".to_string(),
+                            ..Default::default()
+                        })),
+                    }],
+                })),
+            }],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let index = SearchIndex::with_stemming(&book);
+    assert_eq!(index.stemming_language, "ru");
+
+    let hits = index.search(&book, "улицы");
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].start.offset, 6);
+    assert_eq!(hits[0].end.offset, 11);
+}
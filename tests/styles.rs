@@ -0,0 +1,102 @@
+use protobook::{content, span, Book, ConvertOptions, FontStyle, Paragraph, Text, TextStyle};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+const FB2: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<FictionBook xmlns="http://www.gribuser.ru/xml/fictionbook/2.0" xmlns:l="http://www.w3.org/1999/xlink">
+  <description>
+    <title-info>
+      <genre>prose</genre>
+      <book-title>Письма</book-title>
+      <lang>ru</lang>
+    </title-info>
+  </description>
+  <body>
+    <section>
+      <p style="letter">Дорогой <style name="smallcaps">друг</style>!</p>
+      <p style="letter italic">Твой <style name="sign"><style name="smallcaps">Иван</style></style></p>
+      <p><style name="italic">Постскриптум</style></p>
+      <p><style name="light">Тише, <strong>громче</strong></style></p>
+      <p style="light"><strong>Громче</strong>, тише</p>
+    </section>
+  </body>
+</FictionBook>"##;
+
+fn paragraphs(options: &ConvertOptions) -> Vec<Paragraph> {
+    let fb2: fb2::FictionBook = quick_xml::de::from_str(FB2).unwrap();
+    let book = Book::from_fb2_with_options(fb2, Uuid::new_v4(), &HashMap::new(), options).unwrap();
+    book.chapters[0]
+        .content
+        .iter()
+        .map(|c| match &c.content {
+            Some(content::Content::Paragraph(p)) => p.clone(),
+            _ => panic!("expected a paragraph"),
+        })
+        .collect()
+}
+
+fn texts(paragraph: &Paragraph) -> Vec<Text> {
+    paragraph
+        .content
+        .iter()
+        .map(|s| match &s.span {
+            Some(span::Span::Text(t)) => t.clone(),
+            _ => panic!("expected a text"),
+        })
+        .collect()
+}
+
+#[test]
+fn classes() {
+    let paragraphs = paragraphs(&ConvertOptions::default());
+    assert_eq!(paragraphs[0].classes, ["letter"]);
+    assert_eq!(paragraphs[1].classes, ["letter", "italic"]);
+
+    let classes: Vec<_> = texts(&paragraphs[0])
+        .into_iter()
+        .map(|t| (t.value, t.classes))
+        .collect();
+    assert_eq!(
+        classes,
+        [
+            ("Дорогой ".to_string(), vec![]),
+            ("друг".to_string(), vec!["smallcaps".to_string()]),
+            ("!".to_string(), vec![]),
+        ]
+    );
+    assert_eq!(texts(&paragraphs[1])[1].classes, ["sign", "smallcaps"]);
+    assert_eq!(texts(&paragraphs[2])[0].classes, ["italic"]);
+}
+
+#[test]
+fn style_map() {
+    let italic = TextStyle {
+        font_style: Some(FontStyle::Italic),
+        ..Default::default()
+    };
+    let light = TextStyle {
+        font_weight: Some(300),
+        ..Default::default()
+    };
+    let options = ConvertOptions {
+        style_map: HashMap::from([("italic".to_string(), italic), ("light".to_string(), light)]),
+        ..Default::default()
+    };
+    let paragraphs = paragraphs(&options);
+
+    assert_eq!(paragraphs[1].classes, ["letter"]);
+    let texts_1 = texts(&paragraphs[1]);
+    assert!(texts_1.iter().all(|t| t.font_style() == FontStyle::Italic));
+    assert_eq!(texts_1[1].classes, ["sign", "smallcaps"]);
+
+    let postscript = &texts(&paragraphs[2])[0];
+    assert_eq!(postscript.font_style(), FontStyle::Italic);
+    assert!(postscript.classes.is_empty());
+
+    // inner elements keep their attributes
+    for paragraph in &paragraphs[3..] {
+        let weights: Vec<_> = texts(paragraph).iter().map(|t| t.font_weight).collect();
+        assert!(weights.contains(&Some(300)));
+        assert!(weights.contains(&Some(600)));
+    }
+}
@@ -0,0 +1,74 @@
+use protobook::{
+    content, span, Book, Chapter, Content, Direction, FontStyle, Paragraph, Span, Text,
+};
+
+fn text(value: &str) -> Text {
+    Text {
+        value: value.to_string(),
+        ..Default::default()
+    }
+}
+
+fn book(language: &str, spans: Vec<Text>) -> Book {
+    Book {
+        language: language.to_string(),
+        chapters: vec![Chapter {
+            content: vec![Content {
+                content: Some(content::Content::Paragraph(Paragraph {
+                    anchor: String::new(),
+                    language: String::new(),
+                    direction: Direction::Unknown.into(),
+                    classes: vec![],
+                    content: spans
+                        .into_iter()
+                        .map(|t| Span {
+                            span: Some(span::Span::Text(t)),
+                        })
+                        .collect(),
+                })),
+            }],
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn russian_typography() {
+    let mut code = text("\"x\" - y");
+    code.set_font_style(FontStyle::Code);
+    let mut book = book(
+        "ru-RU",
+        vec![
+            text("- Он сказал: \"Это "),
+            text("\"книга\""),
+            text("\" - и ушёл в лес..."),
+            code.clone(),
+        ],
+    );
+    book.apply_typography();
+
+    let expected = self::book(
+        "ru-RU",
+        vec![
+            text("—\u{a0}Он\u{a0}сказал: «Это "),
+            text("„книга“"),
+            text("»\u{a0}— и\u{a0}ушёл в\u{a0}лес…"),
+            code,
+        ],
+    );
+    assert_eq!(book, expected);
+}
+
+#[test]
+fn english_typography() {
+    let mut book = book("en", vec![text("\"It's 'fine'\" - he said")]);
+    book.apply_typography();
+
+    let expected = self::book("en", vec![text("“It’s ‘fine’” — he said")]);
+    assert_eq!(book, expected);
+
+    let mut book = self::book("en", vec![text("Well.."), text(". Fine")]);
+    book.apply_typography();
+    assert_eq!(book, self::book("en", vec![text("Well…"), text(" Fine")]));
+}